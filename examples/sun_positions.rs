@@ -12,18 +12,19 @@ fn main() {
         }
     };
 
+    // Using placeholder coordinates (Montreal, Canada as example)
+    let latitude = 45.5017;
+    let longitude = -73.5673;
+
+    // Time steps centered on local solar noon (in UTC)
     let generator = DateTimeGenerator::new(config.clone());
-    let datetime_series = generator.generate_datetime_series();
+    let datetime_series = generator.generate_datetime_series_local(longitude as f64);
 
     for dt in datetime_series {
         // Extract Julian day and hour from datetime
         let julian_day = dt.ordinal() as i16;
         let hour = dt.hour() as f32 + (dt.minute() as f32 / 60.0);
 
-        // Using placeholder coordinates (Montreal, Canada as example)
-        let latitude = 45.5017;
-        let longitude = -73.5673;
-
         let sun_position = SolarPosition::calculate(julian_day, hour, latitude, longitude);
 
         println!(
@@ -1,6 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Bbox {
     pub xmin: f64,
     pub xmax: f64,
@@ -18,6 +18,12 @@ impl Bbox {
             return Err("Latitude values must be between -90 and 90".to_string());
         }
 
+        Self::new_projected(xmin, xmax, ymin, ymax)
+    }
+
+    /// Same as `new` for coordinates in a projected CRS (e.g. metres), where only the order of
+    /// the bounds is checked.
+    pub fn new_projected(xmin: f64, xmax: f64, ymin: f64, ymax: f64) -> Result<Self, String> {
         if xmin > xmax || ymin > ymax {
             return Err("Min values must be <= max values".to_string());
         }
@@ -29,6 +35,36 @@ impl Bbox {
             ymax,
         })
     }
+
+    /// Extent of a `width` x `height` raster with a geographic (lon/lat) geotransform. The four
+    /// corners are computed with the full affine transform, so north-up grids with a negative
+    /// pixel height as well as rotated grids are supported.
+    pub fn from_geotransform(
+        geotransform: &[f64; 6],
+        width: u32,
+        height: u32,
+    ) -> Result<Self, String> {
+        let corner = |px: f64, py: f64| {
+            (
+                geotransform[0] + px * geotransform[1] + py * geotransform[2],
+                geotransform[3] + px * geotransform[4] + py * geotransform[5],
+            )
+        };
+
+        let (width, height) = (width as f64, height as f64);
+        let corners = [
+            corner(0.0, 0.0),
+            corner(width, 0.0),
+            corner(0.0, height),
+            corner(width, height),
+        ];
+
+        let (xs, ys): (Vec<f64>, Vec<f64>) = corners.into_iter().unzip();
+        let min = |values: &[f64]| values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = |values: &[f64]| values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        Bbox::new(min(&xs), max(&xs), min(&ys), max(&ys))
+    }
 }
 
 #[cfg(test)]
@@ -60,5 +96,32 @@ mod test {
 
         let invalid_order_lat = Bbox::new(0.0, 10.0, 10.0, 0.0);
         assert!(invalid_order_lat.is_err());
+
+        // Projected coordinates are only checked for order
+        assert!(Bbox::new_projected(-2_000_000.0, -1_000_000.0, 500_000.0, 900_000.0).is_ok());
+        assert!(Bbox::new_projected(1_000_000.0, -1_000_000.0, 500_000.0, 900_000.0).is_err());
+    }
+
+    #[test]
+    fn test_bbox_from_geotransform() {
+        // 0.25 degree grid, north-up with a negative pixel height
+        let geotransform = [-70.0, 0.25, 0.0, 75.0, 0.0, -0.25];
+
+        let bbox = Bbox::from_geotransform(&geotransform, 40, 20).unwrap();
+        assert_eq!(bbox.xmin, -70.0);
+        assert_eq!(bbox.xmax, -60.0);
+        assert_eq!(bbox.ymin, 70.0);
+        assert_eq!(bbox.ymax, 75.0);
+
+        // Global 1 degree grid
+        let bbox = Bbox::from_geotransform(&[-180.0, 1.0, 0.0, 90.0, 0.0, -1.0], 360, 180).unwrap();
+        assert_eq!(
+            (bbox.xmin, bbox.xmax, bbox.ymin, bbox.ymax),
+            (-180.0, 180.0, -90.0, 90.0)
+        );
+
+        // Projected coordinates are not a valid lon/lat extent
+        let polar = [-3_850_000.0, 25_000.0, 0.0, 5_850_000.0, 0.0, -25_000.0];
+        assert!(Bbox::from_geotransform(&polar, 304, 448).is_err());
     }
 }
@@ -0,0 +1,280 @@
+use chrono::NaiveDate;
+
+use crate::bbox::Bbox;
+use crate::config::{
+    ChlaSource, Config, ConfigError, DEFAULT_OUTPUT_FILENAME_PATTERN, OutputFormat, PpUnit,
+    RasterFile, TimeStep,
+};
+use crate::models::vgpm::DEFAULT_MAX_PP;
+use crate::sat_bands::Satellites;
+
+/// Builds a `Config` in code. `build` runs the same validation as loading a JSON config.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigBuilder {
+    model_id: Option<String>,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+    frequency: Option<TimeStep>,
+    hourly_increment: Option<u8>,
+    minute_increment: Option<u16>,
+    bbox: Option<Bbox>,
+    raster_templates: Vec<RasterFile>,
+    output_directory: Option<String>,
+    allow_partial: bool,
+    output_format: OutputFormat,
+    output_nodata: Option<f64>,
+    skip_existing: bool,
+    overwrite: Option<bool>,
+    output_filename_pattern: Option<String>,
+    bathymetry_file: Option<String>,
+    min_water_depth: f64,
+    l2_flags: Option<Vec<String>>,
+    chla_source: ChlaSource,
+    satellite: Satellites,
+    max_pp: Option<f32>,
+    output_unit: PpUnit,
+    create_output_dir: bool,
+    bbox_crs: Option<String>,
+    max_threads: Option<usize>,
+    min_valid_days: Option<u32>,
+    align_grids: bool,
+    reference_grid: Option<String>,
+}
+
+impl ConfigBuilder {
+    pub fn model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = Some(model_id.into());
+        self
+    }
+
+    pub fn start_date(mut self, start_date: NaiveDate) -> Self {
+        self.start_date = Some(start_date);
+        self
+    }
+
+    pub fn end_date(mut self, end_date: NaiveDate) -> Self {
+        self.end_date = Some(end_date);
+        self
+    }
+
+    pub fn frequency(mut self, frequency: TimeStep) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    pub fn hourly_increment(mut self, hourly_increment: u8) -> Self {
+        self.hourly_increment = Some(hourly_increment);
+        self
+    }
+
+    pub fn minute_increment(mut self, minute_increment: u16) -> Self {
+        self.minute_increment = Some(minute_increment);
+        self
+    }
+
+    pub fn bbox(mut self, bbox: Bbox) -> Self {
+        self.bbox = Some(bbox);
+        self
+    }
+
+    pub fn raster_templates(mut self, raster_templates: Vec<RasterFile>) -> Self {
+        self.raster_templates = raster_templates;
+        self
+    }
+
+    pub fn raster_template(mut self, raster_template: RasterFile) -> Self {
+        self.raster_templates.push(raster_template);
+        self
+    }
+
+    pub fn output_directory(mut self, output_directory: impl Into<String>) -> Self {
+        self.output_directory = Some(output_directory.into());
+        self
+    }
+
+    pub fn allow_partial(mut self, allow_partial: bool) -> Self {
+        self.allow_partial = allow_partial;
+        self
+    }
+
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    pub fn output_nodata(mut self, output_nodata: f64) -> Self {
+        self.output_nodata = Some(output_nodata);
+        self
+    }
+
+    pub fn skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = Some(overwrite);
+        self
+    }
+
+    pub fn output_filename_pattern(mut self, output_filename_pattern: impl Into<String>) -> Self {
+        self.output_filename_pattern = Some(output_filename_pattern.into());
+        self
+    }
+
+    pub fn bathymetry_file(mut self, bathymetry_file: impl Into<String>) -> Self {
+        self.bathymetry_file = Some(bathymetry_file.into());
+        self
+    }
+
+    pub fn min_water_depth(mut self, min_water_depth: f64) -> Self {
+        self.min_water_depth = min_water_depth;
+        self
+    }
+
+    pub fn l2_flags(mut self, l2_flags: Vec<String>) -> Self {
+        self.l2_flags = Some(l2_flags);
+        self
+    }
+
+    pub fn chla_source(mut self, chla_source: ChlaSource) -> Self {
+        self.chla_source = chla_source;
+        self
+    }
+
+    pub fn satellite(mut self, satellite: Satellites) -> Self {
+        self.satellite = satellite;
+        self
+    }
+
+    pub fn max_pp(mut self, max_pp: f32) -> Self {
+        self.max_pp = Some(max_pp);
+        self
+    }
+
+    pub fn output_unit(mut self, output_unit: PpUnit) -> Self {
+        self.output_unit = output_unit;
+        self
+    }
+
+    pub fn create_output_dir(mut self, create_output_dir: bool) -> Self {
+        self.create_output_dir = create_output_dir;
+        self
+    }
+
+    pub fn bbox_crs(mut self, bbox_crs: &str) -> Self {
+        self.bbox_crs = Some(bbox_crs.to_string());
+        self
+    }
+
+    pub fn max_threads(mut self, max_threads: usize) -> Self {
+        self.max_threads = Some(max_threads);
+        self
+    }
+
+    pub fn min_valid_days(mut self, min_valid_days: u32) -> Self {
+        self.min_valid_days = Some(min_valid_days);
+        self
+    }
+
+    pub fn align_grids(mut self, align_grids: bool) -> Self {
+        self.align_grids = align_grids;
+        self
+    }
+
+    pub fn reference_grid(mut self, reference_grid: &str) -> Self {
+        self.reference_grid = Some(reference_grid.to_string());
+        self
+    }
+
+    /// Returns `ConfigError::MissingField` if a required field was not set, or the same errors as
+    /// `Config::from_file` if the values are invalid.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let config = Config {
+            model_id: self.model_id.ok_or(ConfigError::MissingField("model_id"))?,
+            start_date: self
+                .start_date
+                .ok_or(ConfigError::MissingField("start_date"))?,
+            end_date: self.end_date.ok_or(ConfigError::MissingField("end_date"))?,
+            frequency: self
+                .frequency
+                .ok_or(ConfigError::MissingField("frequency"))?,
+            hourly_increment: self
+                .hourly_increment
+                .ok_or(ConfigError::MissingField("hourly_increment"))?,
+            minute_increment: self.minute_increment,
+            bbox: self.bbox.ok_or(ConfigError::MissingField("bbox"))?,
+            raster_templates: self.raster_templates,
+            output_directory: self
+                .output_directory
+                .ok_or(ConfigError::MissingField("output_directory"))?,
+            allow_partial: self.allow_partial,
+            output_format: self.output_format,
+            output_nodata: self.output_nodata,
+            skip_existing: self.skip_existing,
+            overwrite: self.overwrite.unwrap_or(true),
+            output_filename_pattern: self
+                .output_filename_pattern
+                .unwrap_or_else(|| DEFAULT_OUTPUT_FILENAME_PATTERN.to_string()),
+            bathymetry_file: self.bathymetry_file,
+            min_water_depth: self.min_water_depth,
+            l2_flags: self.l2_flags,
+            chla_source: self.chla_source,
+            satellite: self.satellite,
+            max_pp: self.max_pp.unwrap_or(DEFAULT_MAX_PP),
+            output_unit: self.output_unit,
+            create_output_dir: self.create_output_dir,
+            bbox_crs: self.bbox_crs,
+            max_threads: self.max_threads,
+            min_valid_days: self.min_valid_days.unwrap_or(1),
+            align_grids: self.align_grids,
+            reference_grid: self.reference_grid,
+        };
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{raster_template, test_builder};
+
+    #[test]
+    fn test_build_valid_config() {
+        let config = test_builder().build().unwrap();
+
+        assert_eq!(config.model_id(), "test_model");
+        assert_eq!(config.hourly_increment(), 6);
+        assert_eq!(config.date_iter().count(), 3);
+        assert_eq!(config.output_format(), OutputFormat::GeoTiff);
+    }
+
+    #[test]
+    fn test_build_missing_field() {
+        let result = Config::builder().model_id("test_model").build();
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::MissingField("start_date"))
+        ));
+    }
+
+    #[test]
+    fn test_build_runs_validation() {
+        let result = test_builder()
+            .start_date(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap())
+            .build();
+        assert!(matches!(result, Err(ConfigError::DateOrder)));
+
+        let result = test_builder().hourly_increment(5).build();
+        assert!(matches!(result, Err(ConfigError::HourlyIncrement)));
+
+        let result = test_builder()
+            .raster_template(raster_template("chlor_a", "chl.tif"))
+            .build();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+}
@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+/// Source of the chlorophyll-a concentration used to compute PP
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChlaSource {
+    /// `chlor_a` input band
+    #[default]
+    Band,
+    /// Chlorophyll derived from the Rrs input bands with QAA v6
+    Qaa,
+}
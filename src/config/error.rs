@@ -10,7 +10,11 @@ pub enum ConfigError {
     Io(std::io::Error),
     Json(serde_json::Error),
     HourlyIncrement,
+    MinuteIncrement(u16),
     OutputDirectory(String),
+    MissingField(&'static str),
+    UnknownOutputFormat(String),
+    Invalid(String),
 }
 
 impl fmt::Display for ConfigError {
@@ -24,13 +28,29 @@ impl fmt::Display for ConfigError {
             ConfigError::HourlyIncrement => {
                 write!(f, "hourly_increment should one of 1, 2, 3, 4, 6, 8, 12")
             }
+            ConfigError::MinuteIncrement(minutes) => {
+                write!(
+                    f,
+                    "minute_increment should be a positive divisor of 1440 (got {})",
+                    minutes
+                )
+            }
             ConfigError::OutputDirectory(path) => {
                 write!(f, "Output directory does not exist: {}", path)
             }
+            ConfigError::MissingField(field) => write!(f, "Missing required field: {}", field),
+            ConfigError::UnknownOutputFormat(format) => write!(
+                f,
+                "Unknown output_format '{}', expected one of gtiff, gpkg, cog",
+                format
+            ),
+            ConfigError::Invalid(message) => write!(f, "{}", message),
         }
     }
 }
 
+impl std::error::Error for ConfigError {}
+
 impl From<std::io::Error> for ConfigError {
     fn from(err: std::io::Error) -> ConfigError {
         ConfigError::Io(err)
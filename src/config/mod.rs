@@ -5,11 +5,16 @@ use serde::Deserialize;
 use serde::Deserializer;
 use serde::de::Error;
 
+use gdal::spatial_ref::SpatialRef;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 
 use crate::bbox::Bbox;
+use crate::iop::flags::{DEFAULT_L2_FLAGS_MASK, mask_from_names};
+use crate::models::vgpm::DEFAULT_MAX_PP;
+use crate::oceanographic_model::processor::{QAA_RRS_BANDS, ValueScaling};
+use crate::sat_bands::Satellites;
 
 pub mod error;
 pub use error::ConfigError;
@@ -17,12 +22,76 @@ pub use error::ConfigError;
 pub mod timestep;
 pub use timestep::TimeStep;
 
+pub mod output_format;
+pub use output_format::OutputFormat;
+
+pub mod chla_source;
+pub use chla_source::ChlaSource;
+
+pub mod pp_unit;
+pub use pp_unit::PpUnit;
+
+pub mod builder;
+pub use builder::ConfigBuilder;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct RasterFile {
     pub name: String,
     pub base_directory: String,
+    // File name with a `{}` date placeholder, or `{start}` and `{end}` placeholders for the first
+    // and last day of composite periods. `*` matches any characters
     pub filename_pattern: String,
     pub date_format: String,
+    // Variable (subdataset) to read from NetCDF files, when it differs from `name`
+    #[serde(default)]
+    pub netcdf_variable: Option<String>,
+    // How many directory levels below `base_directory` are searched for matching files, all of
+    // them if unset
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    // 1-based index of the band to read, e.g. one Rrs band of a multi-band GeoTIFF
+    #[serde(default)]
+    pub band: Option<usize>,
+    // Scale, offset and no-data value overriding the file metadata, when it lacks or misreports
+    // them. Values are decoded as `raw * scale + offset`.
+    #[serde(default)]
+    pub scale: Option<f64>,
+    #[serde(default)]
+    pub offset: Option<f64>,
+    #[serde(default)]
+    pub nodata: Option<f64>,
+}
+
+impl RasterFile {
+    /// Name of the NetCDF variable holding this raster: `netcdf_variable` if set, `name` otherwise
+    pub fn variable_name(&self) -> &str {
+        self.netcdf_variable.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Band read from the matching files: `band` if set, 1 otherwise
+    pub fn band_index(&self) -> usize {
+        self.band.unwrap_or(1)
+    }
+
+    /// Overrides of the file scale, offset and no-data value given by this template
+    pub fn value_scaling(&self) -> ValueScaling {
+        ValueScaling {
+            scale: self.scale,
+            offset: self.offset,
+            nodata: self.nodata,
+        }
+    }
+
+    /// Search depth below `base_directory`: `max_depth`, `None` (unlimited) if unset
+    pub fn search_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Whether `filename_pattern` holds a `{}` placeholder, or both `{start}` and `{end}`
+    pub fn has_date_placeholder(&self) -> bool {
+        let pattern = &self.filename_pattern;
+        pattern.contains("{}") || (pattern.contains("{start}") && pattern.contains("{end}"))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,9 +101,45 @@ pub struct Config {
     end_date: NaiveDate,
     frequency: TimeStep,
     hourly_increment: u8,
+    minute_increment: Option<u16>,
     bbox: Bbox,
     raster_templates: Vec<RasterFile>,
     output_directory: String,
+    allow_partial: bool,
+    output_format: OutputFormat,
+    output_nodata: Option<f64>,
+    skip_existing: bool,
+    overwrite: bool,
+    output_filename_pattern: String,
+    bathymetry_file: Option<String>,
+    min_water_depth: f64,
+    l2_flags: Option<Vec<String>>,
+    chla_source: ChlaSource,
+    satellite: Satellites,
+    max_pp: f32,
+    output_unit: PpUnit,
+    create_output_dir: bool,
+    bbox_crs: Option<String>,
+    max_threads: Option<usize>,
+    min_valid_days: u32,
+    align_grids: bool,
+    reference_grid: Option<String>,
+}
+
+/// Default name of the output rasters. `{model_id}`, `{date}` (YYYYMMDD) and `{ext}` are replaced
+pub const DEFAULT_OUTPUT_FILENAME_PATTERN: &str =
+    "boreas_daily_primary_production_{model_id}_{date}.{ext}";
+
+fn default_overwrite() -> bool {
+    true
+}
+
+fn default_min_valid_days() -> u32 {
+    1
+}
+
+fn default_max_pp() -> f32 {
+    DEFAULT_MAX_PP
 }
 
 // This function deserializes a Config object from a deserializer, ensuring the dates are valid and
@@ -51,9 +156,49 @@ impl<'de> Deserialize<'de> for Config {
             end_date: String,
             frequency: TimeStep,
             hourly_increment: u8,
+            #[serde(default)]
+            minute_increment: Option<u16>,
             raster_templates: Vec<RasterFile>,
             bbox: BboxHelper,
             output_directory: String,
+            #[serde(default)]
+            allow_partial: bool,
+            #[serde(default)]
+            output_format: Option<String>,
+            #[serde(default)]
+            output_nodata: Option<f64>,
+            #[serde(default)]
+            skip_existing: bool,
+            #[serde(default = "default_overwrite")]
+            overwrite: bool,
+            #[serde(default)]
+            output_filename_pattern: Option<String>,
+            #[serde(default)]
+            bathymetry_file: Option<String>,
+            #[serde(default)]
+            min_water_depth: f64,
+            #[serde(default)]
+            l2_flags: Option<Vec<String>>,
+            #[serde(default)]
+            chla_source: ChlaSource,
+            #[serde(default)]
+            satellite: Satellites,
+            #[serde(default = "default_max_pp")]
+            max_pp: f32,
+            #[serde(default)]
+            output_unit: PpUnit,
+            #[serde(default)]
+            create_output_dir: bool,
+            #[serde(default)]
+            bbox_crs: Option<String>,
+            #[serde(default)]
+            max_threads: Option<usize>,
+            #[serde(default = "default_min_valid_days")]
+            min_valid_days: u32,
+            #[serde(default)]
+            align_grids: bool,
+            #[serde(default)]
+            reference_grid: Option<String>,
         }
 
         #[derive(Deserialize)]
@@ -75,75 +220,228 @@ impl<'de> Deserialize<'de> for Config {
         let end_date = NaiveDate::parse_from_str(&helper.end_date, "%Y-%m-%d")
             .map_err(|e| D::Error::custom(format!("Invalid end_date format: {}", e)))?;
 
+        // Unknown formats are reported as ConfigError::UnknownOutputFormat
+        let output_format = helper
+            .output_format
+            .map(|format| format.parse::<OutputFormat>())
+            .transpose()
+            .map_err(D::Error::custom)?
+            .unwrap_or_default();
+
+        let config = Config {
+            model_id: helper.model_id,
+            start_date,
+            end_date,
+            frequency: helper.frequency,
+            hourly_increment: helper.hourly_increment,
+            minute_increment: helper.minute_increment,
+            raster_templates: helper.raster_templates,
+            bbox: Bbox {
+                xmin: helper.bbox.xmin,
+                xmax: helper.bbox.xmax,
+                ymin: helper.bbox.ymin,
+                ymax: helper.bbox.ymax,
+            },
+            output_directory: helper.output_directory,
+            allow_partial: helper.allow_partial,
+            output_format,
+            output_nodata: helper.output_nodata,
+            skip_existing: helper.skip_existing,
+            overwrite: helper.overwrite,
+            output_filename_pattern: helper
+                .output_filename_pattern
+                .unwrap_or_else(|| DEFAULT_OUTPUT_FILENAME_PATTERN.to_string()),
+            bathymetry_file: helper.bathymetry_file,
+            min_water_depth: helper.min_water_depth,
+            l2_flags: helper.l2_flags,
+            chla_source: helper.chla_source,
+            satellite: helper.satellite,
+            max_pp: helper.max_pp,
+            output_unit: helper.output_unit,
+            create_output_dir: helper.create_output_dir,
+            bbox_crs: helper.bbox_crs,
+            max_threads: helper.max_threads,
+            min_valid_days: helper.min_valid_days,
+            align_grids: helper.align_grids,
+            reference_grid: helper.reference_grid,
+        };
+
+        config.validate().map_err(D::Error::custom)?;
+
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// Returns a builder to create a `Config` in code rather than from a JSON file
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    // Checks that the dates are in order, the time increments are valid, the templates and bbox
+    // are well formed and the output directory exists (creating it if `create_output_dir` is set).
+    // Shared by the deserializer and the builder.
+    fn validate(&self) -> Result<(), ConfigError> {
         // Ensure start_date is before end_date
-        if start_date > end_date {
-            return Err(D::Error::custom(ConfigError::DateOrder));
+        if self.start_date > self.end_date {
+            return Err(ConfigError::DateOrder);
         }
 
         // Validate hourly_increment
         let valid_timestep = [1, 2, 3, 4, 6, 8, 12];
-        if !valid_timestep.contains(&helper.hourly_increment) {
-            return Err(D::Error::custom(ConfigError::HourlyIncrement));
+        if !valid_timestep.contains(&self.hourly_increment) {
+            return Err(ConfigError::HourlyIncrement);
+        }
+
+        // Validate minute_increment, if set it must split the day evenly
+        if let Some(minute_increment) = self.minute_increment
+            && (minute_increment == 0 || 1440 % minute_increment != 0)
+        {
+            return Err(ConfigError::MinuteIncrement(minute_increment));
         }
 
         // Validate model_id is not empty
-        if helper.model_id.trim().is_empty() {
-            return Err(D::Error::custom("model_id cannot be empty"));
+        if self.model_id.trim().is_empty() {
+            return Err(ConfigError::Invalid("model_id cannot be empty".to_string()));
         }
 
         // Validate each raster template
-        for template in &helper.raster_templates {
-            if template.name.trim().is_empty() {
-                return Err(D::Error::custom("raster template name cannot be empty"));
-            }
-            if template.base_directory.trim().is_empty() {
-                return Err(D::Error::custom(
-                    "raster template base_directory cannot be empty",
-                ));
-            }
-            if template.filename_pattern.trim().is_empty() {
-                return Err(D::Error::custom(
-                    "raster template filename_pattern cannot be empty",
-                ));
+        for template in &self.raster_templates {
+            let error = if template.name.trim().is_empty() {
+                Some("raster template name cannot be empty")
+            } else if template.base_directory.trim().is_empty() {
+                Some("raster template base_directory cannot be empty")
+            } else if template.filename_pattern.trim().is_empty() {
+                Some("raster template filename_pattern cannot be empty")
+            } else if template.max_depth == Some(0) {
+                Some("raster template max_depth must be at least 1")
+            } else if template.band == Some(0) {
+                Some("raster template band must be at least 1")
+            } else if template
+                .scale
+                .is_some_and(|scale| scale == 0.0 || !scale.is_finite())
+                || template.offset.is_some_and(|offset| !offset.is_finite())
+            {
+                Some("raster template scale and offset must be finite, with a nonzero scale")
+            } else if template.date_format.trim().is_empty() {
+                Some("raster template date_format cannot be empty")
+            } else if !template.has_date_placeholder() {
+                Some(
+                    "raster template filename_pattern must contain '{}' or '{start}' and '{end}' placeholders",
+                )
+            } else {
+                None
+            };
+
+            if let Some(error) = error {
+                return Err(ConfigError::Invalid(error.to_string()));
             }
-            if template.date_format.trim().is_empty() {
-                return Err(D::Error::custom(
-                    "raster template date_format cannot be empty",
+        }
+
+        if self.min_valid_days == 0 {
+            return Err(ConfigError::Invalid(
+                "min_valid_days must be at least 1".to_string(),
+            ));
+        }
+
+        if let Some(reference_grid) = &self.reference_grid {
+            if !self.align_grids {
+                return Err(ConfigError::Invalid(
+                    "reference_grid requires align_grids".to_string(),
                 ));
             }
-            if !template.filename_pattern.contains("{}") {
-                return Err(D::Error::custom(
-                    "raster template filename_pattern must contain '{}' placeholder",
-                ));
+            if !self
+                .raster_templates
+                .iter()
+                .any(|template| &template.name == reference_grid)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "reference_grid '{}' is not the name of a raster template",
+                    reference_grid
+                )));
             }
         }
 
-        // Validate bbox
-        let bbox = Bbox::new(
-            helper.bbox.xmin,
-            helper.bbox.xmax,
-            helper.bbox.ymin,
-            helper.bbox.ymax,
-        )
-        .map_err(|e| D::Error::custom(format!("Invalid bbox: {}", e)))?;
+        if self.max_threads == Some(0) {
+            return Err(ConfigError::Invalid(
+                "max_threads must be at least 1".to_string(),
+            ));
+        }
+
+        // Validate output_filename_pattern, every date needs its own output file
+        if !self.output_filename_pattern.contains("{date}") {
+            return Err(ConfigError::Invalid(
+                "output_filename_pattern must contain '{date}' placeholder".to_string(),
+            ));
+        }
+
+        // Validate the bathymetry mask
+        if self.min_water_depth < 0.0 || !self.min_water_depth.is_finite() {
+            return Err(ConfigError::Invalid(
+                "min_water_depth must be a positive depth in meters".to_string(),
+            ));
+        }
 
-        // Validate output directory exists
-        if !Path::new(&helper.output_directory).exists() {
-            return Err(D::Error::custom(ConfigError::OutputDirectory(
-                helper.output_directory.clone(),
+        if let Some(bathymetry_file) = &self.bathymetry_file
+            && !Path::new(bathymetry_file).exists()
+        {
+            return Err(ConfigError::Invalid(format!(
+                "bathymetry_file does not exist: {}",
+                bathymetry_file
             )));
         }
 
-        Ok(Config {
-            model_id: helper.model_id,
-            start_date,
-            end_date,
-            frequency: helper.frequency,
-            hourly_increment: helper.hourly_increment,
-            raster_templates: helper.raster_templates,
-            bbox,
-            output_directory: helper.output_directory,
-        })
+        // Validate the l2_flags names
+        if let Some(l2_flags) = &self.l2_flags {
+            mask_from_names(l2_flags).map_err(ConfigError::Invalid)?;
+        }
+
+        if self.max_pp <= 0.0 || self.max_pp.is_nan() {
+            return Err(ConfigError::Invalid(
+                "max_pp must be a positive production in mg C m-2 d-1".to_string(),
+            ));
+        }
+
+        // QAA chlorophyll needs every Rrs band
+        if self.chla_source == ChlaSource::Qaa {
+            let missing: Vec<&str> = QAA_RRS_BANDS
+                .iter()
+                .map(|(name, _)| *name)
+                .filter(|name| !self.raster_templates.iter().any(|t| t.name == *name))
+                .collect();
+
+            if !missing.is_empty() {
+                return Err(ConfigError::Invalid(format!(
+                    "chla_source 'qaa' requires raster templates for {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+
+        // Validate bbox. Coordinates in a projected bbox_crs are only checked for order.
+        let geographic = match &self.bbox_crs {
+            Some(bbox_crs) => SpatialRef::from_definition(bbox_crs)
+                .map_err(|e| ConfigError::Invalid(format!("Invalid bbox_crs '{bbox_crs}': {e}")))?
+                .is_geographic(),
+            None => true,
+        };
+        let new_bbox = if geographic {
+            Bbox::new
+        } else {
+            Bbox::new_projected
+        };
+        new_bbox(
+            self.bbox.xmin,
+            self.bbox.xmax,
+            self.bbox.ymin,
+            self.bbox.ymax,
+        )
+        .map_err(|e| ConfigError::Invalid(format!("Invalid bbox: {}", e)))?;
+
+        // Validate output directory exists, creating it if requested
+        self.ensure_output_directory(&self.output_directory)?;
+
+        Ok(())
     }
 }
 
@@ -157,10 +455,35 @@ impl Config {
         Ok(config)
     }
 
+    pub fn start_date(&self) -> NaiveDate {
+        self.start_date
+    }
+
+    pub fn end_date(&self) -> NaiveDate {
+        self.end_date
+    }
+
+    pub fn frequency(&self) -> TimeStep {
+        self.frequency
+    }
+
     pub fn hourly_increment(&self) -> u8 {
         self.hourly_increment
     }
 
+    /// Sub-hourly time step in minutes, if set. It takes precedence over `hourly_increment`.
+    pub fn minute_increment(&self) -> Option<u16> {
+        self.minute_increment
+    }
+
+    /// Time step of the datetime series in minutes
+    pub fn step_minutes(&self) -> u32 {
+        match self.minute_increment {
+            Some(minutes) => minutes as u32,
+            None => self.hourly_increment as u32 * 60,
+        }
+    }
+
     pub fn raster_templates(&self) -> &Vec<RasterFile> {
         &self.raster_templates
     }
@@ -177,6 +500,164 @@ impl Config {
         &self.model_id
     }
 
+    /// Whether dates with missing input files are skipped instead of failing the whole batch
+    pub fn allow_partial(&self) -> bool {
+        self.allow_partial
+    }
+
+    /// File format of the daily output rasters (GeoTIFF by default)
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    /// No-data value of the output rasters. `None` means `NaN`.
+    pub fn output_nodata(&self) -> Option<f64> {
+        self.output_nodata
+    }
+
+    /// Whether dates whose output file already exists are skipped instead of recomputed
+    pub fn skip_existing(&self) -> bool {
+        self.skip_existing
+    }
+
+    /// Whether existing output files may be replaced (the default). When false, processing fails
+    /// on the first existing output file, unless `skip_existing` is set.
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Name of the output rasters, with `{model_id}`, `{date}` and `{ext}` placeholders
+    pub fn output_filename_pattern(&self) -> &str {
+        &self.output_filename_pattern
+    }
+
+    /// Output file name of `date`, built from `output_filename_pattern`
+    pub fn output_filename(&self, date: &NaiveDate) -> String {
+        self.output_filename_for(&date.format("%Y%m%d").to_string())
+    }
+
+    /// Output file name built from `output_filename_pattern`, with `label` as `{date}`. Used for
+    /// outputs covering several dates, e.g. `mean_20230101_20230131`.
+    pub fn output_filename_for(&self, label: &str) -> String {
+        self.output_filename_pattern
+            .replace("{model_id}", &self.model_id)
+            .replace("{date}", label)
+            .replace("{ext}", self.output_format.extension())
+    }
+
+    /// Bathymetry raster (elevation in meters, negative below sea level) on the grid of the
+    /// inputs, used to skip land and shallow pixels
+    pub fn bathymetry_file(&self) -> Option<&str> {
+        self.bathymetry_file.as_deref()
+    }
+
+    /// Minimum water depth (m) of the pixels where PP is computed when `bathymetry_file` is set.
+    /// Defaults to 0, i.e. only pixels above sea level are skipped.
+    pub fn min_water_depth(&self) -> f64 {
+        self.min_water_depth
+    }
+
+    /// Bits of the `l2_flags` input band that exclude a pixel, from the `l2_flags` flag names.
+    /// Defaults to `DEFAULT_L2_FLAGS_MASK` (land, cloud/ice, glint, failed retrievals).
+    pub fn l2_flags_mask(&self) -> u32 {
+        match &self.l2_flags {
+            Some(names) => mask_from_names(names).unwrap_or(DEFAULT_L2_FLAGS_MASK),
+            None => DEFAULT_L2_FLAGS_MASK,
+        }
+    }
+
+    /// Source of the chlorophyll used to compute PP: the `chlor_a` band (default) or QAA applied
+    /// to the Rrs bands
+    pub fn chla_source(&self) -> ChlaSource {
+        self.chla_source
+    }
+
+    /// Sensor of the Rrs bands, which sets the wavelengths QAA runs at when `chla_source` is
+    /// `qaa`. The Rrs templates keep their nominal names (rrs_412, ..., rrs_670) and are matched
+    /// to the closest sensor band. Defaults to SeaWiFS.
+    pub fn satellite(&self) -> Satellites {
+        self.satellite
+    }
+
+    /// Largest PP (mg C m^-2 d^-1) considered valid, higher values are written as no-data.
+    /// Defaults to `DEFAULT_MAX_PP` (2000).
+    pub fn max_pp(&self) -> f32 {
+        self.max_pp
+    }
+
+    /// Unit of the PP written to the output rasters (mg C m-2 d-1 by default)
+    pub fn output_unit(&self) -> PpUnit {
+        self.output_unit
+    }
+
+    /// CRS of `bbox` (any definition accepted by GDAL, e.g. "EPSG:3413"), `None` for lon/lat
+    /// (EPSG:4326). The bbox is reprojected from this CRS to the CRS of the input rasters, so a
+    /// projected bbox can select a rectangular window of a projected (e.g. polar stereographic)
+    /// grid, and the lon/lat range check only applies to geographic CRSs.
+    pub fn bbox_crs(&self) -> Option<&str> {
+        self.bbox_crs.as_deref()
+    }
+
+    /// Largest number of dates processed in parallel, `None` to use all the CPU cores
+    pub fn max_threads(&self) -> Option<usize> {
+        self.max_threads
+    }
+
+    /// Fewest valid days for a pixel of a period mean (`process_mean`, `process_climatology`) to
+    /// be output, no-data otherwise. Defaults to 1.
+    pub fn min_valid_days(&self) -> u32 {
+        self.min_valid_days
+    }
+
+    /// Whether the inputs are resampled onto a common grid when their sizes, geotransforms or
+    /// CRSs differ (see `OceanographicProcessor::with_aligned_grids`)
+    pub fn align_grids(&self) -> bool {
+        self.align_grids
+    }
+
+    /// Name of the raster template whose grid the inputs are resampled onto when `align_grids` is
+    /// set, `None` for the finest input grid
+    pub fn reference_grid(&self) -> Option<&str> {
+        self.reference_grid.as_deref()
+    }
+
+    /// Whether a missing output directory is created instead of rejected
+    pub fn create_output_dir(&self) -> bool {
+        self.create_output_dir
+    }
+
+    /// Overrides the output directory, e.g. from the command line. The directory must exist,
+    /// unless `create_output_dir` is set.
+    pub fn set_output_directory(&mut self, output_directory: String) -> Result<(), ConfigError> {
+        self.ensure_output_directory(&output_directory)?;
+
+        self.output_directory = output_directory;
+        Ok(())
+    }
+
+    // Creates the output directory if it is missing and `create_output_dir` is set, fails with
+    // `ConfigError::OutputDirectory` otherwise
+    fn ensure_output_directory(&self, output_directory: &str) -> Result<(), ConfigError> {
+        if Path::new(output_directory).exists() {
+            return Ok(());
+        }
+
+        if !self.create_output_dir {
+            return Err(ConfigError::OutputDirectory(output_directory.to_string()));
+        }
+
+        std::fs::create_dir_all(output_directory)?;
+        Ok(())
+    }
+
+    /// Iterates over the dates of the period without consuming or modifying the config
+    pub fn date_iter(&self) -> DateIter<'_> {
+        DateIter {
+            config: self,
+            next_date: Some(self.start_date),
+        }
+    }
+
     fn increment_date(&self, current_date: NaiveDate) -> Result<NaiveDate, String> {
         match self.frequency {
             TimeStep::Daily => Ok(current_date + Duration::days(1)),
@@ -188,6 +669,25 @@ impl Config {
     }
 }
 
+/// Borrowing iterator over the dates of a `Config`, see `Config::date_iter`
+pub struct DateIter<'a> {
+    config: &'a Config,
+    next_date: Option<NaiveDate>,
+}
+
+impl Iterator for DateIter<'_> {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current_date = self
+            .next_date
+            .filter(|date| *date <= self.config.end_date)?;
+        self.next_date = self.config.increment_date(current_date).ok();
+        Some(current_date)
+    }
+}
+
+// Consumes the config: iterating moves start_date forward. Prefer `Config::date_iter`.
 impl Iterator for Config {
     type Item = NaiveDate;
 
@@ -205,6 +705,7 @@ impl Iterator for Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{raster_template, test_builder};
     use chrono::NaiveDate;
     use std::fs::File;
     use std::io::Write;
@@ -238,19 +739,257 @@ mod tests {
 
         let config = Config::from_file(file_path).unwrap();
 
-        assert_eq!(config.frequency, TimeStep::Daily);
+        assert_eq!(config.frequency(), TimeStep::Daily);
+        assert_eq!(config.output_format(), OutputFormat::GeoTiff);
 
         assert_eq!(
-            config.start_date,
+            config.start_date(),
             NaiveDate::from_ymd_opt(2023, 1, 1).expect("Invalid date")
         );
 
         assert_eq!(
-            config.end_date,
+            config.end_date(),
             NaiveDate::from_ymd_opt(2023, 1, 10).expect("Invalid date")
         );
     }
 
+    #[test]
+    fn test_output_format() {
+        let config_data = r#"
+    {
+        "model_id": "test_model",
+        "start_date": "2023-01-01",
+        "end_date": "2023-01-10",
+        "frequency": "daily",
+        "hourly_increment": 3,
+        "raster_templates": [],
+        "bbox": {
+            "xmin": 0.0,
+            "xmax": 1.0,
+            "ymin": 0.0,
+            "ymax": 1.0
+        },
+        "output_directory": "/tmp",
+        "output_format": "gpkg"
+    }
+    "#;
+
+        let config: Config = serde_json::from_str(config_data).unwrap();
+
+        assert_eq!(config.output_format(), OutputFormat::GeoPackage);
+        assert_eq!(config.output_format().driver_name(), "GPKG");
+        assert_eq!(config.output_format().extension(), "gpkg");
+
+        let result = serde_json::from_str::<Config>(&config_data.replace("gpkg", "tiff"));
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown output_format 'tiff'")
+        );
+
+        assert!(matches!(
+            "tiff".parse::<OutputFormat>(),
+            Err(ConfigError::UnknownOutputFormat(format)) if format == "tiff"
+        ));
+        for format in OutputFormat::ALL {
+            assert_eq!(format.name().parse::<OutputFormat>().unwrap(), format);
+        }
+    }
+
+    #[test]
+    fn test_output_filename_pattern() {
+        let builder = test_builder();
+        let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+
+        let config = builder.clone().build().unwrap();
+        assert_eq!(
+            config.output_filename(&date),
+            "boreas_daily_primary_production_test_model_20230102.tif"
+        );
+
+        let config = builder
+            .clone()
+            .output_filename_pattern("{model_id}_pp_{date}.{ext}")
+            .output_format(OutputFormat::GeoPackage)
+            .build()
+            .unwrap();
+        assert_eq!(config.output_filename(&date), "test_model_pp_20230102.gpkg");
+
+        let result = builder.output_filename_pattern("pp.tif").build();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_l2_flags_mask() {
+        let builder = test_builder();
+
+        let config = builder.clone().build().unwrap();
+        assert_eq!(config.l2_flags_mask(), DEFAULT_L2_FLAGS_MASK);
+
+        let config = builder
+            .clone()
+            .l2_flags(vec!["LAND".to_string(), "CLDICE".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.l2_flags_mask(),
+            crate::iop::flags::LAND | crate::iop::flags::CLDICE
+        );
+
+        let result = builder.l2_flags(vec!["CLOUDS".to_string()]).build();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_chla_source() {
+        let rrs_templates: Vec<RasterFile> = QAA_RRS_BANDS
+            .iter()
+            .map(|(name, _)| raster_template(name, &format!("{{}}_{}.tif", name)))
+            .collect();
+
+        let builder = test_builder();
+
+        let config = builder.clone().build().unwrap();
+        assert_eq!(config.chla_source(), ChlaSource::Band);
+
+        let config = builder
+            .clone()
+            .raster_templates(rrs_templates.clone())
+            .chla_source(ChlaSource::Qaa)
+            .build()
+            .unwrap();
+        assert_eq!(config.chla_source(), ChlaSource::Qaa);
+
+        // rrs_670 is missing
+        let result = builder
+            .raster_templates(rrs_templates[..4].to_vec())
+            .chla_source(ChlaSource::Qaa)
+            .build();
+        assert!(matches!(result, Err(ConfigError::Invalid(e)) if e.contains("rrs_670")));
+
+        let source: ChlaSource = serde_json::from_str(r#""qaa""#).unwrap();
+        assert_eq!(source, ChlaSource::Qaa);
+    }
+
+    #[test]
+    fn test_satellite() {
+        let builder = test_builder();
+
+        assert_eq!(
+            builder.clone().build().unwrap().satellite(),
+            Satellites::SeaWiFS
+        );
+        assert_eq!(
+            builder
+                .satellite(Satellites::Modis)
+                .build()
+                .unwrap()
+                .satellite(),
+            Satellites::Modis
+        );
+
+        let satellite: Satellites = serde_json::from_str(r#""meris""#).unwrap();
+        assert_eq!(satellite, Satellites::Meris);
+        assert!(serde_json::from_str::<Satellites>(r#""viirs""#).is_err());
+    }
+
+    #[test]
+    fn test_max_pp() {
+        let builder = test_builder();
+
+        assert_eq!(builder.clone().build().unwrap().max_pp(), 2000.0);
+        assert_eq!(
+            builder.clone().max_pp(5000.0).build().unwrap().max_pp(),
+            5000.0
+        );
+        assert!(matches!(
+            builder.max_pp(0.0).build(),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_netcdf_variable() {
+        let template: RasterFile = serde_json::from_str(
+            r#"{
+                "name": "sst",
+                "base_directory": "/tmp",
+                "filename_pattern": "sst_{}.nc",
+                "date_format": "YYYYMMDD",
+                "netcdf_variable": "sea_surface_temperature"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(template.variable_name(), "sea_surface_temperature");
+
+        let template = RasterFile {
+            netcdf_variable: None,
+            ..template
+        };
+        assert_eq!(template.variable_name(), "sst");
+    }
+
+    #[test]
+    fn test_band() {
+        let template: RasterFile = serde_json::from_str(
+            r#"{
+                "name": "rrs_443",
+                "base_directory": "/tmp",
+                "filename_pattern": "rrs_{}.tif",
+                "date_format": "YYYYMMDD",
+                "band": 3
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(template.band_index(), 3);
+
+        let template = RasterFile {
+            band: None,
+            ..template
+        };
+        assert_eq!(template.band_index(), 1);
+
+        let result = test_builder()
+            .raster_template(RasterFile {
+                band: Some(0),
+                ..template
+            })
+            .build();
+        assert!(matches!(result, Err(ConfigError::Invalid(msg)) if msg.contains("band")));
+    }
+
+    #[test]
+    fn test_value_scaling() {
+        let template: RasterFile = serde_json::from_str(
+            r#"{
+                "name": "sst",
+                "base_directory": "/tmp",
+                "filename_pattern": "sst_{}.tif",
+                "date_format": "YYYYMMDD",
+                "scale": 0.005,
+                "nodata": -32767
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.value_scaling(),
+            ValueScaling {
+                scale: Some(0.005),
+                offset: None,
+                nodata: Some(-32767.0),
+            }
+        );
+
+        let result = test_builder()
+            .raster_template(RasterFile {
+                scale: Some(0.0),
+                ..template
+            })
+            .build();
+        assert!(matches!(result, Err(ConfigError::Invalid(msg)) if msg.contains("scale")));
+    }
+
     #[test]
     fn test_increment_date_daily() {
         let config = Config {
@@ -259,9 +998,29 @@ mod tests {
             end_date: NaiveDate::from_ymd_opt(2023, 1, 10).expect("Invalid date"),
             frequency: TimeStep::Daily,
             hourly_increment: 1,
+            minute_increment: None,
             raster_templates: vec![],
             bbox: Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap(),
             output_directory: "/tmp".to_string(),
+            allow_partial: false,
+            output_format: OutputFormat::GeoTiff,
+            output_nodata: None,
+            skip_existing: false,
+            overwrite: true,
+            output_filename_pattern: DEFAULT_OUTPUT_FILENAME_PATTERN.to_string(),
+            bathymetry_file: None,
+            min_water_depth: 0.0,
+            l2_flags: None,
+            chla_source: ChlaSource::Band,
+            satellite: Satellites::SeaWiFS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            create_output_dir: false,
+            bbox_crs: None,
+            max_threads: None,
+            min_valid_days: 1,
+            align_grids: false,
+            reference_grid: None,
         };
 
         let new_date = config
@@ -282,9 +1041,29 @@ mod tests {
             end_date: NaiveDate::from_ymd_opt(2023, 1, 10).expect("Invalid date"),
             frequency: TimeStep::Weekly,
             hourly_increment: 1,
+            minute_increment: None,
             raster_templates: vec![],
             bbox: Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap(),
             output_directory: "/tmp".to_string(),
+            allow_partial: false,
+            output_format: OutputFormat::GeoTiff,
+            output_nodata: None,
+            skip_existing: false,
+            overwrite: true,
+            output_filename_pattern: DEFAULT_OUTPUT_FILENAME_PATTERN.to_string(),
+            bathymetry_file: None,
+            min_water_depth: 0.0,
+            l2_flags: None,
+            chla_source: ChlaSource::Band,
+            satellite: Satellites::SeaWiFS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            create_output_dir: false,
+            bbox_crs: None,
+            max_threads: None,
+            min_valid_days: 1,
+            align_grids: false,
+            reference_grid: None,
         };
 
         let new_date = config
@@ -305,9 +1084,29 @@ mod tests {
             end_date: NaiveDate::from_ymd_opt(2023, 12, 31).expect("Invalid date"),
             frequency: TimeStep::Monthly,
             hourly_increment: 1,
+            minute_increment: None,
             raster_templates: vec![],
             bbox: Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap(),
             output_directory: "/tmp".to_string(),
+            allow_partial: false,
+            output_format: OutputFormat::GeoTiff,
+            output_nodata: None,
+            skip_existing: false,
+            overwrite: true,
+            output_filename_pattern: DEFAULT_OUTPUT_FILENAME_PATTERN.to_string(),
+            bathymetry_file: None,
+            min_water_depth: 0.0,
+            l2_flags: None,
+            chla_source: ChlaSource::Band,
+            satellite: Satellites::SeaWiFS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            create_output_dir: false,
+            bbox_crs: None,
+            max_threads: None,
+            min_valid_days: 1,
+            align_grids: false,
+            reference_grid: None,
         };
 
         let new_date = config
@@ -320,6 +1119,123 @@ mod tests {
         ); // February 31st is invalid, should fallback to 28th
     }
 
+    #[test]
+    fn test_set_output_directory() {
+        let mut config = Config {
+            model_id: "test_model".to_string(),
+            start_date: NaiveDate::from_ymd_opt(2023, 1, 1).expect("Invalid date"),
+            end_date: NaiveDate::from_ymd_opt(2023, 1, 3).expect("Invalid date"),
+            frequency: TimeStep::Daily,
+            hourly_increment: 3,
+            minute_increment: None,
+            raster_templates: vec![],
+            bbox: Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap(),
+            output_directory: "/tmp".to_string(),
+            allow_partial: false,
+            output_format: OutputFormat::GeoTiff,
+            output_nodata: None,
+            skip_existing: false,
+            overwrite: true,
+            output_filename_pattern: DEFAULT_OUTPUT_FILENAME_PATTERN.to_string(),
+            bathymetry_file: None,
+            min_water_depth: 0.0,
+            l2_flags: None,
+            chla_source: ChlaSource::Band,
+            satellite: Satellites::SeaWiFS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            create_output_dir: false,
+            bbox_crs: None,
+            max_threads: None,
+            min_valid_days: 1,
+            align_grids: false,
+            reference_grid: None,
+        };
+
+        let dir = tempdir().unwrap();
+        let new_dir = dir.path().to_string_lossy().to_string();
+        config.set_output_directory(new_dir.clone()).unwrap();
+        assert_eq!(config.output_directory(), &new_dir);
+
+        let result = config.set_output_directory("/does/not/exist".to_string());
+        assert!(matches!(result, Err(ConfigError::OutputDirectory(_))));
+        assert_eq!(config.output_directory(), &new_dir);
+    }
+
+    #[test]
+    fn test_create_output_dir() {
+        let dir = tempdir().unwrap();
+        let output_directory = dir.path().join("outputs").join("daily");
+        let output_directory = output_directory.to_string_lossy().to_string();
+
+        let builder = test_builder().output_directory(output_directory.clone());
+
+        let result = builder.clone().build();
+        assert!(matches!(result, Err(ConfigError::OutputDirectory(_))));
+        assert!(!Path::new(&output_directory).exists());
+
+        let config = builder.create_output_dir(true).build().unwrap();
+        assert!(config.create_output_dir());
+        assert!(Path::new(&output_directory).is_dir());
+    }
+
+    #[test]
+    fn test_bbox_crs() {
+        // Polar stereographic coordinates (m) of the Baffin Bay
+        let builder = test_builder()
+            .bbox(Bbox::new_projected(-1_500_000.0, -500_000.0, -1_500_000.0, -500_000.0).unwrap());
+
+        // Out of the lon/lat range without a projected CRS
+        let result = builder.clone().build();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+
+        let config = builder.clone().bbox_crs("EPSG:3413").build().unwrap();
+        assert_eq!(config.bbox_crs(), Some("EPSG:3413"));
+
+        let result = builder.bbox_crs("not a crs").build();
+        assert!(matches!(result, Err(ConfigError::Invalid(msg)) if msg.contains("bbox_crs")));
+    }
+
+    #[test]
+    fn test_max_threads() {
+        let builder = test_builder();
+
+        assert_eq!(builder.clone().build().unwrap().max_threads(), None);
+        assert_eq!(
+            builder
+                .clone()
+                .max_threads(4)
+                .build()
+                .unwrap()
+                .max_threads(),
+            Some(4)
+        );
+        assert!(matches!(
+            builder.max_threads(0).build(),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_min_valid_days() {
+        let builder = test_builder();
+
+        assert_eq!(builder.clone().build().unwrap().min_valid_days(), 1);
+        assert_eq!(
+            builder
+                .clone()
+                .min_valid_days(3)
+                .build()
+                .unwrap()
+                .min_valid_days(),
+            3
+        );
+        assert!(matches!(
+            builder.min_valid_days(0).build(),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
     #[test]
     fn test_iterator() {
         let config = Config {
@@ -328,12 +1244,37 @@ mod tests {
             end_date: NaiveDate::from_ymd_opt(2023, 1, 3).expect("Invalid date"),
             frequency: TimeStep::Daily,
             hourly_increment: 3,
+            minute_increment: None,
             raster_templates: vec![],
             bbox: Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap(),
             output_directory: "/tmp".to_string(),
+            allow_partial: false,
+            output_format: OutputFormat::GeoTiff,
+            output_nodata: None,
+            skip_existing: false,
+            overwrite: true,
+            output_filename_pattern: DEFAULT_OUTPUT_FILENAME_PATTERN.to_string(),
+            bathymetry_file: None,
+            min_water_depth: 0.0,
+            l2_flags: None,
+            chla_source: ChlaSource::Band,
+            satellite: Satellites::SeaWiFS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            create_output_dir: false,
+            bbox_crs: None,
+            max_threads: None,
+            min_valid_days: 1,
+            align_grids: false,
+            reference_grid: None,
         };
 
+        let borrowed_dates: Vec<NaiveDate> = config.date_iter().collect();
+        // Iterating by reference leaves the config untouched
+        assert_eq!(config.date_iter().count(), 3);
+
         let dates: Vec<NaiveDate> = config.collect();
+        assert_eq!(borrowed_dates, dates);
 
         assert_eq!(
             dates,
@@ -344,4 +1285,32 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_align_grids() {
+        let builder = test_builder().raster_template(raster_template("sst", "sst_{}.nc"));
+
+        let config = builder.clone().build().unwrap();
+        assert!(!config.align_grids());
+        assert_eq!(config.reference_grid(), None);
+
+        let config = builder
+            .clone()
+            .align_grids(true)
+            .reference_grid("sst")
+            .build()
+            .unwrap();
+        assert!(config.align_grids());
+        assert_eq!(config.reference_grid(), Some("sst"));
+
+        // Unknown raster or alignment disabled
+        assert!(matches!(
+            builder.clone().align_grids(true).reference_grid("chlor_a").build(),
+            Err(ConfigError::Invalid(msg)) if msg.contains("reference_grid")
+        ));
+        assert!(matches!(
+            builder.reference_grid("sst").build(),
+            Err(ConfigError::Invalid(msg)) if msg.contains("align_grids")
+        ));
+    }
 }
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use crate::config::ConfigError;
+
+/// File format of the daily output rasters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    GeoTiff,
+    GeoPackage,
+    /// Cloud-Optimized GeoTIFF: tiled, DEFLATE-compressed, with internal overviews
+    Cog,
+}
+
+impl OutputFormat {
+    /// Every supported format
+    pub const ALL: [OutputFormat; 3] = [
+        OutputFormat::GeoTiff,
+        OutputFormat::GeoPackage,
+        OutputFormat::Cog,
+    ];
+
+    /// Name of the format in the `output_format` configuration field
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::GeoTiff => "gtiff",
+            OutputFormat::GeoPackage => "gpkg",
+            OutputFormat::Cog => "cog",
+        }
+    }
+
+    /// Name of the GDAL driver used to write this format
+    pub fn driver_name(&self) -> &'static str {
+        match self {
+            OutputFormat::GeoTiff | OutputFormat::Cog => "GTiff",
+            OutputFormat::GeoPackage => "GPKG",
+        }
+    }
+
+    /// File extension (without the dot) of the output files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::GeoTiff | OutputFormat::Cog => "tif",
+            OutputFormat::GeoPackage => "gpkg",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutputFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| ConfigError::UnknownOutputFormat(s.to_string()))
+    }
+}
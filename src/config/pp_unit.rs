@@ -0,0 +1,60 @@
+use serde::Deserialize;
+
+/// Unit of the primary production written to the output rasters. PP is computed in
+/// mg C m-2 d-1; annual values are the daily rate multiplied by 365 days.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PpUnit {
+    #[default]
+    #[serde(rename(deserialize = "mg_c_m2_d"))]
+    MgCPerDay,
+    #[serde(rename(deserialize = "g_c_m2_d"))]
+    GCPerDay,
+    #[serde(rename(deserialize = "mg_c_m2_yr"))]
+    MgCPerYear,
+    #[serde(rename(deserialize = "g_c_m2_yr"))]
+    GCPerYear,
+}
+
+impl PpUnit {
+    /// Factor converting mg C m-2 d-1 to this unit
+    pub fn factor(&self) -> f32 {
+        match self {
+            PpUnit::MgCPerDay => 1.0,
+            PpUnit::GCPerDay => 1e-3,
+            PpUnit::MgCPerYear => 365.0,
+            PpUnit::GCPerYear => 0.365,
+        }
+    }
+
+    /// Value of the `Unit` metadata item of the output bands
+    pub fn label(&self) -> &'static str {
+        match self {
+            PpUnit::MgCPerDay => "mg C m-2 d-1",
+            PpUnit::GCPerDay => "g C m-2 d-1",
+            PpUnit::MgCPerYear => "mg C m-2 yr-1",
+            PpUnit::GCPerYear => "g C m-2 yr-1",
+        }
+    }
+
+    /// Converts a PP value from mg C m-2 d-1 to this unit
+    pub fn convert(&self, pp: f32) -> f32 {
+        pp * self.factor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(PpUnit::default().convert(500.0), 500.0);
+        assert_eq!(PpUnit::GCPerDay.convert(500.0), 0.5);
+        assert_eq!(PpUnit::MgCPerYear.convert(500.0), 182_500.0);
+        assert!((PpUnit::GCPerYear.convert(500.0) - 182.5).abs() < 1e-4);
+
+        let unit: PpUnit = serde_json::from_str(r#""g_c_m2_yr""#).unwrap();
+        assert_eq!(unit, PpUnit::GCPerYear);
+        assert_eq!(unit.label(), "g C m-2 yr-1");
+    }
+}
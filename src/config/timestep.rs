@@ -1,3 +1,4 @@
+use chrono::{Datelike, Duration, Months, NaiveDate};
 use serde::Deserialize;
 use std::fmt;
 
@@ -11,6 +12,25 @@ pub enum TimeStep {
     Monthly,
 }
 
+impl TimeStep {
+    /// First and last day of the composite period containing `date`: the day itself, its
+    /// Monday-to-Sunday week or its calendar month
+    pub fn period(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            TimeStep::Daily => (date, date),
+            TimeStep::Weekly => {
+                let start = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                (start, start + Duration::days(6))
+            }
+            TimeStep::Monthly => {
+                let start = date.with_day(1).expect("day 1 exists in every month");
+                let end = start + Months::new(1) - Duration::days(1);
+                (start, end)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TimeStepParseError;
 
@@ -21,3 +41,31 @@ impl fmt::Display for TimeStepParseError {
 }
 
 impl std::error::Error for TimeStepParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_period() {
+        let date = ymd(2024, 2, 14);
+
+        assert_eq!(TimeStep::Daily.period(date), (date, date));
+        assert_eq!(
+            TimeStep::Weekly.period(date),
+            (ymd(2024, 2, 12), ymd(2024, 2, 18))
+        );
+        assert_eq!(
+            TimeStep::Monthly.period(date),
+            (ymd(2024, 2, 1), ymd(2024, 2, 29))
+        );
+        assert_eq!(
+            TimeStep::Monthly.period(ymd(2025, 12, 31)),
+            (ymd(2025, 12, 1), ymd(2025, 12, 31))
+        );
+    }
+}
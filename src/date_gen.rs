@@ -1,6 +1,10 @@
 use crate::config::Config;
+use chrono::Duration;
 use chrono::NaiveDate;
 use chrono::NaiveDateTime;
+use log::error;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
 
 #[allow(dead_code)]
 pub struct DateTimeGenerator {
@@ -13,26 +17,25 @@ impl DateTimeGenerator {
         Self { config }
     }
 
+    /// Generates the datetimes of every date of the period, stepping from 00:00 UTC by
+    /// `minute_increment` minutes if set in the config, otherwise by `hourly_increment` hours.
     #[allow(dead_code)]
     pub fn generate_datetime_series(&self) -> Vec<NaiveDateTime> {
-        let hourly_increment = self.config.hourly_increment();
-        if hourly_increment == 0 {
-            eprintln!("Error: hourly_increment must be greater than 0 to avoid division by zero.");
+        let step_minutes = self.config.step_minutes();
+        if step_minutes == 0 {
+            error!("hourly_increment must be greater than 0 to avoid division by zero.");
             return Vec::new();
         }
 
         let mut datetimes = Vec::new();
 
-        // Clone config to use as iterator
-        let config_iter = self.config.clone();
-
-        for date in config_iter {
-            let hours_in_day = 24 / self.config.hourly_increment() as u32;
+        for date in self.config.date_iter() {
+            let steps_in_day = MINUTES_PER_DAY / step_minutes;
 
-            for hour_step in 0..hours_in_day {
-                let hour = hour_step * self.config.hourly_increment() as u32;
+            for step in 0..steps_in_day {
+                let minutes = step * step_minutes;
                 let datetime = date
-                    .and_hms_opt(hour, 0, 0)
+                    .and_hms_opt(minutes / 60, minutes % 60, 0)
                     .unwrap_or_else(|| date.and_hms_opt(0, 0, 0).unwrap());
                 datetimes.push(datetime);
             }
@@ -41,11 +44,40 @@ impl DateTimeGenerator {
         datetimes
     }
 
+    /// Same as `generate_datetime_series`, but the time grid is laid out in local solar time at
+    /// `longitude` (time zone offset of `longitude / 15` hours) and aligned so that one step falls
+    /// on local noon. The returned datetimes are in UTC, so some of them can fall on the previous
+    /// or next UTC day.
     #[allow(dead_code)]
-    pub fn generate_date_series(&self) -> Vec<NaiveDate> {
-        let config_iter = self.config.clone();
+    pub fn generate_datetime_series_local(&self, longitude: f64) -> Vec<NaiveDateTime> {
+        let step_minutes = self.config.step_minutes();
+        if step_minutes == 0 {
+            error!("hourly_increment must be greater than 0 to avoid division by zero.");
+            return Vec::new();
+        }
+
+        // Local solar time = UTC + longitude / 15 hours
+        let utc_offset = Duration::seconds((longitude / 15.0 * 3600.0).round() as i64);
+
+        let mut datetimes = Vec::new();
+
+        for date in self.config.date_iter() {
+            let local_midnight = date.and_hms_opt(0, 0, 0).unwrap();
 
-        config_iter.collect()
+            // First local time of the grid such that noon is one of the steps
+            let mut minutes = (MINUTES_PER_DAY / 2) % step_minutes;
+            while minutes < MINUTES_PER_DAY {
+                datetimes.push(local_midnight + Duration::minutes(minutes as i64) - utc_offset);
+                minutes += step_minutes;
+            }
+        }
+
+        datetimes
+    }
+
+    #[allow(dead_code)]
+    pub fn generate_date_series(&self) -> Vec<NaiveDate> {
+        self.config.date_iter().collect()
     }
 }
 
@@ -105,4 +137,88 @@ mod tests {
             NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()
         );
     }
+
+    #[test]
+    fn test_generate_datetime_series_minute_increment() {
+        let config: Config = serde_json::from_str(
+            r#"
+        {
+            "model_id": "Test",
+            "start_date": "2023-06-21",
+            "end_date": "2023-06-21",
+            "frequency": "daily",
+            "hourly_increment": 1,
+            "minute_increment": 30,
+            "raster_templates": [],
+            "bbox": {
+                "xmin": 0.0,
+                "xmax": 1.0,
+                "ymin": 0.0,
+                "ymax": 1.0
+            },
+            "output_directory": "/tmp"
+        }
+        "#,
+        )
+        .unwrap();
+
+        let series = DateTimeGenerator::new(config).generate_datetime_series();
+
+        assert_eq!(series.len(), 48);
+        assert_eq!((series[1].hour(), series[1].minute()), (0, 30));
+        assert_eq!((series[47].hour(), series[47].minute()), (23, 30));
+    }
+
+    #[test]
+    fn test_minute_increment_must_divide_the_day() {
+        let config_data = r#"
+        {
+            "model_id": "Test",
+            "start_date": "2023-06-21",
+            "end_date": "2023-06-21",
+            "frequency": "daily",
+            "hourly_increment": 1,
+            "minute_increment": 7,
+            "raster_templates": [],
+            "bbox": {
+                "xmin": 0.0,
+                "xmax": 1.0,
+                "ymin": 0.0,
+                "ymax": 1.0
+            },
+            "output_directory": "/tmp"
+        }
+        "#;
+
+        assert!(serde_json::from_str::<Config>(config_data).is_err());
+    }
+
+    #[test]
+    fn test_generate_datetime_series_local() {
+        let config = create_test_config();
+        let generator = DateTimeGenerator::new(config);
+
+        // At 90°W local noon is at 18:00 UTC
+        let series = generator.generate_datetime_series_local(-90.0);
+        assert_eq!(series.len(), 8);
+
+        let noon = NaiveDate::from_ymd_opt(2023, 1, 1)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        assert!(series.contains(&noon));
+        assert_eq!(series[0].hour(), 6);
+
+        // The last step of the first local day falls on the next UTC day
+        assert_eq!(
+            series[3].date(),
+            NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()
+        );
+
+        // At Greenwich the grid matches the UTC one
+        assert_eq!(
+            generator.generate_datetime_series_local(0.0),
+            generator.generate_datetime_series()
+        );
+    }
 }
@@ -32,6 +32,59 @@ pub static AW_ALL: LazyLock<BTreeMap<u32, f64>> = LazyLock::new(|| {
     ])
 });
 
+/// Temperature of the pure water absorption measurements of `AW_ALL` (°C)
+pub const AW_REFERENCE_TEMPERATURE_C: f64 = 22.0;
+
+/// Temperature dependence of the water absorption at different wavelengths (nm)
+/// Values represent the linear coefficient Ψ_T in m^-1 °C^-1
+/// Data from Sullivan et al. (2006) and Röttgers et al. (2014)
+pub static PSI_T_ALL: LazyLock<BTreeMap<u32, f64>> = LazyLock::new(|| {
+    BTreeMap::from([
+        (410, 0.0000),
+        (412, 0.0000),
+        (443, 0.0000),
+        (469, 0.0000),
+        (486, 0.0000),
+        (488, 0.0000),
+        (490, 0.0000),
+        (510, 0.0001),
+        (531, 0.0001),
+        (547, 0.0001),
+        (551, 0.0001),
+        (555, 0.0001),
+        (645, 0.0001),
+        (667, -0.0001),
+        (670, -0.0001),
+        (671, -0.0001),
+        (678, 0.0000),
+    ])
+});
+
+/// Salinity dependence of the water absorption at different wavelengths (nm)
+/// Values represent the linear coefficient Ψ_S in m^-1 PSU^-1
+/// Data from Sullivan et al. (2006) and Röttgers et al. (2014)
+pub static PSI_S_ALL: LazyLock<BTreeMap<u32, f64>> = LazyLock::new(|| {
+    BTreeMap::from([
+        (410, 0.00012),
+        (412, 0.00012),
+        (443, 0.00009),
+        (469, 0.00006),
+        (486, 0.00004),
+        (488, 0.00004),
+        (490, 0.00004),
+        (510, 0.00002),
+        (531, 0.00001),
+        (547, 0.00000),
+        (551, 0.00000),
+        (555, 0.00000),
+        (645, 0.00000),
+        (667, 0.00002),
+        (670, 0.00002),
+        (671, 0.00002),
+        (678, 0.00002),
+    ])
+});
+
 /// Water backscattering coefficients at different wavelengths (nm)
 /// Values represent backscattering coefficient in m^-1
 /// Data from Zhang et al. (2009) and other standard oceanographic references
@@ -104,8 +157,18 @@ pub const G1: f64 = 0.125;
 /// Algorithm decision thresholds from Lee et al. (2002) QAA implementation
 pub const RRS_670_THRESHOLD: f64 = 0.0015;
 
+/// Coefficients of the red reference absorption used above `RRS_670_THRESHOLD`
+/// a(670) = aw(670) + H0 * (Rrs(670) / (Rrs(443) + Rrs(490)))^H1 (Lee et al., QAA v6)
+pub const RED_REF_H0: f64 = 0.39;
+pub const RED_REF_H1: f64 = 1.14;
+
 /// Default coefficients for SeaWiFS
 /// <https://oceancolor.gsfc.nasa.gov/docs/ocssw/qaa_8c_source.html>
 pub const C1: f64 = -1.146;
 pub const C2: f64 = -1.366;
 pub const C3: f64 = -0.469;
+
+/// QAA v6 reference absorption coefficients for a 555nm reference band, also used at 550nm
+/// (MODIS) and 560nm (MERIS) as allowed by the IOCCG QAA notes
+/// <https://oceancolor.gsfc.nasa.gov/docs/ocssw/qaa_8c_source.html>
+pub const QAA_ACOEFS_555: [f64; 3] = [C1, C2, C3];
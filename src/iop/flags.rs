@@ -0,0 +1,155 @@
+//! Level-2 ocean color quality flags
+//!
+//! NASA OBPG Level-2 products carry an `l2_flags` bitfield where each bit reports a condition of
+//! the retrieval (land, cloud or ice, sun glint, atmospheric correction failure, ...). Pixels whose
+//! flags intersect a mask are excluded from further processing, as done when NASA bins Level-2
+//! scenes into Level-3 products.
+//!
+//! ## References
+//!
+//! - NASA Ocean Biology Processing Group. Level-2 Ocean Color Flags.
+//!   <https://oceancolor.gsfc.nasa.gov/resources/atbd/ocl2flags/>
+
+/// Atmospheric correction failure
+pub const ATMFAIL: u32 = 1 << 0;
+/// Pixel is over land
+pub const LAND: u32 = 1 << 1;
+/// One or more product algorithms produced a warning
+pub const PRODWARN: u32 = 1 << 2;
+/// Sun glint: reflectance exceeds threshold
+pub const HIGLINT: u32 = 1 << 3;
+/// Observed radiance very high or saturated
+pub const HILT: u32 = 1 << 4;
+/// Sensor view zenith angle exceeds threshold
+pub const HISATZEN: u32 = 1 << 5;
+/// Pixel is in shallow water
+pub const COASTZ: u32 = 1 << 6;
+/// Probable stray light contamination
+pub const STRAYLIGHT: u32 = 1 << 8;
+/// Probable cloud or ice contamination
+pub const CLDICE: u32 = 1 << 9;
+/// Coccolithophores detected
+pub const COCCOLITH: u32 = 1 << 10;
+/// Turbid water detected
+pub const TURBIDW: u32 = 1 << 11;
+/// Solar zenith exceeds threshold
+pub const HISOLZEN: u32 = 1 << 12;
+/// Very low water-leaving radiance
+pub const LOWLW: u32 = 1 << 14;
+/// Chlorophyll algorithm failure
+pub const CHLFAIL: u32 = 1 << 15;
+/// Navigation quality is suspect
+pub const NAVWARN: u32 = 1 << 16;
+/// Absorbing aerosols determined
+pub const ABSAER: u32 = 1 << 17;
+/// Maximum iterations reached for NIR iteration
+pub const MAXAERITER: u32 = 1 << 19;
+/// Moderate sun glint contamination
+pub const MODGLINT: u32 = 1 << 20;
+/// Chlorophyll out-of-bounds
+pub const CHLWARN: u32 = 1 << 21;
+/// Atmospheric correction is suspect
+pub const ATMWARN: u32 = 1 << 22;
+/// Probable sea ice contamination
+pub const SEAICE: u32 = 1 << 24;
+/// Navigation failure
+pub const NAVFAIL: u32 = 1 << 25;
+/// Pixel rejected by user-defined filter
+pub const FILTER: u32 = 1 << 26;
+/// Bowtie deleted pixels (VIIRS)
+pub const BOWTIEDEL: u32 = 1 << 28;
+/// High degree of polarization determined
+pub const HIPOL: u32 = 1 << 29;
+/// Failure in any product
+pub const PRODFAIL: u32 = 1 << 30;
+
+/// Flag names as written in the `l2_flags` attributes of NASA products, with their bit
+pub const L2_FLAGS: [(&str, u32); 26] = [
+    ("ATMFAIL", ATMFAIL),
+    ("LAND", LAND),
+    ("PRODWARN", PRODWARN),
+    ("HIGLINT", HIGLINT),
+    ("HILT", HILT),
+    ("HISATZEN", HISATZEN),
+    ("COASTZ", COASTZ),
+    ("STRAYLIGHT", STRAYLIGHT),
+    ("CLDICE", CLDICE),
+    ("COCCOLITH", COCCOLITH),
+    ("TURBIDW", TURBIDW),
+    ("HISOLZEN", HISOLZEN),
+    ("LOWLW", LOWLW),
+    ("CHLFAIL", CHLFAIL),
+    ("NAVWARN", NAVWARN),
+    ("ABSAER", ABSAER),
+    ("MAXAERITER", MAXAERITER),
+    ("MODGLINT", MODGLINT),
+    ("CHLWARN", CHLWARN),
+    ("ATMWARN", ATMWARN),
+    ("SEAICE", SEAICE),
+    ("NAVFAIL", NAVFAIL),
+    ("FILTER", FILTER),
+    ("BOWTIEDEL", BOWTIEDEL),
+    ("HIPOL", HIPOL),
+    ("PRODFAIL", PRODFAIL),
+];
+
+/// Default mask: land, cloud/ice, sun glint, failed or suspect atmospheric correction and
+/// navigation, as used by NASA for the Level-3 chlorophyll products
+pub const DEFAULT_L2_FLAGS_MASK: u32 = ATMFAIL
+    | LAND
+    | HIGLINT
+    | HILT
+    | HISATZEN
+    | STRAYLIGHT
+    | CLDICE
+    | COCCOLITH
+    | HISOLZEN
+    | LOWLW
+    | CHLFAIL
+    | NAVWARN
+    | MAXAERITER
+    | ATMWARN
+    | NAVFAIL;
+
+/// Whether a pixel with these `flags` is excluded by `mask_bits`
+pub fn is_masked(flags: u32, mask_bits: u32) -> bool {
+    flags & mask_bits != 0
+}
+
+/// Bit of a flag from its name (e.g. `"CLDICE"`), case-insensitive
+pub fn flag_bit(name: &str) -> Option<u32> {
+    L2_FLAGS
+        .iter()
+        .find(|(flag_name, _)| flag_name.eq_ignore_ascii_case(name))
+        .map(|&(_, bit)| bit)
+}
+
+/// Combines flag names into a mask. Fails on the first unknown name.
+pub fn mask_from_names<S: AsRef<str>>(names: &[S]) -> Result<u32, String> {
+    names.iter().try_fold(0, |mask, name| {
+        flag_bit(name.as_ref())
+            .map(|bit| mask | bit)
+            .ok_or_else(|| format!("Unknown l2_flags flag: {}", name.as_ref()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_masked() {
+        assert!(is_masked(LAND | TURBIDW, DEFAULT_L2_FLAGS_MASK));
+        assert!(is_masked(CLDICE, DEFAULT_L2_FLAGS_MASK));
+        assert!(!is_masked(TURBIDW | COASTZ, DEFAULT_L2_FLAGS_MASK));
+        assert!(!is_masked(0, DEFAULT_L2_FLAGS_MASK));
+    }
+
+    #[test]
+    fn test_mask_from_names() {
+        assert_eq!(mask_from_names(&["LAND", "cldice"]), Ok(LAND | CLDICE));
+        assert_eq!(mask_from_names::<&str>(&[]), Ok(0));
+        assert!(mask_from_names(&["LAND", "CLOUDS"]).is_err());
+        assert!(mask_from_names(&["SPARE"]).is_err());
+    }
+}
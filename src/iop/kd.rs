@@ -0,0 +1,64 @@
+//! Diffuse attenuation coefficient estimators
+//!
+//! Empirical Kd(490) retrieval from the blue/green remote sensing reflectance ratio, written in
+//! the Morel & Maritorena (2001) form where the attenuation is split into a pure water part and a
+//! biogenic part:
+//!
+//! `Kd(490) = Kw(490) + A * (Rrs(490) / Rrs(555))^B`
+//!
+//! ## References
+//!
+//! - Morel, A., & Maritorena, S. (2001). Bio-optical properties of oceanic waters: A reappraisal.
+//!   *Journal of Geophysical Research*, 106(C4), 7163-7180.
+//! - Mueller, J. L. (2000). SeaWiFS algorithm for the diffuse attenuation coefficient, K(490),
+//!   using water-leaving radiances at 490 and 555 nm. *SeaWiFS Postlaunch Technical Report
+//!   Series*, 11, 24-27.
+
+/// Diffuse attenuation coefficient of pure sea water at 490nm (m^-1)
+pub const KW_490: f64 = 0.0166;
+
+/// Scaling coefficient of the biogenic attenuation term
+pub const KD490_A: f64 = 0.0773;
+
+/// Exponent applied to the Rrs(490)/Rrs(555) ratio
+pub const KD490_B: f64 = -1.61;
+
+/// Estimates the diffuse attenuation coefficient at 490nm (m^-1) from remote sensing
+/// reflectance at 490 and 555nm (sr^-1).
+///
+/// The relationship was fitted on Rrs(490)/Rrs(555) ratios between about 0.3 (turbid or
+/// chlorophyll-rich waters) and 5 (clear oligotrophic waters). Ratios outside this range are
+/// extrapolated and should be used with caution.
+///
+/// Returns `NaN` if either reflectance is negative, zero or not finite.
+pub fn kd490_from_rrs(rrs_490: f64, rrs_555: f64) -> f64 {
+    if !(rrs_490.is_finite() && rrs_555.is_finite()) || rrs_490 <= 0.0 || rrs_555 <= 0.0 {
+        return f64::NAN;
+    }
+
+    KW_490 + KD490_A * (rrs_490 / rrs_555).powf(KD490_B)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kd490_from_rrs() {
+        // A ratio of 1 leaves only the two additive terms
+        assert!((kd490_from_rrs(0.002, 0.002) - (KW_490 + KD490_A)).abs() < 1e-12);
+
+        // Clearer water (higher blue/green ratio) attenuates less
+        assert!(kd490_from_rrs(0.008, 0.002) < kd490_from_rrs(0.002, 0.004));
+
+        // Attenuation never goes below the pure water value
+        assert!(kd490_from_rrs(0.01, 0.002) > KW_490);
+    }
+
+    #[test]
+    fn test_kd490_from_rrs_invalid() {
+        assert!(kd490_from_rrs(0.0, 0.002).is_nan());
+        assert!(kd490_from_rrs(0.002, -0.001).is_nan());
+        assert!(kd490_from_rrs(f64::NAN, 0.002).is_nan());
+    }
+}
@@ -0,0 +1,155 @@
+//! Merged OCx / QAA chlorophyll
+//!
+//! Band-ratio algorithms (OCx) are accurate in clear open ocean waters, where the blue-green
+//! ratio is driven by phytoplankton, but overestimate chlorophyll when CDOM or sediments also
+//! absorb or scatter light. The QAA decomposes the absorption and is more robust in such optically
+//! complex waters. This project's merged chlorophyll switches from one algorithm to the other
+//! over a transition zone:
+//!
+//! `chla = (1 - w) * chla_ocx + w * chla_qaa`
+//!
+//! where the QAA weight `w` increases linearly with Rrs(670) from 0 at `RRS_670_CLEAR` to 1 at
+//! `RRS_670_THRESHOLD`, the reflectance above which the QAA v6 itself switches to its turbid
+//! water branch. Red reflectance is a proxy of the particle load, near zero in clear water. The
+//! transition zone and the linear weight are choices of this project, not a published blending.
+//!
+//! ## References
+//!
+//! - Lee, Z., et al. (2014). Update of the Quasi-Analytical Algorithm (QAA_v6). IOCCG software
+//!   report, for the 0.0015 sr^-1 Rrs(670) switch to the red reference band.
+//! - O'Reilly, J. E., & Werdell, P. J. (2019). Chlorophyll algorithms for ocean color sensors -
+//!   OC4, OC5 & OC6. *Remote Sensing of Environment*, 229, 32-47, for the OCx algorithms.
+
+use crate::iop::constants::RRS_670_THRESHOLD;
+use crate::iop::ocx::ocx_chla;
+use crate::iop::qaa::{QAA_FAILURE_FLAGS, qaa_v6};
+use crate::sat_bands::{SatBands, Satellites};
+use std::collections::BTreeMap;
+
+/// Rrs(670) (sr^-1) below which the water is considered clear and OCx is used alone
+pub const RRS_670_CLEAR: f64 = 0.0005;
+
+/// Weight of the QAA chlorophyll in `merged_chla` for a given Rrs(670) (sr^-1), from 0 at
+/// `RRS_670_CLEAR` to 1 at `RRS_670_THRESHOLD`. Returns `NaN` if the reflectance is not finite.
+pub fn qaa_weight(rrs_670: f64) -> f64 {
+    if !rrs_670.is_finite() {
+        return f64::NAN;
+    }
+
+    ((rrs_670 - RRS_670_CLEAR) / (RRS_670_THRESHOLD - RRS_670_CLEAR)).clamp(0.0, 1.0)
+}
+
+/// Chlorophyll-a concentration (mg m^-3) blending `ocx_chla` in clear water and the `qaa_v6`
+/// chlorophyll in optically complex water, weighted by `qaa_weight` of the red band.
+///
+/// `rrs` must be keyed by the sensor wavelengths (nm). QAA results with `QAA_FAILURE_FLAGS` are
+/// discarded. When the red band is missing or only one algorithm gives a valid value, that value
+/// is used. Returns `NaN` if neither does.
+pub fn merged_chla(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> f64 {
+    let red_wl = SatBands::new(satellite).closest_band(670);
+    let weight = rrs
+        .get(&red_wl)
+        .map_or(f64::NAN, |&rrs_670| qaa_weight(rrs_670));
+
+    // QAA is not run in clear water, where its weight is 0
+    let ocx = ocx_chla(rrs, satellite);
+    let qaa = if weight > 0.0 || weight.is_nan() || !ocx.is_finite() {
+        let result = qaa_v6(rrs, satellite);
+        if result.flags() & QAA_FAILURE_FLAGS == 0 {
+            result.chla()
+        } else {
+            f64::NAN
+        }
+    } else {
+        f64::NAN
+    };
+
+    match (ocx.is_finite(), qaa.is_finite()) {
+        (true, true) if weight.is_finite() => (1.0 - weight) * ocx + weight * qaa,
+        (true, _) => ocx,
+        (false, true) => qaa,
+        (false, false) => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // MODIS spectra of clear open ocean and turbid coastal waters
+    fn clear() -> BTreeMap<u32, f64> {
+        BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (531, 0.0021),
+            (547, 0.0015),
+            (667, 0.0002),
+        ])
+    }
+
+    fn turbid() -> BTreeMap<u32, f64> {
+        BTreeMap::from([
+            (412, 0.0040),
+            (443, 0.0050),
+            (488, 0.0070),
+            (531, 0.0082),
+            (547, 0.0090),
+            (667, 0.0030),
+        ])
+    }
+
+    #[test]
+    fn test_qaa_weight() {
+        assert_eq!(qaa_weight(0.0002), 0.0);
+        assert_eq!(qaa_weight(RRS_670_CLEAR), 0.0);
+        assert!((qaa_weight(0.001) - 0.5).abs() < 1e-12);
+        assert_eq!(qaa_weight(RRS_670_THRESHOLD), 1.0);
+        assert_eq!(qaa_weight(0.003), 1.0);
+        assert!(qaa_weight(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_merged_chla_clear_water_is_ocx() {
+        let rrs = clear();
+
+        let chla = merged_chla(&rrs, Satellites::Modis);
+        assert_eq!(chla, ocx_chla(&rrs, Satellites::Modis));
+    }
+
+    #[test]
+    fn test_merged_chla_turbid_water_is_qaa() {
+        let rrs = turbid();
+
+        let chla = merged_chla(&rrs, Satellites::Modis);
+        assert!(chla.is_finite());
+        assert_eq!(chla, qaa_v6(&rrs, Satellites::Modis).chla());
+        assert_ne!(chla, ocx_chla(&rrs, Satellites::Modis));
+    }
+
+    #[test]
+    fn test_merged_chla_transition() {
+        let mut rrs = clear();
+        rrs.insert(667, 0.001);
+
+        let ocx = ocx_chla(&rrs, Satellites::Modis);
+        let qaa = qaa_v6(&rrs, Satellites::Modis).chla();
+        let chla = merged_chla(&rrs, Satellites::Modis);
+
+        assert!((chla - (ocx + qaa) / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_merged_chla_fallbacks() {
+        // Without red band, OCx is used
+        let mut rrs = clear();
+        rrs.remove(&667);
+        assert_eq!(
+            merged_chla(&rrs, Satellites::Modis),
+            ocx_chla(&rrs, Satellites::Modis)
+        );
+
+        // Without bands, neither algorithm applies
+        assert!(merged_chla(&BTreeMap::new(), Satellites::Modis).is_nan());
+    }
+}
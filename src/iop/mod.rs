@@ -6,5 +6,12 @@
 #[allow(dead_code)]
 pub mod constants;
 
+pub mod flags;
+pub mod kd;
+pub mod merged;
+pub mod ocx;
+
 #[allow(dead_code)]
 pub mod qaa;
+pub mod units;
+pub mod zeu;
@@ -0,0 +1,119 @@
+//! OCx band-ratio chlorophyll algorithm
+//!
+//! Empirical maximum band ratio (MBR) algorithm used by NASA for the standard `chlor_a` product:
+//!
+//! `log10(chla) = a0 + a1 * X + a2 * X^2 + a3 * X^3 + a4 * X^4`
+//!
+//! where `X = log10(max(Rrs_blue) / Rrs_green)`.
+//!
+//! - **OC4** (SeaWiFS): blue bands 443, 490, 510 nm, green band 555 nm
+//! - **OC3M** (MODIS): blue bands 443, 488 nm, green band 547 nm
+//! - **OC4E** (MERIS): blue bands 443, 490, 510 nm, green band 560 nm
+//!
+//! ## References
+//!
+//! - O'Reilly, J. E., & Werdell, P. J. (2019). Chlorophyll algorithms for ocean color sensors -
+//!   OC4, OC5 & OC6. *Remote Sensing of Environment*, 229, 32-47.
+//! - NASA OBPG chlorophyll algorithm: <https://oceancolor.gsfc.nasa.gov/resources/atbd/chlor_a/>
+
+use crate::sat_bands::{SatBands, Satellites};
+use std::collections::BTreeMap;
+
+/// OC4 coefficients for SeaWiFS (NASA R2018)
+pub const OC4_COEFS: [f64; 5] = [0.32814, -3.20725, 3.22969, -1.36769, -0.81739];
+
+/// OC3M coefficients for MODIS (NASA R2018)
+pub const OC3M_COEFS: [f64; 5] = [0.26294, -2.64669, 1.28364, 1.08209, -1.76828];
+
+/// OC4E coefficients for MERIS (NASA)
+pub const OC4E_COEFS: [f64; 5] = [0.3255, -2.7677, 2.4409, -1.1288, -0.4990];
+
+// Blue band targets, green band target and polynomial coefficients for each sensor
+fn ocx_parameters(satellite: Satellites) -> (&'static [u32], u32, &'static [f64; 5]) {
+    match satellite {
+        Satellites::SeaWiFS => (&[443, 490, 510], 555, &OC4_COEFS),
+        Satellites::Modis => (&[443, 488], 547, &OC3M_COEFS),
+        Satellites::Meris => (&[443, 490, 510], 560, &OC4E_COEFS),
+    }
+}
+
+/// Computes chlorophyll-a concentration (mg m^-3) with the OCx maximum band ratio algorithm
+/// (OC4 for SeaWiFS, OC3M for MODIS, OC4E for MERIS).
+///
+/// Bands are selected with `SatBands::closest_band`, so `rrs` must be keyed by the sensor
+/// wavelengths (nm) and contain above-water remote sensing reflectance (sr^-1).
+///
+/// Returns `NaN` if a required band is missing, negative, zero or not finite.
+pub fn ocx_chla(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> f64 {
+    let sat_bands = SatBands::new(satellite);
+    let (blue_targets, green_target, coefs) = ocx_parameters(satellite);
+
+    let valid_rrs = |wl: u32| {
+        rrs.get(&sat_bands.closest_band(wl))
+            .copied()
+            .filter(|v| v.is_finite() && *v > 0.0)
+    };
+
+    let Some(green) = valid_rrs(green_target) else {
+        return f64::NAN;
+    };
+
+    let mut blue_max = f64::NEG_INFINITY;
+    for &wl in blue_targets {
+        match valid_rrs(wl) {
+            Some(blue) => blue_max = blue_max.max(blue),
+            None => return f64::NAN,
+        }
+    }
+
+    let x = (blue_max / green).log10();
+    let log_chla = coefs
+        .iter()
+        .enumerate()
+        .map(|(i, a)| a * x.powi(i as i32))
+        .sum::<f64>();
+
+    10.0_f64.powf(log_chla)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ocx_chla_modis() {
+        let rrs = BTreeMap::from([(443, 0.0080), (488, 0.0070), (547, 0.0030)]);
+
+        // X = log10(0.008 / 0.003), chla = 10^(OC3M polynomial)
+        let chla = ocx_chla(&rrs, Satellites::Modis);
+        assert!((chla - 0.247666).abs() < 1e-5, "Got chla = {}", chla);
+    }
+
+    #[test]
+    fn test_ocx_chla_seawifs() {
+        let rrs = BTreeMap::from([
+            (412, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (510, 0.002500),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+
+        // X = log10(0.002974 / 0.001670), chla = 10^(OC4 polynomial)
+        let chla = ocx_chla(&rrs, Satellites::SeaWiFS);
+        assert!((chla - 0.504004).abs() < 1e-5, "Got chla = {}", chla);
+    }
+
+    #[test]
+    fn test_ocx_chla_invalid_inputs() {
+        let negative = BTreeMap::from([(443, -0.001), (488, 0.0070), (547, 0.0030)]);
+        assert!(ocx_chla(&negative, Satellites::Modis).is_nan());
+
+        let nan = BTreeMap::from([(443, 0.0080), (488, 0.0070), (547, f64::NAN)]);
+        assert!(ocx_chla(&nan, Satellites::Modis).is_nan());
+
+        let missing = BTreeMap::from([(443, 0.0080), (547, 0.0030)]);
+        assert!(ocx_chla(&missing, Satellites::Modis).is_nan());
+    }
+}
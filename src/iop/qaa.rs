@@ -28,10 +28,18 @@
 //!
 //! This implementation maintains strict compliance with NASA OCSSW:
 //! - **Constants**: G0=0.089, G1=0.125 (exact NASA values)
-//! - **Coefficients**: acoefs=[-1.146, -1.366, -0.469] for SeaWiFS/MODIS
+//! - **Coefficients**: acoefs=[-1.146, -1.366, -0.469] for SeaWiFS/MODIS,
+//!   [-1.204, -1.229, -0.395] for MERIS (see `SatBands::qaa_acoefs`)
 //! - **Rrs Conversion**: rrs = Rrs / (0.52 + 1.7 * Rrs)
-//! - **Reference Wavelength**: 555nm (primary reference as per NASA)
+//! - **Reference Wavelength**: 555nm (primary reference as per NASA), 670nm in turbid waters
+//!   where Rrs(670) >= 0.0015 sr^-1
 //! - **Quality Flagging**: Bitfield flags matching NASA OCSSW convention
+//! - **Bright Pixels**: u is clamped to `U_MAX` (flag 0x100), since `a = (1 - u) * bb / u` and
+//!   the reference bbp `u * a / (1 - u)` diverge as u approaches 1 on very bright (e.g.
+//!   coccolithophore bloom or sediment plume) pixels
+//! - **Raman Scattering and Fluorescence**: not corrected. Rrs is assumed to be elastic
+//!   reflectance only; in clear waters Raman scattering raises Rrs in the blue-green and in
+//!   productive waters chlorophyll fluorescence raises Rrs near 685nm, biasing the retrieved IOPs
 //!
 //! ## References
 //!
@@ -62,11 +70,15 @@
 //! ```
 
 use crate::iop::constants;
+use crate::iop::zeu;
 use crate::sat_bands::{SatBands, Satellites};
-use std::collections::BTreeMap;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::io::{self, Write};
+use std::sync::LazyLock;
 
 /// QAA algorithm results
 #[derive(Debug)]
@@ -79,15 +91,35 @@ pub struct QaaResult {
     acdom: Vec<f64>,       // CDOM (detrital+dissolved) absorption [m^-1]
     bb: Vec<f64>,          // Total backscattering [m^-1]
     bbp: Vec<f64>,         // Particulate backscattering [m^-1]
-    flags: u8,             // Quality flags [bitfield]
+    flags: u16,            // Quality flags [bitfield]
     chla: f64,             // Chla [mg/m^3]
     version: String,       // Algorithm version (e.g., "QAA v6")
     reference_wl: u32,     // Reference wavelength used [nm]
+    bbp_ref: f64,          // Particulate backscattering at the reference wavelength [m^-1]
     spectral_slope_y: f64, // Spectral slope Y for bbp
     spectral_slope_s: f64, // Spectral slope S for acdom
     aph_ratio_443: f64,    // aph/a ratio at 443nm for quality assessment
+    turbid: bool,          // Red reference branch taken (Rrs(670) >= RRS_670_THRESHOLD)
 }
 
+/// Inherent optical properties (m^-1) at one wavelength, see `QaaResult::at_wavelengths`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IopSet {
+    /// Total absorption
+    pub a: f64,
+    /// Phytoplankton absorption
+    pub aph: f64,
+    /// CDOM (detrital+dissolved) absorption
+    pub acdom: f64,
+    /// Total backscattering
+    pub bb: f64,
+    /// Particulate backscattering
+    pub bbp: f64,
+}
+
+// Spectral exponent of the pure water backscattering (Morel, 1974)
+const BBW_SPECTRAL_EXPONENT: f64 = 4.32;
+
 enum QAAMessage {
     InvalidData,
     NegativeBackscattering,
@@ -97,12 +129,37 @@ enum QAAMessage {
     ChlorophyllCalculationError,
     AphRatioForcedMax,
     BackscatteringLessThanWater,
+    UClamped,
 }
 
+// Bit of the `flags` field associated with each message
+const QAA_FLAGS: [(u16, QAAMessage); 9] = [
+    (0x01, QAAMessage::InvalidData),
+    (0x02, QAAMessage::NegativeBackscattering),
+    (0x04, QAAMessage::DecompositionError),
+    (0x08, QAAMessage::AphCorrectionApplied),
+    (0x10, QAAMessage::NegativeAphValues),
+    (0x20, QAAMessage::ChlorophyllCalculationError),
+    (0x40, QAAMessage::AphRatioForcedMax),
+    (0x80, QAAMessage::BackscatteringLessThanWater),
+    (0x100, QAAMessage::UClamped),
+];
+
+/// Flags of the results whose IOPs and chlorophyll are not usable: invalid input data, negative
+/// backscattering, failed absorption decomposition and chlorophyll calculation error. The other
+/// flags report corrections applied to an otherwise valid retrieval.
+pub const QAA_FAILURE_FLAGS: u16 = 0x01 | 0x02 | 0x04 | 0x20;
+
+/// Upper bound of u = bb / (a + bb). Very bright pixels give u >= 1, where the absorption and the
+/// reference backscattering are negative or infinite.
+pub const U_MAX: f64 = 0.999;
+
 impl QAAMessage {
     fn as_str(&self) -> &'static str {
         match self {
-            QAAMessage::InvalidData => "Invalid data for log calculation (negative Rrs ratios)",
+            QAAMessage::InvalidData => {
+                "Invalid input data (missing bands, non-positive Rrs or negative Rrs ratios) or parameters"
+            }
             QAAMessage::NegativeBackscattering => "Negative particulate backscattering detected",
             QAAMessage::DecompositionError => "Absorption decomposition error (division by zero)",
             QAAMessage::AphCorrectionApplied => "aph/a ratio correction applied at 443nm",
@@ -112,40 +169,279 @@ impl QAAMessage {
             QAAMessage::BackscatteringLessThanWater => {
                 "Backscattering less than water backscattering"
             }
+            QAAMessage::UClamped => "u clamped to 0.999 (very bright pixel)",
         }
     }
 }
 
 impl QaaResult {
-    pub fn get_messages(&self) -> Vec<String> {
-        let mut messages = Vec::new();
+    // Result with every IOP set to NaN, for inputs QAA cannot be run on
+    fn invalid(wavelengths: Vec<u32>, reference_wl: u32, flags: u16) -> Self {
+        let nan = vec![f64::NAN; wavelengths.len()];
 
-        if self.flags & 0x01 != 0 {
-            messages.push(QAAMessage::InvalidData.as_str().to_string());
-        }
-        if self.flags & 0x02 != 0 {
-            messages.push(QAAMessage::NegativeBackscattering.as_str().to_string());
+        Self {
+            wavelengths,
+            rrs: nan.clone(),
+            u: nan.clone(),
+            a: nan.clone(),
+            aph: nan.clone(),
+            acdom: nan.clone(),
+            bb: nan.clone(),
+            bbp: nan,
+            flags,
+            chla: f64::NAN,
+            version: "QAA v6".to_string(),
+            reference_wl,
+            bbp_ref: f64::NAN,
+            spectral_slope_y: f64::NAN,
+            spectral_slope_s: f64::NAN,
+            aph_ratio_443: f64::NAN,
+            turbid: false,
         }
-        if self.flags & 0x04 != 0 {
-            messages.push(QAAMessage::DecompositionError.as_str().to_string());
-        }
-        if self.flags & 0x08 != 0 {
-            messages.push(QAAMessage::AphCorrectionApplied.as_str().to_string());
-        }
-        if self.flags & 0x10 != 0 {
-            messages.push(QAAMessage::NegativeAphValues.as_str().to_string());
+    }
+
+    pub fn wavelengths(&self) -> &[u32] {
+        &self.wavelengths
+    }
+
+    pub fn chla(&self) -> f64 {
+        self.chla
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Whether the turbid water branch (red reference wavelength) was used
+    pub fn turbid(&self) -> bool {
+        self.turbid
+    }
+
+    /// Returns the value of `values` at the result wavelength closest to `wavelength`, so that
+    /// nominal wavelengths (e.g. 555) work for sensors with slightly shifted bands (e.g. 547).
+    fn value_at(&self, values: &[f64], wavelength: u32) -> f64 {
+        self.wavelengths
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &wl)| (wl as i32 - wavelength as i32).abs())
+            .and_then(|(index, _)| values.get(index).copied())
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Total absorption (m^-1) at the band closest to `wavelength`
+    pub fn a_at(&self, wavelength: u32) -> f64 {
+        self.value_at(&self.a, wavelength)
+    }
+
+    /// Phytoplankton absorption (m^-1) at the band closest to `wavelength`
+    pub fn aph_at(&self, wavelength: u32) -> f64 {
+        self.value_at(&self.aph, wavelength)
+    }
+
+    /// CDOM and detrital absorption (m^-1) at the band closest to `wavelength`
+    pub fn acdom_at(&self, wavelength: u32) -> f64 {
+        self.value_at(&self.acdom, wavelength)
+    }
+
+    /// Euphotic zone depth (m) from a(490) and bb(490) with the Lee et al. (2007) model, for a
+    /// 30° solar zenith angle. Returns `NaN` if no band is within 10nm of 490nm.
+    pub fn euphotic_depth(&self) -> f64 {
+        self.euphotic_depth_with_zenith(zeu::DEFAULT_SOLAR_ZENITH_DEG)
+    }
+
+    /// Same as `euphotic_depth`, for a given solar zenith angle in degrees
+    pub fn euphotic_depth_with_zenith(&self, solar_zenith_deg: f64) -> f64 {
+        let has_490 = self
+            .wavelengths
+            .iter()
+            .any(|&wl| (wl as i32 - 490).abs() <= 10);
+
+        if !has_490 {
+            return f64::NAN;
         }
-        if self.flags & 0x20 != 0 {
-            messages.push(QAAMessage::ChlorophyllCalculationError.as_str().to_string());
+
+        zeu::euphotic_depth_lee(self.a_at(490), self.bb_at(490), solar_zenith_deg)
+    }
+
+    /// Total backscattering (m^-1) at the band closest to `wavelength`
+    pub fn bb_at(&self, wavelength: u32) -> f64 {
+        self.value_at(&self.bb, wavelength)
+    }
+
+    /// Particulate backscattering (m^-1) at the band closest to `wavelength`
+    pub fn bbp_at(&self, wavelength: u32) -> f64 {
+        self.value_at(&self.bbp, wavelength)
+    }
+
+    /// IOPs at arbitrary wavelengths (nm), e.g. the native bands of the sensor. Wavelengths of the
+    /// result return the stored values; other wavelengths use the spectral models of QAA:
+    ///
+    /// - `bbp = bbp(ref) * (ref / wl)^Y` and `acdom = acdom(443) * exp(S * (443 - wl))`, defined
+    ///   at every wavelength. They are only meaningful in the visible (about 400-700 nm): the
+    ///   slopes are derived from blue/green ratios and extrapolation errors grow quickly outside.
+    /// - `bb = bbp + bbw`, with the water backscattering following a `wl^-4.32` power law from the
+    ///   closest tabulated value of `BBW_ALL`.
+    /// - `a` is `absorption_at`, the QAA relationship `a = (1 - u) * bb / u`, so both functions
+    ///   give the same total absorption. `u` is held at the closest result wavelength outside
+    ///   their range, which makes `a` rough far from them.
+    /// - `aph = a - aw - acdom`, as in the decomposition step, with `aw` interpolated in `AW_ALL`.
+    ///   It is not clamped and can be negative where the models above are off.
+    pub fn at_wavelengths(&self, wls: &[u32]) -> BTreeMap<u32, IopSet> {
+        wls.iter().map(|&wl| (wl, self.iops_at(wl))).collect()
+    }
+
+    // IOPs at one wavelength, see `at_wavelengths`
+    fn iops_at(&self, wl: u32) -> IopSet {
+        if let Some(index) = self.wavelengths.iter().position(|&w| w == wl) {
+            return IopSet {
+                a: self.a[index],
+                aph: self.aph[index],
+                acdom: self.acdom[index],
+                bb: self.bb[index],
+                bbp: self.bbp[index],
+            };
         }
-        if self.flags & 0x40 != 0 {
-            messages.push(QAAMessage::AphRatioForcedMax.as_str().to_string());
+
+        let bbp = self.particulate_backscattering_at(wl);
+        let bb = bbp + water_backscattering(wl);
+
+        // acdom is modelled from the band closest to 443nm, as in the decomposition step
+        let cyan_wl = self.closest_wavelength(443);
+        let acdom =
+            self.acdom_at(cyan_wl) * (self.spectral_slope_s * (cyan_wl as f64 - wl as f64)).exp();
+
+        let a = self.absorption_at(wl);
+        let aw = subset_optical_data_interp(&[wl], &constants::AW_ALL)[&wl];
+
+        IopSet {
+            a,
+            aph: a - aw - acdom,
+            acdom,
+            bb,
+            bbp,
         }
-        if self.flags & 0x80 != 0 {
-            messages.push(QAAMessage::BackscatteringLessThanWater.as_str().to_string());
+    }
+
+    /// Particulate backscattering at the reference wavelength (m^-1), from which the bbp spectrum
+    /// is derived
+    pub fn bbp_ref(&self) -> f64 {
+        self.bbp_ref
+    }
+
+    /// Total backscattering (m^-1) at any wavelength: `bbp_ref * (ref / wl)^Y + bbw(wl)`. Matches
+    /// `bb_at` at the result wavelengths. See `at_wavelengths` for the validity range.
+    pub fn backscattering_at(&self, wl: u32) -> f64 {
+        self.particulate_backscattering_at(wl) + water_backscattering(wl)
+    }
+
+    /// Total absorption (m^-1) at any wavelength from `backscattering_at` and the QAA relationship
+    /// `a = (1 - u) * bb / u`. `u` is interpolated between the result wavelengths and held at the
+    /// closest one outside their range, so values far from the result wavelengths are rough.
+    /// Matches `a_at` at the result wavelengths and the `a` of `at_wavelengths` everywhere.
+    pub fn absorption_at(&self, wl: u32) -> f64 {
+        let data: BTreeMap<u32, f64> = self
+            .wavelengths
+            .iter()
+            .copied()
+            .zip(self.u.iter().copied())
+            .collect();
+        let u = subset_optical_data_interp(&[wl], &data)[&wl];
+
+        (1.0 - u) * self.backscattering_at(wl) / u
+    }
+
+    fn particulate_backscattering_at(&self, wl: u32) -> f64 {
+        self.bbp_ref * (self.reference_wl as f64 / wl as f64).powf(self.spectral_slope_y)
+    }
+
+    fn closest_wavelength(&self, wavelength: u32) -> u32 {
+        self.wavelengths
+            .iter()
+            .copied()
+            .min_by_key(|&wl| (wl as i32 - wavelength as i32).abs())
+            .unwrap_or(wavelength)
+    }
+
+    /// Writes the result as CSV: the scalar results as `#` comment lines, then a
+    /// `wavelength,rrs,u,a,aph,acdom,bb,bbp` header and one row per wavelength.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "# version: {}", self.version)?;
+        writeln!(w, "# reference_wl: {}", self.reference_wl)?;
+        writeln!(w, "# bbp_ref: {}", self.bbp_ref)?;
+        writeln!(w, "# chla: {}", self.chla)?;
+        writeln!(w, "# flags: {}", self.flags)?;
+        writeln!(w, "# spectral_slope_y: {}", self.spectral_slope_y)?;
+        writeln!(w, "# spectral_slope_s: {}", self.spectral_slope_s)?;
+        writeln!(w, "# aph_ratio_443: {}", self.aph_ratio_443)?;
+        writeln!(w, "# turbid: {}", self.turbid)?;
+        writeln!(w, "wavelength,rrs,u,a,aph,acdom,bb,bbp")?;
+
+        for (i, wl) in self.wavelengths.iter().enumerate() {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{}",
+                wl,
+                self.rrs[i],
+                self.u[i],
+                self.a[i],
+                self.aph[i],
+                self.acdom[i],
+                self.bb[i],
+                self.bbp[i]
+            )?;
         }
 
-        messages
+        Ok(())
+    }
+
+    /// Same as `write_csv`, returned as a String
+    pub fn to_csv(&self) -> String {
+        let mut buffer = Vec::new();
+        // Writing to a Vec never fails
+        self.write_csv(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    /// Returns the description of every quality flag set for this result
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        QAA_FLAGS
+            .iter()
+            .filter(|(mask, _)| self.flags & mask != 0)
+            .map(|(_, message)| message.as_str())
+            .collect()
+    }
+
+    pub fn get_messages(&self) -> Vec<String> {
+        self.flag_names()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+}
+
+// Serialized like the struct fields, plus `flag_messages` listing the active flags
+impl Serialize for QaaResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("QaaResult", 18)?;
+        state.serialize_field("version", &self.version)?;
+        state.serialize_field("reference_wl", &self.reference_wl)?;
+        state.serialize_field("bbp_ref", &self.bbp_ref)?;
+        state.serialize_field("wavelengths", &self.wavelengths)?;
+        state.serialize_field("rrs", &self.rrs)?;
+        state.serialize_field("u", &self.u)?;
+        state.serialize_field("a", &self.a)?;
+        state.serialize_field("aph", &self.aph)?;
+        state.serialize_field("acdom", &self.acdom)?;
+        state.serialize_field("bb", &self.bb)?;
+        state.serialize_field("bbp", &self.bbp)?;
+        state.serialize_field("chla", &self.chla)?;
+        state.serialize_field("spectral_slope_y", &self.spectral_slope_y)?;
+        state.serialize_field("spectral_slope_s", &self.spectral_slope_s)?;
+        state.serialize_field("aph_ratio_443", &self.aph_ratio_443)?;
+        state.serialize_field("turbid", &self.turbid)?;
+        state.serialize_field("flags", &self.flags)?;
+        state.serialize_field("flag_messages", &self.flag_names())?;
+        state.end()
     }
 }
 
@@ -153,12 +449,12 @@ impl Display for QaaResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(
             f,
-            "{} (reference: {} nm) — Flags: 0x{:02X}",
+            "{} (reference: {} nm) — Flags: 0x{:03X}",
             self.version, self.reference_wl, self.flags
         )?;
 
         // Print any quality messages (if present)
-        let messages = self.get_messages();
+        let messages = self.flag_names();
         if !messages.is_empty() {
             writeln!(f, "Messages:")?;
             for msg in messages {
@@ -229,6 +525,57 @@ pub fn subset_optical_data(wavelengths: &[u32], data: &BTreeMap<u32, f64>) -> BT
         .collect()
 }
 
+/// Linearly interpolates `data` at each of the `wavelengths`. Wavelengths outside the tabulated
+/// range take the value of the closest end of the table.
+pub fn subset_optical_data_interp(
+    wavelengths: &[u32],
+    data: &BTreeMap<u32, f64>,
+) -> BTreeMap<u32, f64> {
+    wavelengths
+        .iter()
+        .map(|&lambda| {
+            let below = data.range(..=lambda).next_back();
+            let above = data.range(lambda..).next();
+
+            let value = match (below, above) {
+                (Some((&wl0, &v0)), Some((&wl1, &v1))) if wl1 > wl0 => {
+                    let t = (lambda - wl0) as f64 / (wl1 - wl0) as f64;
+                    v0 + t * (v1 - v0)
+                }
+                (Some((_, &v)), _) | (None, Some((_, &v))) => v,
+                (None, None) => f64::NAN,
+            };
+
+            (lambda, value)
+        })
+        .collect()
+}
+
+// Pure water backscattering (m^-1) at `wavelength`, from the closest tabulated value of
+// `BBW_ALL` with a `wavelength^-4.32` power law
+fn water_backscattering(wavelength: u32) -> f64 {
+    let closest = *constants::BBW_ALL
+        .keys()
+        .min_by_key(|&&wl| (wl as i32 - wavelength as i32).abs())
+        .unwrap();
+
+    constants::BBW_ALL[&closest] * (closest as f64 / wavelength as f64).powf(BBW_SPECTRAL_EXPONENT)
+}
+
+/// Pure water absorption (m^-1) at `wavelength` corrected for temperature and salinity:
+/// `aw(T, S) = aw + Ψ_T (T - T_ref) + Ψ_S S`, with `aw` from `AW_ALL` measured at
+/// `AW_REFERENCE_TEMPERATURE_C` in fresh water (Röttgers et al. 2014; Sullivan et al. 2006).
+///
+/// The coefficients are tabulated at the sensor wavelengths between 410 and 678 nm. Other
+/// wavelengths take the values of the closest tabulated wavelength.
+pub fn aw_corrected(wavelength: u32, temperature_c: f64, salinity_psu: f64) -> f64 {
+    let closest = |data: &BTreeMap<u32, f64>| subset_optical_data(&[wavelength], data)[&wavelength];
+
+    closest(&constants::AW_ALL)
+        + closest(&constants::PSI_T_ALL) * (temperature_c - constants::AW_REFERENCE_TEMPERATURE_C)
+        + closest(&constants::PSI_S_ALL) * salinity_psu
+}
+
 fn calculate_acdom_absorption(
     wavelengths: &[u32],
     ag440: f64,
@@ -264,29 +611,283 @@ fn calculate_phytoplankton_absorption(
 // From <https://www.ioccg.org/groups/Software_OCA/QAA_v5.pdf>
 // The 555 nm used in Eqs. 7-10 can be changed to 550 nm (for MODIS) or 560 nm (for MERIS) without
 // causing significant impacts on final IOP results.
+// Sensor-dependent inputs of QAA v6: the bands mapped to the NASA target wavelengths and the
+// optical constants at these bands. They don't depend on the pixel, so they are built once per
+// sensor (see QAA_SETUPS).
+#[derive(Clone)]
+struct QaaSetup {
+    wavelengths: Vec<u32>,
+    red_wl: u32,
+    green_wl: u32,
+    blue_wl: u32,
+    cyan_wl: u32,
+    violet_wl: u32,
+    aw: BTreeMap<u32, f64>,
+    bbw: BTreeMap<u32, f64>,
+    aphstar: BTreeMap<u32, f64>,
+}
+
+impl QaaSetup {
+    fn new(satellite: Satellites, sampling: OpticalDataSampling) -> Self {
+        // NASA QAA v6 target wavelengths (nm)
+        let nasa_target_wavelengths = [410, 443, 490, 555, 670];
+
+        // Create SatBands for wavelength mapping
+        let sat_bands = SatBands::new(satellite);
+
+        // Map NASA target wavelengths to closest available satellite bands
+        let wavelengths: Vec<u32> = nasa_target_wavelengths
+            .iter()
+            .map(|&target| sat_bands.closest_band(target))
+            .collect();
+
+        // Subset aw, bbw, and aphstar to the mapped wavelengths
+        let subset = match sampling {
+            OpticalDataSampling::Nearest => subset_optical_data,
+            OpticalDataSampling::Linear => subset_optical_data_interp,
+        };
+        let aw = subset(&wavelengths, &constants::AW_ALL);
+        let bbw = subset(&wavelengths, &constants::BBW_ALL);
+        let aphstar = subset(&wavelengths, &constants::APHSTAR_ALL);
+
+        Self {
+            red_wl: sat_bands.closest_band(670),
+            green_wl: sat_bands.closest_band(555), // reference wavelength
+            blue_wl: sat_bands.closest_band(490),
+            cyan_wl: sat_bands.closest_band(443),
+            violet_wl: sat_bands.closest_band(410), // NASA uses 410, not 412
+            wavelengths,
+            aw,
+            bbw,
+            aphstar,
+        }
+    }
+}
+
+/// How the optical constants (aw, bbw, aphstar) are sampled at the sensor wavelengths
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OpticalDataSampling {
+    /// Value of the closest tabulated wavelength, as in the NASA implementation
+    #[default]
+    Nearest,
+    /// Linear interpolation between the surrounding tabulated wavelengths
+    Linear,
+}
+
+// QaaSetup of every sensor and sampling, built on first use and shared by all qaa_v6 calls
+static QAA_SETUPS: LazyLock<HashMap<(Satellites, OpticalDataSampling), QaaSetup>> =
+    LazyLock::new(|| {
+        let mut setups = HashMap::new();
+
+        for satellite in Satellites::ALL {
+            for sampling in [OpticalDataSampling::Nearest, OpticalDataSampling::Linear] {
+                setups.insert((satellite, sampling), QaaSetup::new(satellite, sampling));
+            }
+        }
+
+        setups
+    });
+
+fn cached_setup(satellite: Satellites, sampling: OpticalDataSampling) -> &'static QaaSetup {
+    &QAA_SETUPS[&(satellite, sampling)]
+}
+
+/// Tunable parameters of QAA v6. `Default` gives the NASA OCSSW values for SeaWiFS and
+/// `for_satellite` the values for a given sensor; regional variants can override any of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QaaParams {
+    /// Lowest accepted aph/a ratio at 443nm
+    pub aph_ratio_min: f64,
+    /// Highest accepted aph/a ratio at 443nm
+    pub aph_ratio_max: f64,
+    /// Coefficients of the reference absorption polynomial (clear waters), see
+    /// `SatBands::qaa_acoefs`
+    pub acoefs: [f64; 3],
+    /// Coefficients of the rrs to u relationship (Gordon et al. 1988)
+    pub g0: f64,
+    pub g1: f64,
+    /// Spectral slope of the CDOM absorption
+    pub s: f64,
+    /// Forces the reference wavelength used for bbp(ref), the bb power law and the reported
+    /// `reference_wl`, e.g. 550 for MODIS or 560 for MERIS. The QAA wavelength closest to the
+    /// requested one is used. `None` keeps the band mapped to 555nm. Ignored in turbid waters,
+    /// where the red band is always the reference.
+    pub reference_wl: Option<u32>,
+    /// Input Rrs (sr^-1) at or below which a QAA band is rejected. Pixels with such a band, or a
+    /// non-finite one, give an all-NaN result flagged as invalid data.
+    pub min_rrs: f64,
+}
+
+impl Default for QaaParams {
+    fn default() -> Self {
+        Self {
+            aph_ratio_min: 0.15,
+            aph_ratio_max: 0.6,
+            acoefs: [constants::C1, constants::C2, constants::C3],
+            g0: constants::G0,
+            g1: constants::G1,
+            s: constants::S,
+            reference_wl: None,
+            min_rrs: 0.0,
+        }
+    }
+}
+
+impl QaaParams {
+    /// Default parameters with the sensor-specific `acoefs`
+    pub fn for_satellite(satellite: Satellites) -> Self {
+        Self {
+            acoefs: SatBands::new(satellite).qaa_acoefs(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the parameters can be used: finite aph/a ratio bounds, the lowest one not above
+    /// the highest one. `qaa_v6_with_params` gives an all-NaN result flagged as invalid data
+    /// otherwise.
+    pub fn is_valid(&self) -> bool {
+        self.aph_ratio_min.is_finite()
+            && self.aph_ratio_max.is_finite()
+            && self.aph_ratio_min <= self.aph_ratio_max
+    }
+}
+
 pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
-    // Initialize quality flags
-    let mut flags = 0u8;
+    qaa_v6_with_params(rrs, satellite, &QaaParams::for_satellite(satellite))
+}
+
+/// Same as `qaa_v6`, with custom algorithm parameters
+pub fn qaa_v6_with_params(
+    rrs: &BTreeMap<u32, f64>,
+    satellite: Satellites,
+    params: &QaaParams,
+) -> QaaResult {
+    qaa_v6_with_setup(
+        rrs,
+        cached_setup(satellite, OpticalDataSampling::Nearest),
+        params,
+    )
+}
 
-    // NASA QAA v6 target wavelengths (nm)
-    let nasa_target_wavelengths = [410, 443, 490, 555, 670];
+/// Same as `qaa_v6`, choosing how the optical constants are sampled at the sensor wavelengths
+pub fn qaa_v6_with_sampling(
+    rrs: &BTreeMap<u32, f64>,
+    satellite: Satellites,
+    sampling: OpticalDataSampling,
+) -> QaaResult {
+    qaa_v6_with_setup(
+        rrs,
+        cached_setup(satellite, sampling),
+        &QaaParams::for_satellite(satellite),
+    )
+}
+
+/// Same as `qaa_v6`, forcing the reference wavelength (see `QaaParams::reference_wl`)
+pub fn qaa_v6_with_reference(
+    rrs: &BTreeMap<u32, f64>,
+    satellite: Satellites,
+    reference_wl: Option<u32>,
+) -> QaaResult {
+    let params = QaaParams {
+        reference_wl,
+        ..QaaParams::for_satellite(satellite)
+    };
 
-    // Create SatBands for wavelength mapping
-    let sat_bands = SatBands::new(satellite);
+    qaa_v6_with_params(rrs, satellite, &params)
+}
 
-    // Map NASA target wavelengths to closest available satellite bands
-    let wavelengths: Vec<u32> = nasa_target_wavelengths
+/// Same as `qaa_v6`, with the water absorption corrected for the water temperature (°C) and
+/// salinity (PSU) using `aw_corrected`. A missing temperature defaults to
+/// `AW_REFERENCE_TEMPERATURE_C` and a missing salinity to 0. When both are `None` the static
+/// `AW_ALL` table is used, as in `qaa_v6`.
+pub fn qaa_v6_with_water(
+    rrs: &BTreeMap<u32, f64>,
+    satellite: Satellites,
+    temperature_c: Option<f64>,
+    salinity_psu: Option<f64>,
+) -> QaaResult {
+    let setup = cached_setup(satellite, OpticalDataSampling::Nearest);
+    let params = QaaParams::for_satellite(satellite);
+
+    if temperature_c.is_none() && salinity_psu.is_none() {
+        return qaa_v6_with_setup(rrs, setup, &params);
+    }
+
+    let temperature_c = temperature_c.unwrap_or(constants::AW_REFERENCE_TEMPERATURE_C);
+    let salinity_psu = salinity_psu.unwrap_or(0.0);
+
+    let mut setup = setup.clone();
+    for (&wl, aw) in setup.aw.iter_mut() {
+        *aw = aw_corrected(wl, temperature_c, salinity_psu);
+    }
+
+    qaa_v6_with_setup(rrs, &setup, &params)
+}
+
+/// Runs `qaa_v6` on many reflectance spectra from the same sensor
+pub fn qaa_v6_batch(pixels: &[BTreeMap<u32, f64>], satellite: Satellites) -> Vec<QaaResult> {
+    let setup = cached_setup(satellite, OpticalDataSampling::Nearest);
+    let params = QaaParams::for_satellite(satellite);
+
+    pixels
         .iter()
-        .map(|&target| sat_bands.closest_band(target))
-        .collect();
+        .map(|rrs| qaa_v6_with_setup(rrs, setup, &params))
+        .collect()
+}
 
-    // Subset aw, bbw, and aphstar to the mapped wavelengths
-    let aw = subset_optical_data(&wavelengths, &constants::AW_ALL);
-    let bbw = subset_optical_data(&wavelengths, &constants::BBW_ALL);
-    let aphstar = subset_optical_data(&wavelengths, &constants::APHSTAR_ALL);
+// Checks that the wavelengths are distinct and each one has a distinct closest input band
+fn has_distinct_bands(wavelengths: &[u32], rrs: &BTreeMap<u32, f64>) -> bool {
+    let mut input_bands = Vec::with_capacity(wavelengths.len());
+
+    for (index, &lambda) in wavelengths.iter().enumerate() {
+        if wavelengths[..index].contains(&lambda) {
+            return false;
+        }
+
+        match rrs
+            .keys()
+            .min_by_key(|&&wl| (wl as i32 - lambda as i32).abs())
+        {
+            Some(band) if !input_bands.contains(band) => input_bands.push(*band),
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+fn qaa_v6_with_setup(rrs: &BTreeMap<u32, f64>, setup: &QaaSetup, params: &QaaParams) -> QaaResult {
+    // Initialize quality flags
+    let mut flags = 0u16;
+
+    let wavelengths = setup.wavelengths.clone();
+    let aw = &setup.aw;
+    let bbw = &setup.bbw;
+    let aphstar = &setup.aphstar;
+
+    // The aph/a ratio is clamped to the parameter bounds, which must be ordered
+    if !params.is_valid() {
+        return QaaResult::invalid(wavelengths, setup.green_wl, 0x01);
+    }
+
+    // Each of the five QAA wavelengths needs its own input band. When two NASA targets map to the
+    // same satellite band, or to the same input band (e.g. a single-band input), the band ratios
+    // are meaningless: return an all-NaN result flagged as invalid data.
+    if !has_distinct_bands(&wavelengths, rrs) {
+        return QaaResult::invalid(wavelengths, setup.green_wl, 0x01);
+    }
 
     let mut rrs = subset_optical_data(&wavelengths, rrs);
 
+    // Negative or missing reflectances (e.g. atmospheric correction failures) would propagate
+    // NaNs and infinities through every step and end in a misleading chlorophyll
+    if rrs.values().any(|v| !v.is_finite() || *v <= params.min_rrs) {
+        return QaaResult::invalid(wavelengths, setup.green_wl, 0x01);
+    }
+
+    // Above-water Rrs, used to select and compute the turbid water branch
+    let rrs_above = rrs.clone();
+
     // Convert rrs to below sea level (NASA formulation)
     rrs.iter_mut()
         .for_each(|(_k, v)| *v = *v / (0.52 + (1.7 * *v)));
@@ -295,30 +896,54 @@ pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
     let u: BTreeMap<u32, f64> = rrs
         .iter()
         .map(|(k, v)| {
-            let u = ((constants::G0.powi(2) + 4.0 * constants::G1 * v).sqrt() - constants::G0)
-                / (2.0 * constants::G1);
+            let u =
+                ((params.g0.powi(2) + 4.0 * params.g1 * v).sqrt() - params.g0) / (2.0 * params.g1);
 
             (*k, u)
         })
         .collect();
 
+    // Keep u below 1 on very bright pixels, the IOPs diverge otherwise
+    let u: BTreeMap<u32, f64> = u
+        .into_iter()
+        .map(|(k, u)| {
+            if u > U_MAX {
+                flags |= 0x100; // Set u clamped flag
+                (k, U_MAX)
+            } else {
+                (k, u)
+            }
+        })
+        .collect();
+
     // Step 2: Determine reference wavelength and absorption coefficient (NASA OCSSW approach)
-    // Map NASA target wavelengths to actual satellite bands
-    let red_wl = sat_bands.closest_band(670);
-    let green_wl = sat_bands.closest_band(555); // reference wavelength
-    let blue_wl = sat_bands.closest_band(490);
-    let cyan_wl = sat_bands.closest_band(443);
-    let violet_wl = sat_bands.closest_band(410); // NASA uses 410, not 412
-
-    // NASA QAA v6 uses 555nm as primary reference wavelength
-    let wvlref = green_wl;
+    // Target wavelengths mapped to actual satellite bands
+    let red_wl = setup.red_wl;
+    let green_wl = setup.green_wl;
+    let blue_wl = setup.blue_wl;
+    let cyan_wl = setup.cyan_wl;
+    let violet_wl = setup.violet_wl;
+
+    // QAA v6 switches to the red band as reference in turbid waters, where Rrs(670) is high
+    let turbid = rrs_above[&red_wl] >= constants::RRS_670_THRESHOLD;
+
+    // NASA QAA v6 uses 555nm as primary reference wavelength in clear waters, unless the caller
+    // forces another one. It must be one of the QAA wavelengths since u, aw and bbw are only
+    // known there.
+    let wvlref = match params.reference_wl {
+        _ if turbid => red_wl,
+        Some(target) => *wavelengths
+            .iter()
+            .min_by_key(|&&wl| (wl as i32 - target as i32).abs())
+            .unwrap_or(&green_wl),
+        None => green_wl,
+    };
     let rrs_443 = rrs.get(&cyan_wl).unwrap();
     let rrs_490 = rrs.get(&blue_wl).unwrap();
     let rrs_555 = rrs.get(&green_wl).unwrap();
     let rrs_670 = rrs.get(&red_wl).unwrap();
 
-    // NASA OCSSW coefficients for SeaWiFS
-    let acoefs = [constants::C1, constants::C2, constants::C3];
+    let acoefs = params.acoefs;
 
     // Calculate ratio for absorption estimation
     let numer = rrs_443 + rrs_490;
@@ -329,9 +954,15 @@ pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
         flags |= 0x01; // Set invalid data flag
     }
 
-    let aux = (numer / denom).max(1e-10).log10();
-    let rho = acoefs[0] + acoefs[1] * aux + acoefs[2] * aux.powi(2);
-    let aref = aw.get(&wvlref).unwrap() + 10.0_f64.powf(rho);
+    let aref = if turbid {
+        // Red reference: a(670) = aw(670) + h0 * (Rrs(670) / (Rrs(443) + Rrs(490)))^h1
+        let ratio = rrs_above[&red_wl] / (rrs_above[&cyan_wl] + rrs_above[&blue_wl]);
+        aw.get(&wvlref).unwrap() + constants::RED_REF_H0 * ratio.powf(constants::RED_REF_H1)
+    } else {
+        let aux = (numer / denom).max(1e-10).log10();
+        let rho = acoefs[0] + acoefs[1] * aux + acoefs[2] * aux.powi(2);
+        aw.get(&wvlref).unwrap() + 10.0_f64.powf(rho)
+    };
 
     // Step 3: Calculate reference backscattering
     let u_ref = u.get(&wvlref).unwrap();
@@ -373,7 +1004,7 @@ pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
     let symbol = 0.74 + 0.2 / (0.8 + rat);
 
     // Step 8: Calculate spectral slope Sr (NASA formulation)
-    let sr = constants::S + 0.002 / (0.6 + rat);
+    let sr = params.s + 0.002 / (0.6 + rat);
     let zeta = (sr * (cyan_wl as f64 - violet_wl as f64)).exp(); // Use actual mapped wavelengths
 
     // Step 9: Calculate ag at 443nm and decompose absorption
@@ -395,26 +1026,27 @@ pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
 
     // Calculate initial adg and aph using helper functions
     let initial_adg = calculate_acdom_absorption(&wavelengths, acdom443, sr, cyan_wl);
-    let initial_aph = calculate_phytoplankton_absorption(&wavelengths, &a, &initial_adg, &aw);
+    let initial_aph = calculate_phytoplankton_absorption(&wavelengths, &a, &initial_adg, aw);
 
     // Check and correct aph at 443nm (NASA bounds)
     let mut x1 = initial_aph.get(&cyan_wl).unwrap() / a_443;
 
-    // NASA QAA v6: aph proportion should be between 0.15 and 0.6
-    if !(0.15..=0.6).contains(&x1) || !x1.is_finite() {
+    // NASA QAA v6: aph proportion should be between 0.15 and 0.6 (default bounds)
+    let aph_ratio_bounds = params.aph_ratio_min..=params.aph_ratio_max;
+    if !aph_ratio_bounds.contains(&x1) || !x1.is_finite() {
         x1 = -0.8 + 1.4 * (a_443 - aw_443) / (a_410 - aw_410);
         flags |= 0x08; // Set aph correction flag
     }
 
-    // Clamp to NASA bounds
-    x1 = x1.clamp(0.15, 0.6);
+    // Clamp to the bounds
+    x1 = x1.clamp(params.aph_ratio_min, params.aph_ratio_max);
 
     // Recalculate acdom443 based on corrected aph at 443nm
     let corrected_acdom443 = a_443 - (a_443 * x1) - aw_443;
 
     // Final calculations with corrected acdom443
     let mut acdom = calculate_acdom_absorption(&wavelengths, corrected_acdom443, sr, cyan_wl);
-    let mut aph = calculate_phytoplankton_absorption(&wavelengths, &a, &acdom, &aw);
+    let mut aph = calculate_phytoplankton_absorption(&wavelengths, &a, &acdom, aw);
 
     // Handle negative aph values (NASA QAA v6 approach)
     for (&wl, aph_val) in aph.iter_mut() {
@@ -483,8 +1115,479 @@ pub fn qaa_v6(rrs: &BTreeMap<u32, f64>, satellite: Satellites) -> QaaResult {
         chla,
         version: "QAA v6".to_string(),
         reference_wl: wvlref,
+        bbp_ref: bbpref,
         spectral_slope_y: y,
         spectral_slope_s: sr,
         aph_ratio_443: x1,
+        turbid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spectral_reconstruction_matches_anchors() {
+        let rrs = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+        let result = qaa_v6(&rrs, Satellites::Modis);
+
+        assert!((result.bbp_ref() - result.bbp_at(result.reference_wl)).abs() < 1e-12);
+
+        for &wl in result.wavelengths() {
+            let bb = result.bb_at(wl);
+            let a = result.a_at(wl);
+            assert!((result.backscattering_at(wl) - bb).abs() <= 1e-12 * bb.abs().max(1.0));
+            assert!((result.absorption_at(wl) - a).abs() <= 1e-9 * a.abs().max(1.0));
+        }
+
+        // Continuous spectrum between the anchors
+        let bb_500 = result.backscattering_at(500);
+        assert!(bb_500 < result.bb_at(488) && bb_500 > result.bb_at(547));
+        assert!(result.absorption_at(500).is_finite());
+    }
+
+    #[test]
+    fn test_at_wavelengths() {
+        let rrs = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+        let result = qaa_v6(&rrs, Satellites::Modis);
+        let iops = result.at_wavelengths(&[443, 500, 600, 700]);
+
+        // Result wavelengths return the stored values
+        assert_eq!(iops[&443].a, result.a_at(443));
+        assert_eq!(iops[&443].bbp, result.bbp_at(443));
+
+        // Spectral models between and beyond the result wavelengths
+        let y = result.spectral_slope_y;
+        let expected_bbp =
+            result.bbp_at(result.reference_wl) * (result.reference_wl as f64 / 600.0).powf(y);
+        assert!((iops[&600].bbp - expected_bbp).abs() < 1e-12);
+        assert!(iops[&600].bb > iops[&600].bbp);
+
+        let s = result.spectral_slope_s;
+        let expected_acdom = result.acdom_at(443) * (s * (443.0 - 500.0)).exp();
+        assert!((iops[&500].acdom - expected_acdom).abs() < 1e-12);
+        assert!(iops[&500].aph.is_finite());
+        assert!(iops[&500].a > iops[&500].aph + iops[&500].acdom);
+
+        // Same total absorption as absorption_at, also beyond 667nm
+        for wl in [500, 600, 700] {
+            assert_eq!(iops[&wl].a, result.absorption_at(wl));
+        }
+        let aw_700 = subset_optical_data_interp(&[700], &constants::AW_ALL)[&700];
+        assert!(iops[&700].a.is_finite());
+        assert!((iops[&700].aph - (iops[&700].a - aw_700 - iops[&700].acdom)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cached_setup_gives_identical_results() {
+        let rrs = BTreeMap::from([
+            (410, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+
+        for satellite in Satellites::ALL {
+            let cached = qaa_v6(&rrs, satellite);
+            let fresh = qaa_v6_with_setup(
+                &rrs,
+                &QaaSetup::new(satellite, OpticalDataSampling::Nearest),
+                &QaaParams::for_satellite(satellite),
+            );
+
+            assert_eq!(cached.wavelengths, fresh.wavelengths);
+            assert_eq!(cached.flags, fresh.flags);
+            assert_eq!(cached.chla.to_bits(), fresh.chla.to_bits());
+            for (c, f) in [
+                (&cached.a, &fresh.a),
+                (&cached.aph, &fresh.aph),
+                (&cached.acdom, &fresh.acdom),
+                (&cached.bbp, &fresh.bbp),
+            ] {
+                let c_bits: Vec<u64> = c.iter().map(|v| v.to_bits()).collect();
+                let f_bits: Vec<u64> = f.iter().map(|v| v.to_bits()).collect();
+                assert_eq!(c_bits, f_bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_qaa_v6_with_reference() {
+        let rrs = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+
+        let default = qaa_v6(&rrs, Satellites::Modis);
+        assert_eq!(default.reference_wl, 547);
+
+        // 550 maps to the same MODIS band as 555
+        let modis_550 = qaa_v6_with_reference(&rrs, Satellites::Modis, Some(550));
+        assert_eq!(modis_550.reference_wl, 547);
+        assert_eq!(modis_550.chla.to_bits(), default.chla.to_bits());
+
+        let red = qaa_v6_with_reference(&rrs, Satellites::Modis, Some(670));
+        assert_eq!(red.reference_wl, 667);
+    }
+
+    #[test]
+    fn test_bright_pixel_clamps_u() {
+        // Sediment plume, u > 1 at 555 and 670nm without clamping
+        let rrs = BTreeMap::from([
+            (412, 0.020),
+            (443, 0.030),
+            (490, 0.060),
+            (510, 0.080),
+            (555, 0.200),
+            (670, 0.180),
+        ]);
+
+        let result = qaa_v6(&rrs, Satellites::SeaWiFS);
+
+        assert_eq!(result.flags & 0x100, 0x100);
+        assert!(result.u.iter().all(|&u| u <= U_MAX));
+        assert!(result.a.iter().all(|a| a.is_finite() && *a > 0.0));
+        assert!(result.bb.iter().all(|bb| bb.is_finite()));
+        assert!(
+            result
+                .flag_names()
+                .contains(&"u clamped to 0.999 (very bright pixel)")
+        );
+
+        // Typical waters are not affected
+        let clear = qaa_v6(
+            &BTreeMap::from([
+                (412, 0.001974),
+                (443, 0.002570),
+                (490, 0.002974),
+                (510, 0.002500),
+                (555, 0.001670),
+                (670, 0.000324),
+            ]),
+            Satellites::SeaWiFS,
+        );
+        assert_eq!(clear.flags & 0x100, 0);
+    }
+
+    #[test]
+    fn test_invalid_rrs_gives_nan_result() {
+        let valid = BTreeMap::from([
+            (410, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+        assert!(qaa_v6(&valid, Satellites::SeaWiFS).chla.is_finite());
+
+        for (wl, value) in [
+            (443, -0.0005),
+            (555, f64::NAN),
+            (670, 0.0),
+            (410, f64::INFINITY),
+        ] {
+            let mut rrs = valid.clone();
+            rrs.insert(wl, value);
+
+            let result = qaa_v6(&rrs, Satellites::SeaWiFS);
+            assert!(result.chla.is_nan(), "Rrs({wl}) = {value}");
+            assert!(result.a.iter().chain(&result.bbp).all(|v| v.is_nan()));
+            assert_eq!(result.flags, 0x01);
+        }
+
+        // Stricter threshold
+        let params = QaaParams {
+            min_rrs: 0.0005,
+            ..QaaParams::for_satellite(Satellites::SeaWiFS)
+        };
+        let result = qaa_v6_with_params(&valid, Satellites::SeaWiFS, &params);
+        assert!(result.chla.is_nan());
+        assert_eq!(result.flags, 0x01);
+    }
+
+    #[test]
+    fn test_single_band_input_does_not_panic() {
+        let result = qaa_v6(&BTreeMap::from([(443, 0.002)]), Satellites::Modis);
+
+        assert!(result.chla.is_nan());
+        assert!(result.a.iter().all(|v| v.is_nan()));
+        assert_eq!(result.flags & 0x01, 0x01);
+
+        let result = qaa_v6(&BTreeMap::new(), Satellites::SeaWiFS);
+        assert!(result.chla.is_nan());
+    }
+
+    #[test]
+    fn test_euphotic_depth() {
+        // Example SeaWiFS spectrum of examples/qaa_example.rs
+        let rrs = BTreeMap::from([
+            (410, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+
+        let result = qaa_v6(&rrs, Satellites::SeaWiFS);
+        let zeu = result.euphotic_depth();
+
+        assert!(zeu.is_finite());
+        assert_eq!(
+            zeu,
+            zeu::euphotic_depth_lee(result.a_at(490), result.bb_at(490), 30.0)
+        );
+        // Mesotrophic water, Zeu between the turbid coastal and clearest oceanic values
+        assert!((10.0..150.0).contains(&zeu), "zeu = {}", zeu);
+
+        assert!(
+            qaa_v6(&BTreeMap::new(), Satellites::SeaWiFS)
+                .euphotic_depth()
+                .is_nan()
+        );
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let rrs = BTreeMap::from([
+            (410, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+
+        let result = qaa_v6(&rrs, Satellites::SeaWiFS);
+        let csv = result.to_csv();
+
+        let rows: Vec<&str> = csv.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(rows[0], "wavelength,rrs,u,a,aph,acdom,bb,bbp");
+        assert_eq!(rows.len(), 1 + result.wavelengths.len());
+        assert!(rows[1].starts_with("412,"));
+        assert_eq!(rows[1].split(',').count(), 8);
+
+        assert!(csv.contains(&format!("# chla: {}", result.chla)));
+    }
+
+    #[test]
+    fn test_serialize_json() {
+        let rrs = BTreeMap::from([
+            (410, 0.001974),
+            (443, 0.002570),
+            (490, 0.002974),
+            (555, 0.001670),
+            (670, 0.000324),
+        ]);
+
+        let result = qaa_v6(&rrs, Satellites::SeaWiFS);
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&result).unwrap()).unwrap();
+
+        assert_eq!(json["version"], "QAA v6");
+        assert_eq!(json["reference_wl"], 555);
+        assert_eq!(json["flags"], result.flags);
+        assert_eq!(json["chla"].as_f64().unwrap(), result.chla);
+        assert_eq!(
+            json["flag_messages"].as_array().unwrap().len(),
+            result.get_messages().len()
+        );
+    }
+
+    #[test]
+    fn test_subset_optical_data_interp() {
+        // 500nm lies halfway between the 490 and 510nm aw table entries
+        let nearest = subset_optical_data(&[500], &constants::AW_ALL)[&500];
+        let linear = subset_optical_data_interp(&[500], &constants::AW_ALL)[&500];
+
+        assert!(nearest == constants::AW_ALL[&490] || nearest == constants::AW_ALL[&510]);
+        assert!((linear - (constants::AW_ALL[&490] + constants::AW_ALL[&510]) / 2.0).abs() < 1e-12);
+
+        // Tabulated wavelengths are unchanged
+        assert_eq!(
+            subset_optical_data_interp(&[443], &constants::AW_ALL)[&443],
+            constants::AW_ALL[&443]
+        );
+
+        // Outside the table, the closest end is used
+        assert_eq!(
+            subset_optical_data_interp(&[700], &constants::AW_ALL)[&700],
+            constants::AW_ALL[&678]
+        );
+    }
+
+    #[test]
+    fn test_qaa_v6_batch_matches_single_pixel() {
+        let pixels = vec![
+            BTreeMap::from([
+                (412, 0.0020),
+                (443, 0.0023),
+                (488, 0.0030),
+                (547, 0.0015),
+                (667, 0.0002),
+            ]),
+            BTreeMap::from([
+                (412, 0.0012),
+                (443, 0.0015),
+                (488, 0.0024),
+                (547, 0.0021),
+                (667, 0.0004),
+            ]),
+        ];
+
+        let batch = qaa_v6_batch(&pixels, Satellites::Modis);
+
+        assert_eq!(batch.len(), 2);
+        for (result, rrs) in batch.iter().zip(&pixels) {
+            assert_eq!(
+                result.chla.to_bits(),
+                qaa_v6(rrs, Satellites::Modis).chla.to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn test_aw_corrected() {
+        // Reference conditions give back the static table
+        assert_eq!(
+            aw_corrected(443, constants::AW_REFERENCE_TEMPERATURE_C, 0.0),
+            constants::AW_ALL[&443]
+        );
+
+        // Salt water absorbs more in the blue
+        assert!(
+            aw_corrected(412, constants::AW_REFERENCE_TEMPERATURE_C, 35.0)
+                > constants::AW_ALL[&412]
+        );
+
+        // Untabulated wavelengths use the closest tabulated one
+        assert_eq!(aw_corrected(700, 0.0, 35.0), aw_corrected(678, 0.0, 35.0));
+    }
+
+    #[test]
+    fn test_qaa_v6_with_water() {
+        let rrs = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+
+        let default = qaa_v6(&rrs, Satellites::Modis);
+
+        let static_table = qaa_v6_with_water(&rrs, Satellites::Modis, None, None);
+        assert_eq!(static_table.chla.to_bits(), default.chla.to_bits());
+
+        let arctic = qaa_v6_with_water(&rrs, Satellites::Modis, Some(-1.0), Some(32.0));
+        assert!(arctic.chla.is_finite());
+        assert_ne!(arctic.a_at(412), default.a_at(412));
+    }
+
+    #[test]
+    fn test_turbid_branch() {
+        let clear = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+        let turbid = BTreeMap::from([
+            (412, 0.0040),
+            (443, 0.0050),
+            (488, 0.0070),
+            (547, 0.0090),
+            (667, 0.0030),
+        ]);
+
+        let result = qaa_v6(&clear, Satellites::Modis);
+        assert!(!result.turbid());
+        assert_eq!(result.reference_wl, 547);
+
+        let result = qaa_v6(&turbid, Satellites::Modis);
+        assert!(result.turbid());
+        assert_eq!(result.reference_wl, 667);
+        assert!(result.a_at(667) > constants::AW_ALL[&667]);
+        assert!(result.chla.is_finite());
+    }
+
+    #[test]
+    fn test_qaa_v6_with_params() {
+        let rrs = BTreeMap::from([
+            (412, 0.0020),
+            (443, 0.0023),
+            (488, 0.0030),
+            (547, 0.0015),
+            (667, 0.0002),
+        ]);
+
+        let default = qaa_v6(&rrs, Satellites::Modis);
+        let same = qaa_v6_with_params(
+            &rrs,
+            Satellites::Modis,
+            &QaaParams::for_satellite(Satellites::Modis),
+        );
+        assert_eq!(same.chla.to_bits(), default.chla.to_bits());
+
+        // A narrow aph/a range forces the ratio into it
+        let params = QaaParams {
+            aph_ratio_min: 0.5,
+            aph_ratio_max: 0.55,
+            ..QaaParams::default()
+        };
+        let result = qaa_v6_with_params(&rrs, Satellites::Modis, &params);
+        assert!((0.5..=0.55).contains(&result.aph_ratio_443));
+
+        // Inverted or NaN bounds are rejected instead of panicking
+        for (aph_ratio_min, aph_ratio_max) in [(0.6, 0.15), (f64::NAN, 0.6)] {
+            let params = QaaParams {
+                aph_ratio_min,
+                aph_ratio_max,
+                ..QaaParams::default()
+            };
+            assert!(!params.is_valid());
+
+            let result = qaa_v6_with_params(&rrs, Satellites::Modis, &params);
+            assert!(result.chla.is_nan());
+            assert_eq!(result.flags, 0x01);
+        }
+    }
+
+    #[test]
+    fn test_acoefs_for_satellite() {
+        for satellite in [Satellites::SeaWiFS, Satellites::Modis, Satellites::Meris] {
+            assert_eq!(
+                QaaParams::for_satellite(satellite).acoefs,
+                constants::QAA_ACOEFS_555
+            );
+        }
+
+        let rrs = BTreeMap::from([
+            (413, 0.0020),
+            (443, 0.0023),
+            (490, 0.0030),
+            (510, 0.0022),
+            (560, 0.0015),
+            (665, 0.0002),
+        ]);
+        let result = qaa_v6(&rrs, Satellites::Meris);
+        assert_eq!(result.reference_wl, 560);
+        assert!(result.chla.is_finite());
     }
 }
@@ -0,0 +1,131 @@
+//! Remote sensing reflectance unit conversions
+//!
+//! `qaa_v6` and the empirical algorithms expect above-water remote sensing reflectance (Rrs) in
+//! sr^-1. Ocean color products are also distributed as normalized water-leaving radiance (nLw) or
+//! as dimensionless reflectance (ρ = π Rrs), sometimes in percent. These helpers bring such values
+//! back to Rrs and catch magnitudes that cannot be Rrs.
+//!
+//! ## References
+//!
+//! - Mobley, C. D., et al. (2016). Atmospheric Correction for Satellite Ocean Color Radiometry.
+//!   *NASA/TM-2016-217551*.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+/// Lowest plausible Rrs (sr^-1). Slightly negative values are common after atmospheric correction
+pub const RRS_MIN_PLAUSIBLE: f64 = -0.005;
+
+/// Highest plausible Rrs (sr^-1). Even very turbid waters stay well below this value
+pub const RRS_MAX_PLAUSIBLE: f64 = 0.1;
+
+/// Unit of reflectance values given to `normalize_rrs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RrsUnit {
+    /// Remote sensing reflectance in sr^-1
+    #[default]
+    Rrs,
+    /// Dimensionless reflectance ρ = π Rrs
+    Reflectance,
+    /// Dimensionless reflectance in percent
+    PercentReflectance,
+}
+
+/// Converts a normalized water-leaving radiance to Rrs (sr^-1).
+///
+/// `nlw` and the mean extraterrestrial solar irradiance `f0` must use the same radiometric units,
+/// e.g. mW cm^-2 µm^-1 sr^-1 and mW cm^-2 µm^-1.
+pub fn nlw_to_rrs(nlw: f64, f0: f64) -> f64 {
+    nlw / f0
+}
+
+/// Converts a dimensionless reflectance (ρ = π Rrs) to Rrs (sr^-1)
+pub fn rrs_from_reflectance(reflectance: f64) -> f64 {
+    reflectance / PI
+}
+
+/// Converts a reflectance in percent to Rrs (sr^-1)
+pub fn rrs_from_percent_reflectance(reflectance_percent: f64) -> f64 {
+    rrs_from_reflectance(reflectance_percent / 100.0)
+}
+
+/// Rrs spectrum returned by `normalize_rrs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedRrs {
+    /// Rrs in sr^-1. Implausible values are replaced by NaN
+    pub rrs: BTreeMap<u32, f64>,
+    /// Wavelengths whose value was outside `RRS_MIN_PLAUSIBLE..=RRS_MAX_PLAUSIBLE`
+    pub implausible: Vec<u32>,
+}
+
+impl NormalizedRrs {
+    pub fn is_plausible(&self) -> bool {
+        self.implausible.is_empty()
+    }
+}
+
+/// Converts a reflectance spectrum given in `unit` to Rrs (sr^-1) and flags the values outside
+/// the plausible Rrs range, which usually means the input unit is wrong (e.g. percent reflectance
+/// passed as Rrs). Flagged and non-finite values are set to NaN so they cannot produce a
+/// realistic-looking but wrong chlorophyll.
+pub fn normalize_rrs(values: &BTreeMap<u32, f64>, unit: RrsUnit) -> NormalizedRrs {
+    let convert: fn(f64) -> f64 = match unit {
+        RrsUnit::Rrs => |v: f64| v,
+        RrsUnit::Reflectance => rrs_from_reflectance,
+        RrsUnit::PercentReflectance => rrs_from_percent_reflectance,
+    };
+
+    let mut implausible = Vec::new();
+
+    let rrs = values
+        .iter()
+        .map(|(&wl, &value)| {
+            let rrs = convert(value);
+
+            if rrs.is_finite() && (RRS_MIN_PLAUSIBLE..=RRS_MAX_PLAUSIBLE).contains(&rrs) {
+                (wl, rrs)
+            } else {
+                implausible.push(wl);
+                (wl, f64::NAN)
+            }
+        })
+        .collect();
+
+    NormalizedRrs { rrs, implausible }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        // SeaWiFS F0(443) ~ 189.9 mW cm^-2 µm^-1
+        assert!((nlw_to_rrs(1.899, 189.9) - 0.01).abs() < 1e-12);
+
+        assert!((rrs_from_reflectance(PI * 0.004) - 0.004).abs() < 1e-12);
+        assert!((rrs_from_percent_reflectance(100.0 * PI * 0.004) - 0.004).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_normalize_rrs() {
+        let percent = BTreeMap::from([(443, 1.2566), (555, 0.6283)]);
+
+        let normalized = normalize_rrs(&percent, RrsUnit::PercentReflectance);
+        assert!(normalized.is_plausible());
+        assert!((normalized.rrs[&443] - 0.004).abs() < 1e-6);
+        assert!((normalized.rrs[&555] - 0.002).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_normalize_rrs_flags_wrong_units() {
+        // Percent reflectance passed as Rrs
+        let percent = BTreeMap::from([(443, 1.2566), (555, 0.0020)]);
+
+        let normalized = normalize_rrs(&percent, RrsUnit::Rrs);
+        assert!(!normalized.is_plausible());
+        assert_eq!(normalized.implausible, vec![443]);
+        assert!(normalized.rrs[&443].is_nan());
+        assert_eq!(normalized.rrs[&555], 0.0020);
+    }
+}
@@ -0,0 +1,166 @@
+//! Euphotic zone depth estimators
+//!
+//! The euphotic zone depth (Zeu) is the depth where the photosynthetically available radiation
+//! drops to 1% of its surface value.
+//!
+//! ## IOP-based model (Lee et al. 2007)
+//!
+//! The vertical attenuation of PAR is modelled as `K_PAR(z) = K1 + K2 / sqrt(1 + z)` with
+//!
+//! - `K1 = (X0 + X1 * a(490)^0.5 + X2 * bb(490)) * (1 + A0 * sin(theta))`
+//! - `K2 = (Z0 + Z1 * a(490) + Z2 * bb(490)) * (A1 + A2 * cos(theta))`
+//!
+//! where `theta` is the solar zenith angle in air. Zeu is the depth where `K_PAR(z) * z = 4.605`
+//! (i.e. -ln(0.01)), found numerically.
+//!
+//! ## Chlorophyll-based model (Morel & Maritorena 2001)
+//!
+//! The surface chlorophyll concentration is converted to the chlorophyll content of the
+//! euphotic layer `Ctot` (mg m^-2, Morel & Berthon 1989), from which Zeu is derived with the
+//! Morel & Maritorena (2001) power laws.
+//!
+//! ## References
+//!
+//! - Morel, A., & Berthon, J.-F. (1989). Surface pigments, algal biomass profiles, and potential
+//!   production of the euphotic layer. *Limnology and Oceanography*, 34(8), 1545-1562.
+//! - Morel, A., & Maritorena, S. (2001). Bio-optical properties of oceanic waters: A reappraisal.
+//!   *Journal of Geophysical Research*, 106(C4), 7163-7180.
+//! - Lee, Z., Du, K., Arnone, R., Liew, S., & Penta, B. (2005). Penetration of solar radiation in
+//!   the upper ocean: A numerical model for oceanic and coastal waters. *Journal of Geophysical
+//!   Research*, 110, C09019.
+//! - Lee, Z., Weidemann, A., Kindle, J., Arnone, R., Carder, K. L., & Davis, C. (2007). Euphotic
+//!   zone depth: Its derivation and implication to ocean-color remote sensing. *Journal of
+//!   Geophysical Research*, 112, C03009.
+
+/// Coefficients of the K1 term (Lee et al. 2005)
+const X0: f64 = -0.057;
+const X1: f64 = 0.482;
+const X2: f64 = 4.221;
+
+/// Coefficients of the K2 term (Lee et al. 2005)
+const Z0: f64 = 0.183;
+const Z1: f64 = 0.702;
+const Z2: f64 = -2.567;
+
+/// Solar zenith angle dependence of K1 and K2 (Lee et al. 2005)
+const A0: f64 = 0.090;
+const A1: f64 = 1.465;
+const A2: f64 = -0.667;
+
+/// -ln(0.01), optical depth of the 1% light level
+const ONE_PERCENT_OPTICAL_DEPTH: f64 = 4.605;
+
+/// Solar zenith angle (degrees) used when none is given
+pub const DEFAULT_SOLAR_ZENITH_DEG: f64 = 30.0;
+
+// Deepest euphotic depth searched (m)
+const MAX_ZEU: f64 = 1000.0;
+
+/// Estimates the euphotic zone depth (m) from total absorption and backscattering at 490nm
+/// (m^-1) with the Lee et al. (2007) model, for a solar zenith angle in degrees.
+///
+/// Returns `NaN` if the inputs are not finite, negative, or give no 1% light depth above 1000 m.
+pub fn euphotic_depth_lee(a_490: f64, bb_490: f64, solar_zenith_deg: f64) -> f64 {
+    if !(a_490.is_finite() && bb_490.is_finite() && solar_zenith_deg.is_finite())
+        || a_490 < 0.0
+        || bb_490 < 0.0
+    {
+        return f64::NAN;
+    }
+
+    let theta = solar_zenith_deg.to_radians();
+    let k1 = (X0 + X1 * a_490.sqrt() + X2 * bb_490) * (1.0 + A0 * theta.sin());
+    let k2 = (Z0 + Z1 * a_490 + Z2 * bb_490) * (A1 + A2 * theta.cos());
+
+    let optical_depth = |z: f64| (k1 + k2 / (1.0 + z).sqrt()) * z - ONE_PERCENT_OPTICAL_DEPTH;
+
+    if optical_depth(MAX_ZEU) < 0.0 {
+        return f64::NAN;
+    }
+
+    // Bisection, K_PAR(z) * z increases with depth for realistic K1 and K2
+    let (mut low, mut high) = (0.0, MAX_ZEU);
+    for _ in 0..60 {
+        let mid = 0.5 * (low + high);
+        if optical_depth(mid) < 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    0.5 * (low + high)
+}
+
+/// Estimates the euphotic zone depth (m) from the surface chlorophyll-a concentration (mg m^-3)
+/// with the Morel & Maritorena (2001) relationship.
+///
+/// Returns `NaN` if the concentration is negative, zero or not finite.
+pub fn euphotic_depth_morel(chl: f64) -> f64 {
+    if !chl.is_finite() || chl <= 0.0 {
+        return f64::NAN;
+    }
+
+    // Chlorophyll content of the euphotic layer (mg m^-2)
+    let ctot = if chl < 1.0 {
+        38.0 * chl.powf(0.425)
+    } else {
+        40.2 * chl.powf(0.507)
+    };
+
+    let zeu = 912.5 * ctot.powf(-0.839);
+    if zeu > 102.0 {
+        426.3 * ctot.powf(-0.547)
+    } else {
+        zeu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_euphotic_depth_lee_reaches_one_percent() {
+        let (a_490, bb_490) = (0.03, 0.003);
+        let zeu = euphotic_depth_lee(a_490, bb_490, DEFAULT_SOLAR_ZENITH_DEG);
+
+        // Clear oceanic water, Zeu of about 75 m
+        assert!((zeu - 75.7).abs() < 0.5, "zeu = {}", zeu);
+
+        let theta = DEFAULT_SOLAR_ZENITH_DEG.to_radians();
+        let k1 = (X0 + X1 * a_490.sqrt() + X2 * bb_490) * (1.0 + A0 * theta.sin());
+        let k2 = (Z0 + Z1 * a_490 + Z2 * bb_490) * (A1 + A2 * theta.cos());
+        let k_par = k1 + k2 / (1.0 + zeu).sqrt();
+        assert!((k_par * zeu - ONE_PERCENT_OPTICAL_DEPTH).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_euphotic_depth_lee_turbid_is_shallower() {
+        let clear = euphotic_depth_lee(0.03, 0.003, DEFAULT_SOLAR_ZENITH_DEG);
+        let turbid = euphotic_depth_lee(0.5, 0.05, DEFAULT_SOLAR_ZENITH_DEG);
+
+        assert!(turbid < clear);
+        assert!(turbid > 0.0);
+    }
+
+    #[test]
+    fn test_euphotic_depth_morel() {
+        // Ctot = 40.2 mg m-2 at 1 mg m-3
+        let zeu = euphotic_depth_morel(1.0);
+        assert!((zeu - 912.5 * 40.2_f64.powf(-0.839)).abs() < 1e-9);
+
+        // Deeper in oligotrophic waters, using the Zeu > 102 m branch
+        let oligotrophic = euphotic_depth_morel(0.02);
+        assert!(oligotrophic > 102.0);
+        assert!(oligotrophic > euphotic_depth_morel(0.3));
+
+        assert!(euphotic_depth_morel(0.0).is_nan());
+    }
+
+    #[test]
+    fn test_euphotic_depth_lee_invalid() {
+        assert!(euphotic_depth_lee(f64::NAN, 0.003, 30.0).is_nan());
+        assert!(euphotic_depth_lee(-0.1, 0.003, 30.0).is_nan());
+    }
+}
@@ -3,5 +3,10 @@ pub mod config;
 pub mod date_gen;
 pub mod iop;
 pub mod lut;
+pub mod models;
 pub mod oceanographic_model;
 pub mod sat_bands;
+pub mod utils;
+
+#[cfg(test)]
+pub(crate) mod test_support;
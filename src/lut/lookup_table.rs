@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
 // LUT data provided by Simon Belanger (UQAR) on 2011.
 // Dimensions: Wavelength(83) * TauCld(8) * Ozone(8) * Thetas(19)
@@ -9,6 +11,53 @@ use std::io::{BufRead, BufReader};
 // [wavelength][theta][ozone][taucl][albedo]
 type LutArray = Box<[[[[[f32; 7]; 8]; 10]; 19]; 83]>;
 
+/// Wavelengths (nm) of the LUT spectra: 290 to 700 in `LUT_WAVELENGTH_STEP` steps
+pub const LUT_WAVELENGTHS: std::ops::RangeInclusive<u32> = 290..=700;
+pub const LUT_WAVELENGTH_STEP: u32 = 5;
+
+/// Wavelengths (nm) of the photosynthetically available radiation
+pub const PAR_WAVELENGTHS: std::ops::RangeInclusive<u32> = 400..=700;
+
+// Planck constant (J s), speed of light (m s^-1) and Avogadro number (mol^-1)
+const PLANCK: f64 = 6.626_070_15e-34;
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+const AVOGADRO: f64 = 6.022_140_76e23;
+
+// Header of the binary cache written by `Lut::save_cache`
+const CACHE_MAGIC: &[u8; 8] = b"EDLUTv01";
+
+// Allocates a zeroed LUT directly on the heap (the array is too large for the stack)
+fn empty_lut() -> LutArray {
+    vec![[[[[0.0f32; 7]; 8]; 10]; 19]; 83]
+        .into_boxed_slice()
+        .try_into()
+        .expect("LUT has 83 wavelengths")
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_axis<R: Read>(reader: &mut R, expected_len: usize, name: &str) -> io::Result<Vec<f32>> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    let len = u32::from_le_bytes(bytes) as usize;
+
+    if len != expected_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "LUT cache {} axis has {} elements, expected {}",
+                name, len, expected_len
+            ),
+        ));
+    }
+
+    (0..len).map(|_| read_f32(reader)).collect()
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Lut {
@@ -20,6 +69,17 @@ pub struct Lut {
     ed_lut: LutArray,
 }
 
+/// Input that was clamped to the LUT interpolation bounds, with the value that was passed in
+/// (`original`) and the value actually used for the interpolation (`used`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClampWarning {
+    Theta { original: f32, used: f32 },
+    Ozone { original: f32, used: f32 },
+    Taucl { original: f32, used: f32 },
+    Albedo { original: f32, used: f32 },
+    CloudFraction { original: f32, used: f32 },
+}
+
 fn blend(a: f32, b: f32, factor: f32) -> f32 {
     (1.0 - factor) * a + factor * b
 }
@@ -41,11 +101,12 @@ impl Lut {
         let xalb: Vec<f32> = vec![0.05, 0.2, 0.35, 0.5, 0.65, 0.8, 0.95];
         let wavelengths: Vec<f32> = (0..83).map(|i| 290.0 + (i * 5) as f32).collect();
 
+        let expected_count =
+            xthetas.len() * xozone.len() * xtaucl.len() * xalb.len() * wavelengths.len();
+
         let file = File::open(filename)?;
         let reader = BufReader::new(file);
-        let mut values: Vec<f32> = Vec::with_capacity(
-            xthetas.len() * xozone.len() * xtaucl.len() * xalb.len() * wavelengths.len(),
-        );
+        let mut values: Vec<f32> = Vec::with_capacity(expected_count);
 
         // Read all values from file
         for line in reader.lines() {
@@ -57,8 +118,21 @@ impl Lut {
             }
         }
 
+        // A truncated or malformed file would otherwise leave zeros in the LUT
+        if values.len() != expected_count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "LUT file {} has {} values, expected {}",
+                    filename,
+                    values.len(),
+                    expected_count
+                ),
+            ));
+        }
+
         // Fill the lookup table following C++ order: theta, ozone, taucl, albedo, wavelength
-        let mut ed_lut = Box::new([[[[[0.0; 7]; 8]; 10]; 19]; 83]);
+        let mut ed_lut = empty_lut();
         let mut idx = 0;
 
         #[allow(clippy::needless_range_loop)]
@@ -67,10 +141,8 @@ impl Lut {
                 for taucl in 0..xtaucl.len() {
                     for albedo in 0..xalb.len() {
                         for wavelength in 0..wavelengths.len() {
-                            if idx < values.len() {
-                                ed_lut[wavelength][theta][ozone][taucl][albedo] = values[idx];
-                                idx += 1;
-                            }
+                            ed_lut[wavelength][theta][ozone][taucl][albedo] = values[idx];
+                            idx += 1;
                         }
                     }
                 }
@@ -87,6 +159,86 @@ impl Lut {
         })
     }
 
+    /// Writes the LUT to a binary cache file so that later runs can skip parsing the text file.
+    ///
+    /// The cache contains a small header, the five axis vectors (length + values) and the Ed0-
+    /// values, all stored as little-endian 32-bit numbers.
+    pub fn save_cache<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(CACHE_MAGIC)?;
+
+        for axis in [
+            &self.xthetas,
+            &self.xozone,
+            &self.xtaucl,
+            &self.xalb,
+            &self.wavelengths,
+        ] {
+            writer.write_all(&(axis.len() as u32).to_le_bytes())?;
+            for value in axis {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        for value in self.ed_lut.iter().flatten().flatten().flatten().flatten() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// Loads a LUT previously written with [`Lut::save_cache`].
+    ///
+    /// Returns an `InvalidData` error if the file is not a LUT cache or if its element count does
+    /// not match the fixed LUT dimensions.
+    pub fn load_cache<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *CACHE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "File is not a LUT cache",
+            ));
+        }
+
+        let xthetas = read_axis(&mut reader, 19, "theta")?;
+        let xozone = read_axis(&mut reader, 10, "ozone")?;
+        let xtaucl = read_axis(&mut reader, 8, "taucl")?;
+        let xalb = read_axis(&mut reader, 7, "albedo")?;
+        let wavelengths = read_axis(&mut reader, 83, "wavelength")?;
+
+        let mut ed_lut = empty_lut();
+        for value in ed_lut.iter_mut().flatten().flatten().flatten().flatten() {
+            *value = read_f32(&mut reader).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "LUT cache has fewer values than expected",
+                )
+            })?;
+        }
+
+        let mut trailing = Vec::new();
+        reader.read_to_end(&mut trailing)?;
+        if !trailing.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "LUT cache has more values than expected",
+            ));
+        }
+
+        Ok(Lut {
+            xthetas,
+            xozone,
+            xtaucl,
+            xalb,
+            wavelengths,
+            ed_lut,
+        })
+    }
+
     pub fn get_wavelength_values(
         &self,
         theta_idx: usize,
@@ -143,21 +295,32 @@ impl Lut {
         self.ed_lut[wavelength][theta][ozone][taucl][albedo]
     }
 
-    fn get_indice(&self, vec: &[f32], mut target: f32) -> (usize, f32) {
-        // Apply Fortran-style boundary clamping first
+    /// Applies the Fortran-style boundary clamping used before the index search.
+    fn clamp_target(&self, vec: &[f32], target: f32) -> f32 {
         if vec == self.xthetas && target >= 90.0 {
-            target = 89.99;
+            89.99
         } else if vec == self.xozone && target >= 550.0 {
-            target = 549.99;
+            549.99
         } else if vec == self.xtaucl && target >= 64.0 {
-            target = 63.99;
-        } else if vec == self.xalb {
-            if target <= 0.05 {
-                target = 0.051;
-            } else if target >= 0.95 {
-                target = 0.9499;
-            }
+            63.99
+        } else if vec == self.xalb && target <= 0.05 {
+            0.051
+        } else if vec == self.xalb && target >= 0.95 {
+            0.9499
+        } else {
+            target
         }
+    }
+
+    /// Returns the value effectively used by the interpolation for `target`, i.e. after the upper
+    /// clamping and the lower bound of the axis (values below the first node use the first node).
+    fn effective_target(&self, vec: &[f32], target: f32) -> f32 {
+        self.clamp_target(vec, target).max(vec[0])
+    }
+
+    fn get_indice(&self, vec: &[f32], target: f32) -> (usize, f32) {
+        // Apply Fortran-style boundary clamping first
+        let target = self.clamp_target(vec, target);
 
         // Fortran-style index finding
         if target < vec[0] {
@@ -242,6 +405,11 @@ impl Lut {
     /// - `cf`: Cloud fraction (0-1)
     /// - `alb`: Surface albedo (0.05-0.95)
     ///
+    /// The spectrum is the clear sky one weighted by `1 - cf` plus the cloudy one weighted by
+    /// `cf`. A cloud fraction outside 0-1 (e.g. 1.5, which would give a negative clear sky
+    /// weight) is clamped to these bounds and a negative `tcl` is taken as 0 (clear sky). The
+    /// other inputs are clamped to the interpolation bounds, see [`Lut::ed0moins_checked`].
+    ///
     /// # Returns
     /// Vector of Ed0- values for all wavelengths (290-700nm in 5nm steps)
     ///
@@ -257,10 +425,14 @@ impl Lut {
     /// // Partly cloudy conditions
     /// let ed_cloudy = lut.ed0moins(45.0, 350.0, 16.0, 0.5, 0.2);
     ///
-    /// // Print Ed0- at 400nm (wavelength index 22)
-    /// println!("Ed0- at 400nm: {:.4}", ed_cloudy[22]);
+    /// // Print Ed0- at 400nm
+    /// let index = Lut::wavelength_index(400).unwrap();
+    /// println!("Ed0- at 400nm: {:.4}", ed_cloudy[index]);
     /// ```
     pub fn ed0moins(&self, thetas: f32, o3: f32, tcl: f32, cf: f32, alb: f32) -> Vec<f32> {
+        let cf = cf.clamp(0.0, 1.0);
+        let tcl = tcl.max(0.0);
+
         let ed_cloud = self.interpol_ed0moins(thetas, o3, tcl, alb);
         let ed_clear = self.interpol_ed0moins(thetas, o3, 0.0, alb);
 
@@ -276,4 +448,283 @@ impl Lut {
 
         ed_inst
     }
+
+    /// Same as [`Lut::ed0moins`], keyed by wavelength (nm)
+    pub fn ed0moins_map(
+        &self,
+        thetas: f32,
+        o3: f32,
+        tcl: f32,
+        cf: f32,
+        alb: f32,
+    ) -> BTreeMap<u32, f32> {
+        LUT_WAVELENGTHS
+            .step_by(LUT_WAVELENGTH_STEP as usize)
+            .zip(self.ed0moins(thetas, o3, tcl, cf, alb))
+            .collect()
+    }
+
+    /// Index of the wavelength `wl` (nm) in the spectra returned by [`Lut::ed0moins`], `None` if
+    /// it is not one of the LUT wavelengths (290-700nm in 5nm steps)
+    pub fn wavelength_index(wl: u32) -> Option<usize> {
+        let offset = wl.checked_sub(*LUT_WAVELENGTHS.start())?;
+
+        (LUT_WAVELENGTHS.contains(&wl) && offset % LUT_WAVELENGTH_STEP == 0)
+            .then_some((offset / LUT_WAVELENGTH_STEP) as usize)
+    }
+
+    /// PAR (µmol photons m⁻² s⁻¹) of a spectral irradiance in energy units (W m⁻² nm⁻¹), given on
+    /// the LUT wavelengths like the spectra of [`Lut::ed0moins`]. Each 5nm bin of
+    /// `PAR_WAVELENGTHS` is converted to a photon flux with `E = hc/λ` and summed.
+    ///
+    /// The Ed0- spectra of the LUT are already photon fluxes (see `daily_par`): this is meant for
+    /// irradiances in W m⁻² nm⁻¹, e.g. from a radiative transfer model. Wavelengths missing from a
+    /// shorter `ed` are skipped.
+    pub fn par_from_spectrum(ed: &[f32]) -> f32 {
+        // µmol photons per J at 1nm: λ (m) / (h c) / N_A * 1e6
+        let umol_per_joule_nm = 1e-9 / (PLANCK * SPEED_OF_LIGHT * AVOGADRO) * 1e6;
+
+        let sum: f64 = LUT_WAVELENGTHS
+            .step_by(LUT_WAVELENGTH_STEP as usize)
+            .zip(ed)
+            .filter(|(wl, _)| PAR_WAVELENGTHS.contains(wl))
+            .map(|(wl, &e)| e as f64 * wl as f64 * umol_per_joule_nm)
+            .sum();
+
+        (sum * LUT_WAVELENGTH_STEP as f64) as f32
+    }
+
+    /// Same as [`Lut::ed0moins`] but also reports which inputs were clamped to the LUT
+    /// interpolation bounds.
+    ///
+    /// The interpolation bounds are:
+    /// - Theta: 0-89.99 degrees
+    /// - Ozone: 100-549.99 DU
+    /// - Taucl: 0-63.99
+    /// - Albedo: 0.051-0.9499
+    /// - Cloud fraction: 0-1
+    ///
+    /// # Returns
+    /// The Ed0- spectrum (identical to `ed0moins`) and one `ClampWarning` per clamped input
+    pub fn ed0moins_checked(
+        &self,
+        thetas: f32,
+        o3: f32,
+        tcl: f32,
+        cf: f32,
+        alb: f32,
+    ) -> (Vec<f32>, Vec<ClampWarning>) {
+        let mut warnings = Vec::new();
+
+        let used = self.effective_target(&self.xthetas, thetas);
+        if used != thetas {
+            warnings.push(ClampWarning::Theta {
+                original: thetas,
+                used,
+            });
+        }
+
+        let used = self.effective_target(&self.xozone, o3);
+        if used != o3 {
+            warnings.push(ClampWarning::Ozone { original: o3, used });
+        }
+
+        let used = self.effective_target(&self.xtaucl, tcl);
+        if used != tcl {
+            warnings.push(ClampWarning::Taucl {
+                original: tcl,
+                used,
+            });
+        }
+
+        let used = self.effective_target(&self.xalb, alb);
+        if used != alb {
+            warnings.push(ClampWarning::Albedo {
+                original: alb,
+                used,
+            });
+        }
+
+        let used = cf.clamp(0.0, 1.0);
+        if used != cf {
+            warnings.push(ClampWarning::CloudFraction { original: cf, used });
+        }
+
+        (self.ed0moins(thetas, o3, tcl, cf, alb), warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LUT whose Ed0- only depends on the cloud optical thickness: 100 / (1 + its index)
+    fn synthetic_lut() -> Lut {
+        let mut ed_lut = empty_lut();
+        for thetas in ed_lut.iter_mut() {
+            for ozones in thetas.iter_mut() {
+                for taucls in ozones.iter_mut() {
+                    for (k, albedos) in taucls.iter_mut().enumerate() {
+                        albedos.fill(100.0 / (1 + k) as f32);
+                    }
+                }
+            }
+        }
+
+        Lut {
+            xthetas: (0..19).map(|i| (i * 5) as f32).collect(),
+            xozone: (0..10).map(|i| 100.0 + (i * 50) as f32).collect(),
+            xtaucl: vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0],
+            xalb: vec![0.05, 0.2, 0.35, 0.5, 0.65, 0.8, 0.95],
+            wavelengths: (0..83).map(|i| 290.0 + (i * 5) as f32).collect(),
+            ed_lut,
+        }
+    }
+
+    #[test]
+    fn test_ed0moins_cloud_fraction() {
+        let lut = synthetic_lut();
+        let ed = |tcl: f32, cf: f32| lut.ed0moins(30.0, 300.0, tcl, cf, 0.2)[0];
+
+        // Pure clear sky, whatever the clouds
+        assert!((ed(16.0, 0.0) - 100.0).abs() < 1e-3);
+        // Pure cloud, tcl = 16 is the 6th node
+        assert!((ed(16.0, 1.0) - 100.0 / 6.0).abs() < 1e-3);
+        assert!((ed(16.0, 0.5) - (100.0 + 100.0 / 6.0) / 2.0).abs() < 1e-3);
+
+        // Out of range cloud fractions are clamped
+        assert_eq!(ed(16.0, 1.5), ed(16.0, 1.0));
+        assert_eq!(ed(16.0, -0.5), ed(16.0, 0.0));
+
+        // Negative cloud optical thickness is clear sky
+        assert_eq!(ed(-4.0, 1.0), ed(0.0, 1.0));
+
+        let (spectrum, warnings) = lut.ed0moins_checked(30.0, 300.0, 16.0, 1.5, 0.2);
+        assert_eq!(spectrum, lut.ed0moins(30.0, 300.0, 16.0, 1.0, 0.2));
+        assert_eq!(
+            warnings,
+            vec![ClampWarning::CloudFraction {
+                original: 1.5,
+                used: 1.0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ed0moins_checked_reports_clamping() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        // Inputs within bounds: no warnings and same spectrum as ed0moins
+        let (ed, warnings) = lut.ed0moins_checked(30.0, 300.0, 4.0, 0.5, 0.2);
+        assert!(warnings.is_empty());
+        assert_eq!(ed, lut.ed0moins(30.0, 300.0, 4.0, 0.5, 0.2));
+
+        // Ozone above and albedo below the bounds
+        let (_, warnings) = lut.ed0moins_checked(30.0, 600.0, 4.0, 0.5, 0.01);
+        assert_eq!(
+            warnings,
+            vec![
+                ClampWarning::Ozone {
+                    original: 600.0,
+                    used: 549.99
+                },
+                ClampWarning::Albedo {
+                    original: 0.01,
+                    used: 0.051
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wavelength_index() {
+        assert_eq!(Lut::wavelength_index(290), Some(0));
+        assert_eq!(Lut::wavelength_index(400), Some(22));
+        assert_eq!(Lut::wavelength_index(700), Some(82));
+        assert_eq!(Lut::wavelength_index(285), None);
+        assert_eq!(Lut::wavelength_index(402), None);
+        assert_eq!(Lut::wavelength_index(705), None);
+        assert_eq!(Lut::wavelength_index(0), None);
+    }
+
+    #[test]
+    fn test_par_from_spectrum() {
+        // 1 W m-2 nm-1 everywhere: 5nm bins of 400, 405, ..., 700nm, 1 W m-2 at λ nm gives
+        // λ / 119.627 µmol photons m-2 s-1
+        let ed = vec![1.0; 83];
+        let expected = 5.0 * (400..=700).step_by(5).sum::<u32>() as f32 / 119.626_57;
+
+        let par = Lut::par_from_spectrum(&ed);
+        assert!((par - expected).abs() < 1e-3, "{} != {}", par, expected);
+        assert!((par - 1402.28).abs() < 0.01);
+
+        // Only 400-700nm counts
+        let mut ed_uv = vec![0.0; 83];
+        ed_uv[..Lut::wavelength_index(400).unwrap()].fill(10.0);
+        assert_eq!(Lut::par_from_spectrum(&ed_uv), 0.0);
+
+        assert_eq!(Lut::par_from_spectrum(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_ed0moins_map() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        let ed = lut.ed0moins(45.0, 350.0, 16.0, 0.5, 0.2);
+        let ed_map = lut.ed0moins_map(45.0, 350.0, 16.0, 0.5, 0.2);
+
+        assert_eq!(ed_map.len(), 83);
+        assert_eq!(ed_map.keys().next(), Some(&290));
+        assert_eq!(ed_map.keys().next_back(), Some(&700));
+        for (wl, value) in &ed_map {
+            assert_eq!(*value, ed[Lut::wavelength_index(*wl).unwrap()]);
+        }
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("ed0moins.bin");
+
+        lut.save_cache(&cache_path).unwrap();
+        let cached = Lut::load_cache(&cache_path).unwrap();
+
+        assert_eq!(
+            lut.ed0moins(30.0, 300.0, 0.0, 0.0, 0.1),
+            cached.ed0moins(30.0, 300.0, 0.0, 0.0, 0.1)
+        );
+        assert_eq!(
+            lut.ed0moins(45.0, 350.0, 16.0, 0.5, 0.2),
+            cached.ed0moins(45.0, 350.0, 16.0, 0.5, 0.2)
+        );
+    }
+
+    #[test]
+    fn test_load_cache_rejects_truncated_file() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("ed0moins.bin");
+        lut.save_cache(&cache_path).unwrap();
+
+        let bytes = std::fs::read(&cache_path).unwrap();
+        std::fs::write(&cache_path, &bytes[..bytes.len() - 4]).unwrap();
+
+        let err = Lut::load_cache(&cache_path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_from_file_rejects_truncated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("truncated_lut.dat");
+        std::fs::write(&path, "0.1 0.2 0.3\n0.4 0.5\n").unwrap();
+
+        let err = Lut::from_file(path.to_str().unwrap()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("has 5 values, expected 883120"));
+    }
 }
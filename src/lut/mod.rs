@@ -1,2 +1,140 @@
 pub mod lookup_table;
 pub mod sunpos;
+
+use chrono::{Datelike, NaiveDate};
+
+use lookup_table::Lut;
+use sunpos::SolarPosition;
+
+/// Computes the daily-integrated photosynthetically available radiation (PAR) just below the
+/// surface from the Ed0moins LUT.
+///
+/// The day is sampled every `hourly_increment` hours starting at 00:00 UTC (the same grid used by
+/// `DateTimeGenerator`). At each step the solar zenith angle is computed with `SolarPosition`,
+/// the Ed0- spectrum is interpolated from the LUT and summed over the 400-700nm wavelengths
+/// (5nm bins). The instantaneous values are then integrated over the day, the last step being
+/// shortened to end at 24:00 when `hourly_increment` does not divide 24.
+///
+/// # Parameters
+/// - `lut`: Ed0moins lookup table (Ed0- in µmol photons m⁻² s⁻¹ nm⁻¹)
+/// - `date`: Day for which PAR is computed
+/// - `lat`, `lon`: Location in decimal degrees
+/// - `ozone`: Ozone column in DU
+/// - `taucl`: Cloud optical thickness
+/// - `cloud_fraction`: Cloud fraction (0-1)
+/// - `albedo`: Surface albedo
+/// - `hourly_increment`: Time step in hours
+///
+/// # Returns
+/// Daily PAR in mol photons m⁻² d⁻¹, or 0 if `hourly_increment` is 0
+#[allow(clippy::too_many_arguments)]
+pub fn daily_par(
+    lut: &Lut,
+    date: NaiveDate,
+    lat: f32,
+    lon: f32,
+    ozone: f32,
+    taucl: f32,
+    cloud_fraction: f32,
+    albedo: f32,
+    hourly_increment: u8,
+) -> f32 {
+    if hourly_increment == 0 {
+        return 0.0;
+    }
+
+    let jday = date.ordinal() as i16;
+    let increment = hourly_increment as u32;
+
+    // One more, partial, step when the increment does not divide 24h
+    let full_steps = 24 / increment;
+    let steps = if full_steps > 0 && 24 % increment != 0 {
+        full_steps + 1
+    } else {
+        full_steps
+    };
+
+    // Indices of the 400-700nm wavelengths in the LUT (290-700nm in 5nm steps)
+    let first_par_idx = Lut::wavelength_index(400).expect("400nm is a LUT wavelength");
+
+    let mut par_umol = 0.0f32;
+
+    for step in 0..steps {
+        let hour = step * increment;
+        let thetas = SolarPosition::calculate(jday, hour as f32, lat, lon).zenith_angle_deg;
+
+        let ed = lut.ed0moins(thetas, ozone, taucl, cloud_fraction, albedo);
+        let par_inst: f32 = ed.iter().skip(first_par_idx).sum::<f32>() * 5.0;
+
+        let dt_seconds = increment.min(24 - hour) as f32 * 3600.0;
+        par_umol += par_inst * dt_seconds;
+    }
+
+    // µmol photons m-2 d-1 -> mol photons m-2 d-1
+    par_umol / 1.0e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_par_arctic_summer_vs_polar_night() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        let summer = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        let winter = NaiveDate::from_ymd_opt(2025, 12, 21).unwrap();
+
+        let par_summer = daily_par(&lut, summer, 72.0, -62.0, 300.0, 0.0, 0.0, 0.05, 1);
+        let par_winter = daily_par(&lut, winter, 80.0, -62.0, 300.0, 0.0, 0.0, 0.05, 1);
+
+        assert!(par_summer > 0.0);
+        assert_eq!(par_winter, 0.0);
+    }
+
+    #[test]
+    fn test_daily_par_zero_increment() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        let date = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        assert_eq!(
+            daily_par(&lut, date, 72.0, -62.0, 300.0, 0.0, 0.0, 0.05, 0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_daily_par_partial_last_step() {
+        let lut = Lut::from_file("./data/Ed0moins_LUT_5nm_v2.dat").unwrap();
+
+        // The sun stays at the same elevation over the day at the North Pole, so the daily PAR
+        // does not depend on the sampling as long as the whole day is integrated
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let par = |hourly_increment| {
+            daily_par(
+                &lut,
+                date,
+                90.0,
+                0.0,
+                300.0,
+                0.0,
+                0.0,
+                0.05,
+                hourly_increment,
+            )
+        };
+
+        let hourly = par(1);
+        assert!(hourly > 0.0);
+
+        for hourly_increment in [5, 7, 24] {
+            let relative_error = (par(hourly_increment) - hourly).abs() / hourly;
+            assert!(
+                relative_error < 1e-4,
+                "{}h increment is {:e} off the hourly PAR",
+                hourly_increment,
+                relative_error
+            );
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::{Datelike, NaiveDate};
+
 /// Solar position calculation module
 ///
 /// Rust implementation of the FORTRAN sunpos subroutine
@@ -108,6 +110,26 @@ impl SolarPosition {
     }
 }
 
+/// Day length (photoperiod) in hours at `latitude` (decimal degrees) on `date`
+///
+/// Uses the sunset hour angle ω0 given by cos(ω0) = -tan(φ) tan(δ), with the solar declination δ
+/// of `SolarPosition::calculate`. Returns 24 during polar day and 0 during polar night.
+pub fn day_length_hours(date: NaiveDate, latitude: f64) -> f32 {
+    let declination = 23.45_f64.to_radians()
+        * (360.0_f64.to_radians() * (284.0 + date.ordinal() as f64) / 365.0).sin();
+
+    let cos_hour_angle = -latitude.to_radians().tan() * declination.tan();
+
+    if cos_hour_angle <= -1.0 {
+        24.0
+    } else if cos_hour_angle >= 1.0 {
+        0.0
+    } else {
+        // 15 degrees of hour angle per hour, from sunrise to sunset
+        (2.0 * cos_hour_angle.acos().to_degrees() / 15.0) as f32
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -283,4 +305,35 @@ mod tests {
             "Max declination should be ~+23.45°"
         );
     }
+
+    #[test]
+    fn test_day_length_equator() {
+        for month in 1..=12 {
+            let date = NaiveDate::from_ymd_opt(2025, month, 15).unwrap();
+            assert!((day_length_hours(date, 0.0) - 12.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_day_length_polar_day_and_night() {
+        let summer = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let winter = NaiveDate::from_ymd_opt(2025, 12, 21).unwrap();
+
+        assert_eq!(day_length_hours(summer, 80.0), 24.0);
+        assert_eq!(day_length_hours(winter, 80.0), 0.0);
+
+        // Seasons are reversed in the southern hemisphere
+        assert_eq!(day_length_hours(summer, -80.0), 0.0);
+        assert_eq!(day_length_hours(winter, -80.0), 24.0);
+    }
+
+    #[test]
+    fn test_day_length_mid_latitude() {
+        let summer = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let winter = NaiveDate::from_ymd_opt(2025, 12, 21).unwrap();
+
+        // About 15.4h and 8.6h at 45N around the solstices
+        assert!((day_length_hours(summer, 45.0) - 15.4).abs() < 0.2);
+        assert!((day_length_hours(winter, 45.0) - 8.6).abs() < 0.2);
+    }
 }
@@ -1,23 +1,60 @@
-mod bbox;
-mod config;
-mod date_gen;
-mod iop;
-mod lut;
-mod oceanographic_model;
-mod sat_bands;
-mod utils;
-
-use config::Config;
-use oceanographic_model::batch_runner::BatchRunner;
+use boreas::config::Config;
+use boreas::oceanographic_model::batch_runner::{BatchPlan, BatchRunner};
+use boreas::utils::list_subdatasets;
+use clap::Parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::time::Instant;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Oceanographic primary production processing
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Path to the JSON configuration file
+    #[arg(short, long, default_value = "./data/config/simple_config.json")]
+    config: PathBuf,
+
+    /// Output directory, overrides `output_directory` from the configuration file
+    #[arg(short, long)]
+    output_dir: Option<String>,
+
+    /// Only report which input files are found or missing for each date, without processing
+    #[arg(long)]
+    dry_run: bool,
+
+    /// List the variables (subdatasets) of a NetCDF or HDF file and exit
+    #[arg(long, value_name = "PATH")]
+    list_vars: Option<String>,
+}
+
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = args.list_vars {
+        for subdataset in list_subdatasets(&path)? {
+            println!(
+                "{}\t{}\t{}",
+                subdataset.variable(),
+                subdataset.description,
+                subdataset.name
+            );
+        }
+        return Ok(());
+    }
+
+    let mut config = Config::from_file(&args.config)?;
+
+    if let Some(output_dir) = args.output_dir {
+        config.set_output_directory(output_dir)?;
+    }
+
+    if args.dry_run {
+        println!("{}", BatchPlan::new(&config));
+        return Ok(());
+    }
+
     let start = Instant::now();
     println!("Starting oceanographic primary production processing...");
 
-    let config = Config::from_file("./data/config/simple_config.json").unwrap();
-
-    let processor = BatchRunner::new(config);
+    let processor = BatchRunner::new(config)?;
     let output_files = processor.process()?;
 
     println!(
@@ -32,3 +69,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Time elapsed {:>.2?}", Instant::now() - start);
     Ok(())
 }
+
+fn main() -> ExitCode {
+    // Progress messages are shown by default, RUST_LOG overrides the level
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_target(false)
+        .format_timestamp(None)
+        .init();
+
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
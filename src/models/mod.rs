@@ -0,0 +1,6 @@
+//! Primary production models
+//!
+//! Free functions computing the daily depth-integrated primary production of a pixel from its
+//! inputs, independent of how these inputs are read (see `PixelData`).
+
+pub mod vgpm;
@@ -0,0 +1,162 @@
+//! Vertically Generalized Production Model (VGPM)
+//!
+//! Behrenfeld & Falkowski (1997) express the daily depth-integrated primary production as
+//!
+//! `PP = 0.66125 * Pb_opt * E0 / (E0 + 4.1) * Zeu * Chl * DL`
+//!
+//! where `Pb_opt` is the maximum chlorophyll-specific carbon fixation rate, a function of the sea
+//! surface temperature, `E0` the surface PAR, `Zeu` the euphotic depth and `DL` the day length.
+//!
+//! - `vgpm` and `vgpm_with_zeu` are the simplified form used by `PixelData`, without the light
+//!   terms and with an exponential `Pb_opt(SST)`.
+//! - `vgpm_full` is the complete model with the `Pb_opt` polynomial of Behrenfeld & Falkowski.
+//!
+//! ## References
+//!
+//! - Behrenfeld, M. J., & Falkowski, P. G. (1997). Photosynthetic rates derived from
+//!   satellite-based chlorophyll concentration. *Limnology and Oceanography*, 42(1), 1-20.
+
+/// Default upper bound of the simplified model output (mg C m^-2 d^-1). Larger values are
+/// discarded as unrealistic in the open ocean (typical range: 10-2000 mg C m^-2 d^-1), but
+/// eutrophic coastal and upwelling waters can exceed it, see `vgpm_with_zeu`.
+pub const DEFAULT_MAX_PP: f32 = 2000.0;
+
+/// Simplified VGPM primary production (mg C m^-2 d^-1) from the chlorophyll (mg m^-3), the SST
+/// (°C) and Kd(490) (m^-1). The euphotic depth is the 1% light level `4.6 / Kd(490)`. Results
+/// above `DEFAULT_MAX_PP` are discarded.
+pub fn vgpm(chl: f32, sst: f32, kd: f32) -> Option<f32> {
+    if kd <= 0.0 {
+        return None;
+    }
+
+    vgpm_with_zeu(chl, sst, 4.6 / kd, DEFAULT_MAX_PP)
+}
+
+/// Same as `vgpm`, with a known euphotic depth (m) and upper bound `max_pp` (mg C m^-2 d^-1).
+/// Returns `None` for a non-positive chlorophyll or a result outside `(0, max_pp]`.
+pub fn vgpm_with_zeu(chl: f32, sst: f32, zeu: f32, max_pp: f32) -> Option<f32> {
+    if chl <= 0.0 {
+        return None;
+    }
+
+    let pp = 0.66125 * pbopt_simplified(sst) * chl * zeu; // mg C m-2 d-1
+
+    if !pp.is_finite() || pp <= 0.0 || pp > max_pp {
+        return None;
+    }
+
+    Some(pp)
+}
+
+/// Exponential `Pb_opt(SST)` of the simplified model (`vgpm`, `vgpm_with_zeu`)
+pub fn pbopt_simplified(sst: f32) -> f32 {
+    let exponent = 0.0275 * sst - 0.07 * sst.powf(2.0) + 0.0025 * sst.powf(3.0);
+    1.54 * 10_f32.powf(exponent)
+}
+
+/// Maximum chlorophyll-specific carbon fixation rate (mg C mg Chl^-1 h^-1) as a function of SST
+/// (°C), 7th order polynomial of Behrenfeld & Falkowski (1997)
+pub fn pbopt(sst: f32) -> f32 {
+    if sst < -1.0 {
+        return 1.13;
+    }
+    if sst > 28.5 {
+        return 4.0;
+    }
+
+    let t = sst as f64;
+    let pbopt = 1.2956 + 2.749e-1 * t + 6.17e-2 * t.powi(2) - 2.05e-2 * t.powi(3)
+        + 2.462e-3 * t.powi(4)
+        - 1.348e-4 * t.powi(5)
+        + 3.4132e-6 * t.powi(6)
+        - 3.27e-8 * t.powi(7);
+
+    pbopt as f32
+}
+
+/// Complete VGPM primary production (mg C m^-2 d^-1) from the chlorophyll (mg m^-3), the SST
+/// (°C), the euphotic depth (m), the surface PAR (mol photons m^-2 d^-1) and the day length
+/// (hours). Returns `None` for non-positive inputs.
+pub fn vgpm_full(chl: f32, sst: f32, zeu: f32, par: f32, day_length: f32) -> Option<f32> {
+    if chl <= 0.0 || zeu <= 0.0 || par <= 0.0 || day_length <= 0.0 {
+        return None;
+    }
+
+    let pp = 0.66125 * pbopt(sst) * par / (par + 4.1) * zeu * chl * day_length;
+
+    (pp.is_finite() && pp > 0.0).then_some(pp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vgpm_guards() {
+        // (chl, sst, kd, valid)
+        let cases = [
+            (1.0, 0.0, 0.1, true),
+            (1.0, 15.0, 0.1, true),
+            (0.0, 15.0, 0.1, false),  // chl <= 0
+            (-1.0, 15.0, 0.1, false), // chl <= 0
+            (1.0, 15.0, 0.0, false),  // kd <= 0
+            (1.0, 15.0, -0.1, false), // kd <= 0
+            (1.0, -5.0, 0.1, true),   // no SST filter, e.g. ice edge noise
+            (1.0, 45.0, 0.1, false),  // pp not finite
+            (50.0, 0.0, 0.1, false),  // pp > 2000
+        ];
+
+        for (chl, sst, kd, valid) in cases {
+            assert_eq!(
+                vgpm(chl, sst, kd).is_some(),
+                valid,
+                "chl={chl}, sst={sst}, kd={kd}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_vgpm_value() {
+        // Pb_opt = 1.54 at 0°C, Zeu = 46m
+        let pp = vgpm(1.0, 0.0, 0.1).unwrap();
+        assert!((pp - 0.66125 * 1.54 * 46.0).abs() < 1e-3);
+
+        assert_eq!(
+            vgpm(1.0, 0.0, 0.1),
+            vgpm_with_zeu(1.0, 0.0, 46.0, DEFAULT_MAX_PP)
+        );
+    }
+
+    #[test]
+    fn test_vgpm_max_pp() {
+        // ~2500 mg C m-2 d-1, e.g. a productive upwelling
+        let chl = 2500.0 / (0.66125 * 1.54 * 46.0);
+
+        assert_eq!(vgpm(chl, 0.0, 0.1), None);
+
+        let pp = vgpm_with_zeu(chl, 0.0, 46.0, 5000.0).unwrap();
+        assert!((pp - 2500.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_pbopt() {
+        assert!((pbopt(0.0) - 1.2956).abs() < 1e-6);
+        assert_eq!(pbopt(-1.5), 1.13);
+        assert_eq!(pbopt(30.0), 4.0);
+        assert!(pbopt(20.0) > pbopt(5.0));
+    }
+
+    #[test]
+    fn test_vgpm_full() {
+        let pp = vgpm_full(1.0, 0.0, 46.0, 40.0, 12.0).unwrap();
+        let expected = 0.66125 * 1.2956 * 40.0 / 44.1 * 46.0 * 12.0;
+        assert!((pp - expected).abs() < 1e-2);
+
+        // Not bounded by DEFAULT_MAX_PP
+        assert!(vgpm_full(10.0, 20.0, 20.0, 50.0, 14.0).unwrap() > DEFAULT_MAX_PP);
+
+        assert_eq!(vgpm_full(1.0, 0.0, 46.0, 0.0, 12.0), None);
+        assert_eq!(vgpm_full(1.0, 0.0, 46.0, 40.0, 0.0), None);
+        assert_eq!(vgpm_full(1.0, 50.0, 46.0, 40.0, 12.0), None);
+    }
+}
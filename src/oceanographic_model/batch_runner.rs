@@ -1,77 +1,200 @@
-use chrono::NaiveDate;
-use std::collections::HashMap;
+use chrono::{Datelike, NaiveDate};
+use gdal::Dataset;
+use glob::Pattern;
+use log::{info, warn};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::path::Path;
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
-use crate::config::Config;
-use crate::date_gen::DateTimeGenerator;
-use crate::oceanographic_model::OceanographicProcessor;
+use crate::bbox::Bbox;
+use crate::config::{ChlaSource, Config, TimeStep};
+use crate::oceanographic_model::output::{
+    create_temporal_mean_dataset, release_mem_dataset, save_dataset, write_temporal_mean,
+};
+use crate::oceanographic_model::processor::{BATHYMETRY_DATASET, ValueScaling};
+use crate::oceanographic_model::{BatchError, OceanographicProcessor};
+use crate::utils::{Stats, dataset_stats};
+
+/// Input files resolved for a single date of the batch
+#[derive(Debug, Clone)]
+pub struct DatePlan {
+    pub date: NaiveDate,
+    // Raster template name -> matching file path
+    pub found: HashMap<String, String>,
+    // Raster template names without a matching file
+    pub missing: Vec<String>,
+}
+
+impl DatePlan {
+    /// Resolves the input file of every raster template of `config` for `date`
+    pub fn new(config: &Config, date: NaiveDate) -> Self {
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+
+        for template in config.raster_templates() {
+            // Find files that match this template and contain this date
+            match BatchRunner::find_matching_file(template, &date, config.frequency()) {
+                Some(matching_file) => {
+                    found.insert(template.name.clone(), matching_file);
+                }
+                None => missing.push(template.name.clone()),
+            }
+        }
+
+        DatePlan {
+            date,
+            found,
+            missing,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Preview of the files a batch run would use, per requested date. Building a plan never fails on
+/// missing files, so it can be used to check the data layout before a long run.
+#[derive(Debug, Clone)]
+pub struct BatchPlan {
+    pub dates: Vec<DatePlan>,
+}
+
+impl BatchPlan {
+    pub fn new(config: &Config) -> Self {
+        let dates = config
+            .date_iter()
+            .map(|date| DatePlan::new(config, date))
+            .collect();
+
+        BatchPlan { dates }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.dates.iter().all(DatePlan::is_complete)
+    }
+
+    pub fn missing_dates(&self) -> Vec<NaiveDate> {
+        self.dates
+            .iter()
+            .filter(|date_plan| !date_plan.is_complete())
+            .map(|date_plan| date_plan.date)
+            .collect()
+    }
+}
+
+impl Display for BatchPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for date_plan in &self.dates {
+            if date_plan.is_complete() {
+                writeln!(f, "✓ {}", date_plan.date)?;
+            } else {
+                writeln!(
+                    f,
+                    "✗ {} (missing: {})",
+                    date_plan.date,
+                    date_plan.missing.join(", ")
+                )?;
+            }
+
+            let mut found: Vec<_> = date_plan.found.iter().collect();
+            found.sort();
+            for (name, path) in found {
+                writeln!(f, "    {}: {}", name, path)?;
+            }
+        }
+
+        write!(
+            f,
+            "{} of {} date periods have all their input files",
+            self.dates.len() - self.missing_dates().len(),
+            self.dates.len()
+        )
+    }
+}
+
+/// Date of the batch and its input files, by raster template name
+type DateFiles = (NaiveDate, HashMap<String, String>);
 
 #[derive(Debug)]
 pub struct BatchRunner {
-    datasets: Vec<HashMap<String, String>>,
+    // Resolved input files of each processed date
+    datasets: Vec<DateFiles>,
     config: Config,
+    plan: BatchPlan,
 }
 
 impl BatchRunner {
-    pub fn new(config: Config) -> Self {
-        let datasets = Self::create_period_datasets(&config).unwrap();
-        BatchRunner { datasets, config }
+    /// Resolves the input files for every requested date.
+    ///
+    /// Returns `BatchError::MissingDates` if some dates are missing input files, unless
+    /// `allow_partial` is set in the configuration, in which case those dates are skipped.
+    pub fn new(config: Config) -> Result<Self, BatchError> {
+        let plan = BatchPlan::new(&config);
+        let datasets = Self::create_period_datasets(&plan, config.allow_partial())?;
+        Ok(BatchRunner {
+            datasets,
+            config,
+            plan,
+        })
+    }
+
+    /// Returns which input files were found or are missing for each requested date
+    pub fn plan(&self) -> &BatchPlan {
+        &self.plan
     }
 
     /// Creates datasets by finding actual files that match the date patterns
-    fn create_period_datasets(config: &Config) -> Result<Vec<HashMap<String, String>>, String> {
+    fn create_period_datasets(
+        plan: &BatchPlan,
+        allow_partial: bool,
+    ) -> Result<Vec<DateFiles>, BatchError> {
         let mut datasets = Vec::new();
         let mut missing_dates = Vec::new();
 
-        // Use DateTimeGenerator to generate the date series
-        let date_generator = DateTimeGenerator::new(config.clone());
-        let dates = date_generator.generate_date_series();
-        println!("Requested {} date periods: {:?}", dates.len(), dates);
-
-        let raster_templates = config.raster_templates();
-
-        for date in &dates {
-            let mut rasters = HashMap::new();
-            let mut missing_templates = Vec::new();
-
-            for template in raster_templates {
-                // Find files that match this template and contain this date
-                if let Some(matching_file) = Self::find_matching_file(template, date) {
-                    rasters.insert(template.name.clone(), matching_file);
-                } else {
-                    missing_templates.push(&template.name);
-                }
-            }
+        let dates: Vec<NaiveDate> = plan.dates.iter().map(|date_plan| date_plan.date).collect();
+        info!("Requested {} date periods: {:?}", dates.len(), dates);
 
+        for date_plan in &plan.dates {
             // Check if we found all required raster files for this date
-            if rasters.len() == raster_templates.len() {
-                println!(
+            if date_plan.is_complete() {
+                info!(
                     "✓ Found all {} raster files for date {}",
-                    rasters.len(),
-                    date
+                    date_plan.found.len(),
+                    date_plan.date
                 );
-                datasets.push(rasters);
+                datasets.push((date_plan.date, date_plan.found.clone()));
             } else {
-                println!(
+                warn!(
                     "✗ Missing raster files for date {}: {:?}",
-                    date, missing_templates
+                    date_plan.date, date_plan.missing
                 );
-                missing_dates.push(*date);
+                missing_dates.push(date_plan.date);
             }
         }
 
         // Error if we couldn't find files for some requested dates
         if !missing_dates.is_empty() {
-            panic!(
-                "ERROR: Requested {} days of data, but could only find files for {} days. Missing data for dates: {:?}",
+            if !allow_partial {
+                return Err(BatchError::MissingDates(missing_dates));
+            }
+
+            warn!(
+                "Skipping {} of {} date periods with missing files: {:?}",
+                missing_dates.len(),
                 dates.len(),
-                datasets.len(),
                 missing_dates
             );
+            return Ok(datasets);
         }
 
-        println!(
+        info!(
             "Successfully found files for all {} requested date periods",
             datasets.len()
         );
@@ -80,43 +203,85 @@ impl BatchRunner {
     }
 
     /// Find a file that matches the template pattern for the specified date
-    /// Searches recursively within the base directory
+    /// Searches recursively within the base directory. `*` in the pattern matches any characters;
+    /// the most recently modified file is used when several files match.
+    /// `{start}` and `{end}` are replaced by the first and last day of the `frequency` period
+    /// containing the date, e.g. `20250701_20250731` for monthly composites.
     fn find_matching_file(
         template: &crate::config::RasterFile,
         target_date: &NaiveDate,
+        frequency: TimeStep,
     ) -> Option<String> {
         // Format the date according to the template's date format
         let formatted_date = Self::format_date_for_template(target_date, &template.date_format);
+        let (period_start, period_end) = frequency.period(*target_date);
 
-        // Generate the expected filename by replacing {} with the formatted date
-        let expected_filename = template.filename_pattern.replace("{}", &formatted_date);
+        // Generate the expected filename by replacing the placeholders with the formatted dates
+        let expected_filename = template
+            .filename_pattern
+            .replace("{}", &formatted_date)
+            .replace(
+                "{start}",
+                &Self::format_date_for_template(&period_start, &template.date_format),
+            )
+            .replace(
+                "{end}",
+                &Self::format_date_for_template(&period_end, &template.date_format),
+            );
 
         // First try direct path (backwards compatibility)
-        let direct_path = format!("{}/{}", template.base_directory, expected_filename);
-        if Path::new(&direct_path).exists() {
-            return Some(direct_path);
+        if !expected_filename.contains('*') {
+            let direct_path = format!("{}/{}", template.base_directory, expected_filename);
+            if Path::new(&direct_path).exists() {
+                return Some(direct_path);
+            }
         }
 
         // If not found directly, search recursively in base directory
-        Self::search_file_recursively(&template.base_directory, &expected_filename)
+        Self::search_file_recursively(
+            &template.base_directory,
+            &Self::filename_glob(&expected_filename),
+            template.search_depth(),
+        )
+    }
+
+    // Glob matching `filename`, where only `*` is a wildcard
+    fn filename_glob(filename: &str) -> Pattern {
+        let escaped: Vec<String> = filename.split('*').map(Pattern::escape).collect();
+        Pattern::new(&escaped.join("*")).expect("escaped glob pattern is always valid")
     }
 
-    /// Search for a file recursively within a directory
-    fn search_file_recursively(base_dir: &str, filename: &str) -> Option<String> {
+    /// Search for a file matching `pattern` below a directory, at most `max_depth` levels down
+    /// if set. The shallowest matches win; the most recently modified of them is returned.
+    fn search_file_recursively(
+        base_dir: &str,
+        pattern: &Pattern,
+        max_depth: Option<usize>,
+    ) -> Option<String> {
         if !Path::new(base_dir).exists() {
             return None;
         }
 
-        for entry in WalkDir::new(base_dir).into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file()
-                && let Some(file_name) = entry.path().file_name()
-                && file_name.to_string_lossy() == filename
-            {
-                return Some(entry.path().to_string_lossy().to_string());
-            }
-        }
+        let walker = match max_depth {
+            Some(depth) => WalkDir::new(base_dir).max_depth(depth),
+            None => WalkDir::new(base_dir),
+        };
 
-        None
+        walker
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| {
+                entry.file_type().is_file() && pattern.matches(&entry.file_name().to_string_lossy())
+            })
+            .max_by_key(|entry| {
+                (
+                    Reverse(entry.depth()),
+                    entry.metadata().ok().and_then(|m| m.modified().ok()),
+                    entry.path().to_path_buf(),
+                )
+            })
+            .map(|entry| entry.path().to_string_lossy().to_string())
     }
 
     /// Formats a date according to the specified format pattern
@@ -130,38 +295,913 @@ impl BatchRunner {
     }
 
     pub fn process(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let output_dir = self.config.output_directory();
-
-        // Generate the date series to match with datasets
-        let date_generator = DateTimeGenerator::new(self.config.clone());
-        let dates = date_generator.generate_date_series();
-
-        let mut output_files = Vec::new();
-
-        // For each day, calculate pp and save the results in a geotiff
-        for (index, raster_dataset) in self.datasets.iter().enumerate() {
-            let proc = OceanographicProcessor::new(raster_dataset)?;
-            let bbox = self.config.bbox();
-            let dataset = proc.calculate_pp_for_bbox(bbox)?;
-
-            // Generate output filename using the corresponding date
-            let date = dates.get(index).unwrap_or(&dates[0]); // Fallback to first date if index out of bounds
-            let date_str = date.format("%Y%m%d").to_string();
-            let filename = format!(
-                "{}/boreas_daily_primary_production_{}_{}.tif",
-                output_dir,
-                self.config.model_id(),
-                date_str
-            );
+        self.process_with_progress(|_, _, _| {})
+    }
 
-            let driver = gdal::DriverManager::get_driver_by_name("GTiff")?;
-            let options = gdal::cpl::CslStringList::new();
-            let _saved_dataset = dataset.create_copy(&driver, &filename, &options)?;
+    /// Same as [`BatchRunner::process`], but calls `callback` with (completed, total, date) after
+    /// each daily dataset is written, e.g. to drive a progress bar.
+    ///
+    /// Dates are processed in parallel on up to `max_threads` threads, each opening its own input
+    /// datasets. Output files are returned in date order.
+    ///
+    /// API changes of the parallel processing: `callback` must be `Send` and is called in
+    /// completion order rather than date order. A failed date returns a `BatchError`, its own one
+    /// (e.g. `MissingFiles`) or `BatchError::DateFailed` with the message of any other error, as
+    /// GDAL errors can't be sent across threads.
+    pub fn process_with_progress<F>(
+        &self,
+        callback: F,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>>
+    where
+        F: FnMut(usize, usize, &NaiveDate) + Send,
+    {
+        // Checked before any date is processed, the run would fail anyway
+        for (date, _) in &self.datasets {
+            let filename = self.output_filename(date);
 
-            println!("✓ Saved dataset for {} to: {}", date, filename);
-            output_files.push(filename);
+            if Path::new(&filename).exists()
+                && !self.config.skip_existing()
+                && !self.config.overwrite()
+            {
+                return Err(BatchError::OutputExists(filename).into());
+            }
         }
 
-        Ok(output_files)
+        let total = self.datasets.len();
+        let progress = Mutex::new((0, callback));
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.config.max_threads().unwrap_or(0))
+            .build()?;
+
+        // GDAL errors are not `Send`, so they are passed as messages across threads
+        let output_files: Vec<Option<String>> = pool.install(|| {
+            self.datasets
+                .par_iter()
+                .map(|(date, raster_dataset)| {
+                    let output_file = self.write_date(date, raster_dataset).map_err(|e| {
+                        match e.downcast::<BatchError>() {
+                            Ok(batch_error) => *batch_error,
+                            Err(e) => BatchError::DateFailed(*date, e.to_string()),
+                        }
+                    })?;
+
+                    // A panicking callback poisons the lock, the count is still valid
+                    let mut progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+                    progress.0 += 1;
+                    let completed = progress.0;
+                    (progress.1)(completed, total, date);
+
+                    Ok(output_file)
+                })
+                .collect::<Result<Vec<_>, BatchError>>()
+        })?;
+
+        Ok(output_files.into_iter().flatten().collect())
+    }
+
+    // Computes and writes the PP of a date with its summary. Returns the output filename, `None`
+    // when the date is skipped because its output exists.
+    fn write_date(
+        &self,
+        date: &NaiveDate,
+        raster_dataset: &HashMap<String, String>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        // Generate output filename using the dataset date
+        let filename = self.output_filename(date);
+
+        if Path::new(&filename).exists() && self.config.skip_existing() {
+            info!("↷ Skipped {}, output already exists: {}", date, filename);
+            return Ok(None);
+        }
+
+        let proc = self.processor(date, raster_dataset)?;
+        let bbox = self.config.bbox();
+        let covered_bbox = proc.covered_bbox(bbox)?;
+        let clipped = proc.is_bbox_clipped(bbox)?;
+        let dataset = proc.calculate_pp_for_bbox(bbox)?;
+
+        // The in-memory dataset is released before any saving error is returned
+        let stats = save_dataset(&dataset, &filename, self.config.output_format())
+            .and_then(|_| dataset_stats(&dataset));
+        release_mem_dataset(dataset)?;
+        let stats = stats?;
+
+        self.write_summary(&filename, date, &covered_bbox, clipped, stats)?;
+
+        info!("✓ Saved dataset for {} to: {}", date, filename);
+
+        Ok(Some(filename))
+    }
+
+    /// Computes the PP of a single date over the configured bbox, without writing it. The input
+    /// files are resolved for this date only, so it does not need to be in the configured range.
+    /// Returns `BatchError::MissingFiles` if some of them are missing. The dataset is in memory,
+    /// pass it to `release_mem_dataset` once done.
+    pub fn process_date(&self, date: NaiveDate) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let date_plan = DatePlan::new(&self.config, date);
+
+        if !date_plan.is_complete() {
+            return Err(BatchError::MissingFiles(date, date_plan.missing).into());
+        }
+
+        self.processor(&date, &date_plan.found)?
+            .calculate_pp_for_bbox(self.config.bbox())
+    }
+
+    /// Computes the PP of every date and writes their pixel-wise mean to a single raster, with
+    /// the number of valid days of each pixel in a second band. Pixels with fewer valid days than
+    /// `min_valid_days` (1 by default) are set to the output no-data value. Returns the output
+    /// filename, also when the output exists and is kept with `skip_existing`.
+    pub fn process_mean(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let (Some((first_date, _)), Some((last_date, _))) =
+            (self.datasets.first(), self.datasets.last())
+        else {
+            return Err("No dataset to average".into());
+        };
+
+        let label = format!(
+            "mean_{}_{}",
+            first_date.format("%Y%m%d"),
+            last_date.format("%Y%m%d")
+        );
+        let datasets: Vec<_> = self.datasets.iter().collect();
+        let filename = format!(
+            "{}/{}",
+            self.config.output_directory(),
+            self.config.output_filename_for(&label)
+        );
+
+        self.write_mean(&datasets, &label, filename)
+    }
+
+    /// Groups the dates by calendar month or day-of-year bin and writes the pixel-wise mean PP of
+    /// each group, like [`BatchRunner::process_mean`]. Outputs are named after the group rather
+    /// than `output_filename_pattern`, e.g. `pp_clim_month_07.tif` or `pp_clim_doy_033.tif`
+    /// (first day of year of the bin).
+    pub fn process_climatology(
+        &self,
+        grouping: Grouping,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if grouping == Grouping::DoyBin(0) {
+            return Err("Day-of-year bin size must be greater than 0".into());
+        }
+
+        let mut groups: BTreeMap<u32, Vec<&DateFiles>> = BTreeMap::new();
+        for date_dataset in &self.datasets {
+            groups
+                .entry(grouping.group(&date_dataset.0))
+                .or_default()
+                .push(date_dataset);
+        }
+
+        groups
+            .iter()
+            .map(|(&group, datasets)| {
+                let label = grouping.label(group);
+                let filename = format!(
+                    "{}/pp_{}.{}",
+                    self.config.output_directory(),
+                    label,
+                    self.config.output_format().extension()
+                );
+
+                self.write_mean(datasets, &label, filename)
+            })
+            .collect()
+    }
+
+    // Writes the pixel-wise mean PP of `datasets` (in date order) to `filename`. `label` names
+    // the period in the logs.
+    fn write_mean(
+        &self,
+        datasets: &[&DateFiles],
+        label: &str,
+        filename: String,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (Some((first_date, first_files)), Some((last_date, _))) =
+            (datasets.first().copied(), datasets.last().copied())
+        else {
+            return Err("No dataset to average".into());
+        };
+
+        if Path::new(&filename).exists() && self.config.skip_existing() {
+            info!("↷ Skipped {}, output already exists: {}", label, filename);
+            return Ok(filename);
+        }
+
+        if Path::new(&filename).exists() && !self.config.overwrite() {
+            return Err(BatchError::OutputExists(filename).into());
+        }
+
+        let nodata = self.config.output_nodata().unwrap_or(f64::NAN);
+
+        // Daily in-memory rasters are released once read, so the mean raster is created from the
+        // first one right away
+        let (first_daily, values) = self.daily_pp_values(first_date, first_files)?;
+        let dataset = create_temporal_mean_dataset(&first_daily, (first_date, last_date), nodata);
+        release_mem_dataset(first_daily)?;
+        let dataset = dataset?;
+
+        // The mean raster is released before any error of the other dates or saving is returned
+        let saved = self
+            .accumulate_mean(&dataset, datasets, &values, nodata, label)
+            .and_then(|_| save_dataset(&dataset, &filename, self.config.output_format()));
+        release_mem_dataset(dataset)?;
+        saved?;
+
+        info!(
+            "✓ Saved mean of {} dates ({} to {}) to: {}",
+            datasets.len(),
+            first_date,
+            last_date,
+            filename
+        );
+
+        Ok(filename)
+    }
+
+    // Adds the PP of the dates after the first (whose values are `first_values`) and writes the
+    // pixel-wise mean and valid days to the mean raster
+    fn accumulate_mean(
+        &self,
+        dataset: &Dataset,
+        datasets: &[&DateFiles],
+        first_values: &[f32],
+        nodata: f64,
+        label: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut mean = MeanAccumulator::new(first_values.len());
+        mean.add(first_values, nodata);
+        info!("✓ Added {} to {}", datasets[0].0, label);
+
+        for (date, raster_dataset) in &datasets[1..] {
+            let (daily, values) = self.daily_pp_values(date, raster_dataset)?;
+            release_mem_dataset(daily)?;
+
+            if values.len() != mean.len() {
+                return Err(
+                    format!("PP raster of {} does not match the first date size", date).into(),
+                );
+            }
+
+            mean.add(&values, nodata);
+            info!("✓ Added {} to {}", date, label);
+        }
+
+        let (mean_values, valid_days) = mean.finish(nodata, self.config.min_valid_days());
+        write_temporal_mean(dataset, mean_values, valid_days)
+    }
+
+    // PP raster of the input files of a date, with its values in row-major order
+    fn daily_pp_values(
+        &self,
+        date: &NaiveDate,
+        raster_dataset: &HashMap<String, String>,
+    ) -> Result<(Dataset, Vec<f32>), Box<dyn std::error::Error>> {
+        let dataset = self
+            .processor(date, raster_dataset)?
+            .calculate_pp_for_bbox(self.config.bbox())?;
+        let (width, height) = dataset.raster_size();
+        let buffer = dataset
+            .rasterband(1)
+            .and_then(|band| band.read_as::<f32>((0, 0), (width, height), (width, height), None));
+
+        match buffer {
+            Ok(buffer) => Ok((dataset, buffer.into_shape_and_vec().1)),
+            Err(e) => {
+                release_mem_dataset(dataset)?;
+                Err(e.into())
+            }
+        }
+    }
+
+    // PP processor of the input files of a date
+    fn processor(
+        &self,
+        date: &NaiveDate,
+        raster_dataset: &HashMap<String, String>,
+    ) -> Result<OceanographicProcessor, Box<dyn std::error::Error>> {
+        let netcdf_variables: HashMap<String, String> = self
+            .config
+            .raster_templates()
+            .iter()
+            .map(|template| (template.name.clone(), template.variable_name().to_string()))
+            .collect();
+
+        let bands: HashMap<String, usize> = self
+            .config
+            .raster_templates()
+            .iter()
+            .map(|template| (template.name.clone(), template.band_index()))
+            .collect();
+
+        let scaling_overrides: HashMap<String, ValueScaling> = self
+            .config
+            .raster_templates()
+            .iter()
+            .map(|template| (template.name.clone(), template.value_scaling()))
+            .collect();
+
+        // The bathymetry does not depend on the date
+        let mut raster_files = raster_dataset.clone();
+        if let Some(bathymetry_file) = self.config.bathymetry_file() {
+            raster_files.insert(BATHYMETRY_DATASET.to_string(), bathymetry_file.to_string());
+        }
+
+        let processor =
+            OceanographicProcessor::new_with_variables(&raster_files, &netcdf_variables)?
+                .with_output_nodata(self.config.output_nodata().unwrap_or(f64::NAN))
+                .with_date(*date)
+                .with_bands(bands)?
+                .with_scaling_overrides(scaling_overrides)
+                .with_min_water_depth(self.config.min_water_depth())
+                .with_l2_flags_mask(self.config.l2_flags_mask())
+                .with_max_pp(self.config.max_pp())
+                .with_output_unit(self.config.output_unit());
+
+        let processor = match self.config.bbox_crs() {
+            Some(bbox_crs) => processor.with_bbox_crs(bbox_crs),
+            None => processor,
+        };
+
+        let processor = if self.config.align_grids() {
+            processor.with_aligned_grids(self.config.reference_grid())?
+        } else {
+            processor
+        };
+
+        Ok(match self.config.chla_source() {
+            ChlaSource::Band => processor,
+            ChlaSource::Qaa => processor.with_qaa_chla(self.config.satellite()),
+        })
+    }
+
+    // Path of the output raster of `date`
+    fn output_filename(&self, date: &NaiveDate) -> String {
+        format!(
+            "{}/{}",
+            self.config.output_directory(),
+            self.config.output_filename(date)
+        )
+    }
+
+    // Writes the daily statistics as a JSON file next to the output raster
+    fn write_summary(
+        &self,
+        output_filename: &str,
+        date: &NaiveDate,
+        covered_bbox: &Bbox,
+        clipped: bool,
+        stats: Stats,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let summary = DailySummary {
+            date: *date,
+            model_id: self.config.model_id(),
+            bbox: self.config.bbox(),
+            bbox_crs: self.config.bbox_crs(),
+            covered_bbox,
+            clipped,
+            stats,
+        };
+
+        let summary_path = Path::new(output_filename).with_extension("json");
+        std::fs::write(&summary_path, serde_json::to_string_pretty(&summary)?)?;
+
+        Ok(())
+    }
+}
+
+/// How `BatchRunner::process_climatology` groups dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    /// Calendar month
+    Month,
+    /// Bins of the given number of days of year, starting on January 1st
+    DoyBin(u32),
+}
+
+impl Grouping {
+    // Month (1-12) or zero-based day-of-year bin of `date`
+    fn group(&self, date: &NaiveDate) -> u32 {
+        match self {
+            Grouping::Month => date.month(),
+            Grouping::DoyBin(days) => date.ordinal0() / days,
+        }
+    }
+
+    // Name of a group in the output filenames
+    fn label(&self, group: u32) -> String {
+        match self {
+            Grouping::Month => format!("clim_month_{:02}", group),
+            Grouping::DoyBin(days) => format!("clim_doy_{:03}", group * days + 1),
+        }
+    }
+}
+
+// Running pixel-wise sum and count of valid values of same-sized rasters
+struct MeanAccumulator {
+    sums: Vec<f64>,
+    counts: Vec<u32>,
+}
+
+impl MeanAccumulator {
+    fn new(len: usize) -> Self {
+        Self {
+            sums: vec![0.0; len],
+            counts: vec![0; len],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.sums.len()
+    }
+
+    // Adds the values that are neither NaN nor equal to `nodata`
+    fn add(&mut self, values: &[f32], nodata: f64) {
+        for ((sum, count), &value) in self.sums.iter_mut().zip(&mut self.counts).zip(values) {
+            if !value.is_nan() && value as f64 != nodata {
+                *sum += value as f64;
+                *count += 1;
+            }
+        }
+    }
+
+    // Returns the means, `nodata` where fewer than `min_count` (at least 1) values were added,
+    // and the valid value counts
+    fn finish(self, nodata: f64, min_count: u32) -> (Vec<f32>, Vec<f32>) {
+        let min_count = min_count.max(1);
+
+        self.sums
+            .iter()
+            .zip(&self.counts)
+            .map(|(&sum, &count)| {
+                let mean = if count < min_count {
+                    nodata as f32
+                } else {
+                    (sum / count as f64) as f32
+                };
+                (mean, count as f32)
+            })
+            .unzip()
+    }
+}
+
+/// Content of the JSON sidecar file written next to each daily output
+#[derive(Serialize)]
+struct DailySummary<'a> {
+    date: NaiveDate,
+    model_id: &'a str,
+    bbox: &'a Bbox,
+    // CRS of `bbox` when it is not lon/lat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bbox_crs: Option<&'a str>,
+    // Part of `bbox` covered by the input rasters, in lon/lat
+    covered_bbox: &'a Bbox,
+    // Whether `bbox` extends beyond the input rasters
+    clipped: bool,
+    #[serde(flatten)]
+    stats: Stats,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RasterFile;
+    use crate::test_support;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_plan_reports_found_and_missing_files() {
+        let dir = tempdir().unwrap();
+        let data_dir = dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        File::create(data_dir.join("chl_20230101.tif")).unwrap();
+
+        let config_path = dir.path().join("config.json");
+        let config_data = format!(
+            r#"
+        {{
+            "model_id": "Test",
+            "start_date": "2023-01-01",
+            "end_date": "2023-01-02",
+            "frequency": "daily",
+            "hourly_increment": 6,
+            "raster_templates": [
+                {{
+                    "name": "chlor_a",
+                    "base_directory": "{}",
+                    "filename_pattern": "chl_{{}}.tif",
+                    "date_format": "YYYYMMDD"
+                }}
+            ],
+            "bbox": {{
+                "xmin": 0.0,
+                "xmax": 1.0,
+                "ymin": 0.0,
+                "ymax": 1.0
+            }},
+            "output_directory": "{}"
+        }}
+        "#,
+            data_dir.display(),
+            dir.path().display()
+        );
+        File::create(&config_path)
+            .unwrap()
+            .write_all(config_data.as_bytes())
+            .unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        let plan = BatchPlan::new(&config);
+
+        assert_eq!(plan.dates.len(), 2);
+        assert!(plan.dates[0].is_complete());
+        assert!(plan.dates[0].found["chlor_a"].ends_with("chl_20230101.tif"));
+        assert_eq!(plan.dates[1].missing, vec!["chlor_a".to_string()]);
+        assert!(!plan.is_complete());
+        assert_eq!(
+            plan.missing_dates(),
+            vec![NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_create_period_datasets_missing_dates() {
+        let plan = BatchPlan {
+            dates: vec![
+                DatePlan {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                    found: HashMap::from([("chlor_a".to_string(), "chl.tif".to_string())]),
+                    missing: vec![],
+                },
+                DatePlan {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+                    found: HashMap::new(),
+                    missing: vec!["chlor_a".to_string()],
+                },
+            ],
+        };
+
+        match BatchRunner::create_period_datasets(&plan, false) {
+            Err(BatchError::MissingDates(dates)) => {
+                assert_eq!(dates, vec![NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()])
+            }
+            other => panic!("Expected MissingDates error, got {:?}", other),
+        }
+
+        let datasets = BatchRunner::create_period_datasets(&plan, true).unwrap();
+        assert_eq!(datasets.len(), 1);
+        assert_eq!(datasets[0].0, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        assert_eq!(datasets[0].1["chlor_a"], "chl.tif");
+    }
+
+    #[test]
+    fn test_datasets_keep_their_dates_across_gaps() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("chl_20230101.tif")).unwrap();
+        File::create(dir.path().join("chl_20230103.tif")).unwrap();
+
+        let config = test_support::test_builder()
+            .model_id("Test_gap")
+            .raster_template(RasterFile {
+                base_directory: dir.path().display().to_string(),
+                ..test_support::raster_template("chlor_a", "chl_{}.tif")
+            })
+            .output_directory(dir.path().display().to_string())
+            .allow_partial(true)
+            .build()
+            .unwrap();
+
+        let runner = BatchRunner::new(config).unwrap();
+
+        // 2023-01-02 is skipped, so the second dataset must be labeled 2023-01-03
+        let dates: Vec<NaiveDate> = runner.datasets.iter().map(|(date, _)| *date).collect();
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()
+            ]
+        );
+        assert!(runner.datasets[1].1["chlor_a"].ends_with("chl_20230103.tif"));
+        assert!(
+            runner
+                .output_filename(&runner.datasets[1].0)
+                .ends_with("boreas_daily_primary_production_Test_gap_20230103.tif")
+        );
+    }
+
+    #[test]
+    fn test_existing_outputs() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("chl_20230101.tif")).unwrap();
+
+        let builder = test_support::test_builder()
+            .model_id("Test_existing")
+            .end_date(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+            .raster_template(RasterFile {
+                base_directory: dir.path().display().to_string(),
+                ..test_support::raster_template("chlor_a", "chl_{}.tif")
+            })
+            .output_directory(dir.path().display().to_string());
+
+        let runner =
+            BatchRunner::new(builder.clone().skip_existing(true).build().unwrap()).unwrap();
+        File::create(runner.output_filename(&runner.datasets[0].0)).unwrap();
+
+        // The input is not a valid raster, so only skipping the date can succeed
+        let mut progress = Vec::new();
+        let output_files = runner
+            .process_with_progress(|done, total, _| progress.push((done, total)))
+            .unwrap();
+        assert!(output_files.is_empty());
+        assert_eq!(progress, vec![(1, 1)]);
+
+        // Overwritten, failing on the invalid input
+        let runner = BatchRunner::new(builder.clone().build().unwrap()).unwrap();
+        let error = runner.process().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BatchError>(),
+            Some(BatchError::DateFailed(date, _)) if *date == runner.datasets[0].0
+        ));
+
+        let runner = BatchRunner::new(builder.overwrite(false).build().unwrap()).unwrap();
+        let error = runner.process().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BatchError>(),
+            Some(BatchError::OutputExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_process_date() {
+        let dir = tempdir().unwrap();
+        test_support::write_test_inputs(dir.path(), "20230101");
+
+        let config = test_support::test_inputs_config(dir.path())
+            .start_date(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+            .end_date(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap())
+            .allow_partial(true)
+            .build()
+            .unwrap();
+        let runner = BatchRunner::new(config).unwrap();
+
+        let dataset = runner
+            .process_date(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+            .unwrap();
+        assert_eq!(dataset.raster_size(), (2, 2));
+
+        let values = dataset
+            .rasterband(1)
+            .unwrap()
+            .read_as::<f32>((0, 0), (2, 2), (2, 2), None)
+            .unwrap();
+        let expected = crate::models::vgpm::vgpm(1.0, 5.0, 0.1).unwrap();
+        assert!(values.data().iter().all(|pp| (pp - expected).abs() < 1e-3));
+
+        // Nothing is written
+        assert!(
+            !Path::new(&runner.output_filename(&NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()))
+                .exists()
+        );
+
+        let error = runner
+            .process_date(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BatchError>(),
+            Some(BatchError::MissingFiles(_, missing)) if missing.len() == 3
+        ));
+    }
+
+    #[test]
+    fn test_process_in_parallel() {
+        let dir = tempdir().unwrap();
+        for date in ["20230101", "20230102", "20230103"] {
+            test_support::write_test_inputs(dir.path(), date);
+        }
+
+        let config = test_support::test_inputs_config(dir.path())
+            .start_date(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+            .end_date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap())
+            .max_threads(2)
+            .build()
+            .unwrap();
+        let runner = BatchRunner::new(config).unwrap();
+
+        let mut progress = Vec::new();
+        let output_files = runner
+            .process_with_progress(|done, total, _| progress.push((done, total)))
+            .unwrap();
+
+        // Outputs are in date order whatever the completion order
+        let expected: Vec<String> = runner
+            .datasets
+            .iter()
+            .map(|(date, _)| runner.output_filename(date))
+            .collect();
+        assert_eq!(output_files, expected);
+        assert!(output_files.iter().all(|file| Path::new(file).exists()));
+        assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_process_climatology() {
+        let dir = tempdir().unwrap();
+        for date in ["20230701", "20230702", "20230801"] {
+            test_support::write_test_inputs(dir.path(), date);
+        }
+
+        let config = test_support::test_inputs_config(dir.path())
+            .start_date(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap())
+            .end_date(NaiveDate::from_ymd_opt(2023, 8, 1).unwrap())
+            .allow_partial(true)
+            .build()
+            .unwrap();
+        let runner = BatchRunner::new(config).unwrap();
+
+        let output_files = runner.process_climatology(Grouping::Month).unwrap();
+        let expected: Vec<String> = ["pp_clim_month_07.tif", "pp_clim_month_08.tif"]
+            .iter()
+            .map(|name| dir.path().join(name).display().to_string())
+            .collect();
+        assert_eq!(output_files, expected);
+
+        // Mean PP and valid days of every pixel
+        let pp = crate::models::vgpm::vgpm(1.0, 5.0, 0.1).unwrap();
+        for (file, days) in output_files.iter().zip([2.0, 1.0]) {
+            let dataset = Dataset::open(file).unwrap();
+            let band = |index| {
+                dataset
+                    .rasterband(index)
+                    .unwrap()
+                    .read_as::<f32>((0, 0), (2, 2), (2, 2), None)
+                    .unwrap()
+                    .into_shape_and_vec()
+                    .1
+            };
+
+            assert!(band(1).iter().all(|mean| (mean - pp).abs() < 1e-3));
+            assert!(band(2).iter().all(|&count| count == days));
+        }
+
+        assert!(runner.process_climatology(Grouping::DoyBin(0)).is_err());
+
+        // Existing outputs are kept with skip_existing, so the now invalid inputs are not read
+        for date in ["20230701", "20230702", "20230801"] {
+            File::create(dir.path().join(format!("chl_{}.tif", date))).unwrap();
+        }
+        let config = test_support::test_inputs_config(dir.path())
+            .start_date(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap())
+            .end_date(NaiveDate::from_ymd_opt(2023, 8, 1).unwrap())
+            .allow_partial(true)
+            .skip_existing(true)
+            .build()
+            .unwrap();
+        let runner = BatchRunner::new(config).unwrap();
+        assert_eq!(
+            runner.process_climatology(Grouping::Month).unwrap(),
+            output_files
+        );
+    }
+
+    #[test]
+    fn test_mean_accumulator_with_different_masks() {
+        let accumulate = || {
+            let mut mean = MeanAccumulator::new(4);
+            mean.add(&[1.0, f32::NAN, 3.0, -999.0], -999.0);
+            mean.add(&[3.0, 2.0, f32::NAN, -999.0], -999.0);
+            mean.add(&[5.0, f32::NAN, f32::NAN, -999.0], -999.0);
+            mean
+        };
+
+        let (values, counts) = accumulate().finish(-999.0, 1);
+
+        assert_eq!(values, vec![3.0, 2.0, 3.0, -999.0]);
+        assert_eq!(counts, vec![3.0, 1.0, 1.0, 0.0]);
+
+        // Single valid days are not enough, their counts are still reported
+        let (values, counts) = accumulate().finish(-999.0, 2);
+
+        assert_eq!(values, vec![3.0, -999.0, -999.0, -999.0]);
+        assert_eq!(counts, vec![3.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_grouping() {
+        let date = NaiveDate::from_ymd_opt(2023, 7, 15).unwrap();
+
+        assert_eq!(Grouping::Month.group(&date), 7);
+        assert_eq!(Grouping::Month.label(7), "clim_month_07");
+
+        // 2023-07-15 is day 196 of the year, in the 8-day bin of days 193-200
+        assert_eq!(Grouping::DoyBin(8).group(&date), 24);
+        assert_eq!(Grouping::DoyBin(8).label(24), "clim_doy_193");
+        assert_eq!(
+            Grouping::DoyBin(8).group(&NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_find_matching_file_with_wildcard() {
+        let dir = tempdir().unwrap();
+        let older = dir
+            .path()
+            .join("AQUA_MODIS.20230101.L3m.DAY.CHL.chlor_a.4km.R2022.0.nc");
+        let newer = dir
+            .path()
+            .join("AQUA_MODIS.20230101.L3m.DAY.CHL.chlor_a.4km.R2023.1.nc");
+        File::create(
+            dir.path()
+                .join("AQUA_MODIS.20230102.L3m.DAY.CHL.chlor_a.4km.nc"),
+        )
+        .unwrap();
+
+        File::create(&older)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        File::create(&newer).unwrap();
+
+        let template = RasterFile {
+            base_directory: dir.path().display().to_string(),
+            ..test_support::raster_template("chlor_a", "AQUA_MODIS.{}.L3m.DAY.CHL.chlor_a.4km*.nc")
+        };
+
+        let found = BatchRunner::find_matching_file(
+            &template,
+            &NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            TimeStep::Daily,
+        );
+        assert_eq!(found, Some(newer.to_string_lossy().to_string()));
+
+        let missing = BatchRunner::find_matching_file(
+            &template,
+            &NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(),
+            TimeStep::Daily,
+        );
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_find_matching_file_with_period() {
+        let dir = tempdir().unwrap();
+        let monthly = dir
+            .path()
+            .join("AQUA_MODIS.20230201_20230228.L3m.MO.CHL.chlor_a.4km.nc");
+        File::create(&monthly).unwrap();
+
+        let template = RasterFile {
+            base_directory: dir.path().display().to_string(),
+            ..test_support::raster_template(
+                "chlor_a",
+                "AQUA_MODIS.{start}_{end}.L3m.MO.CHL.chlor_a.4km.nc",
+            )
+        };
+
+        let found = BatchRunner::find_matching_file(
+            &template,
+            &NaiveDate::from_ymd_opt(2023, 2, 15).unwrap(),
+            TimeStep::Monthly,
+        );
+        assert_eq!(found, Some(monthly.to_string_lossy().to_string()));
+    }
+
+    #[test]
+    fn test_search_file_recursively_depth() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("2023").join("01");
+        std::fs::create_dir_all(&nested).unwrap();
+        let shallow = dir.path().join("2023").join("chl_20230101.tif");
+        File::create(&shallow).unwrap();
+        File::create(nested.join("chl_20230101.tif")).unwrap();
+        File::create(nested.join("chl_20230102.tif")).unwrap();
+
+        let base_dir = dir.path().to_string_lossy();
+
+        // The shallowest match wins, even if a deeper file is newer
+        let found = BatchRunner::search_file_recursively(
+            &base_dir,
+            &BatchRunner::filename_glob("chl_20230101.tif"),
+            None,
+        );
+        assert_eq!(found, Some(shallow.to_string_lossy().to_string()));
+
+        let pattern = BatchRunner::filename_glob("chl_20230102.tif");
+        assert!(BatchRunner::search_file_recursively(&base_dir, &pattern, None).is_some());
+        assert!(BatchRunner::search_file_recursively(&base_dir, &pattern, Some(3)).is_some());
+        assert!(BatchRunner::search_file_recursively(&base_dir, &pattern, Some(2)).is_none());
+    }
+
+    #[test]
+    fn test_filename_glob_escapes_special_characters() {
+        let pattern = BatchRunner::filename_glob("chl_[v2]_20230101*.tif");
+
+        assert!(pattern.matches("chl_[v2]_20230101_R2023.tif"));
+        assert!(!pattern.matches("chl_v_20230101.tif"));
     }
 }
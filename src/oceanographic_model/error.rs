@@ -0,0 +1,42 @@
+use chrono::NaiveDate;
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum BatchError {
+    MissingDates(Vec<NaiveDate>),
+    // Date and names of the raster templates without a matching file
+    MissingFiles(NaiveDate, Vec<String>),
+    OutputExists(String),
+    // Date and message of the error that stopped its processing
+    DateFailed(NaiveDate, String),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::MissingDates(dates) => write!(
+                f,
+                "Could not find all input files for {} requested date(s): {:?}",
+                dates.len(),
+                dates
+            ),
+            BatchError::MissingFiles(date, names) => write!(
+                f,
+                "Could not find input files for {}: {}",
+                date,
+                names.join(", ")
+            ),
+            BatchError::OutputExists(path) => write!(
+                f,
+                "Output file already exists and overwrite is disabled: {}",
+                path
+            ),
+            BatchError::DateFailed(date, message) => {
+                write!(f, "Could not process {}: {}", date, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
@@ -1,9 +1,14 @@
 use std::path::Path;
 pub mod batch_runner;
+pub mod error;
+pub mod output;
 pub mod pixel;
+pub mod points;
 pub mod processor;
 
-pub use processor::OceanographicProcessor;
+pub use error::BatchError;
+pub use pixel::{EuphoticDepthModel, PixelData};
+pub use processor::{GeoReference, OceanographicProcessor, PointSampling, QaaOutput};
 
 pub fn is_supported_file_type(path: &Path) -> bool {
     matches!(
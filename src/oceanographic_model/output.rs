@@ -0,0 +1,269 @@
+use chrono::NaiveDate;
+use gdal::raster::{Buffer, RasterCreationOptions};
+use gdal::{Dataset, Metadata};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::OutputFormat;
+
+/// Tile size (in pixels) of Cloud-Optimized GeoTIFF outputs
+pub const COG_BLOCK_SIZE: usize = 256;
+
+// Unique /vsimem path of an in-memory GeoTIFF, so that processors running concurrently (e.g. one
+// per date in `BatchRunner::process`) never share a temporary file
+pub(crate) fn vsimem_path(name: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    format!(
+        "/vsimem/{}_{}.tif",
+        name,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Closes an in-memory dataset, such as the output of
+/// `OceanographicProcessor::calculate_pp_for_bbox`, and deletes its /vsimem file. GDAL keeps
+/// /vsimem files until they are unlinked, so every in-memory output should be released once saved.
+pub fn release_mem_dataset(dataset: Dataset) -> Result<(), Box<dyn std::error::Error>> {
+    let filename = dataset.description()?;
+    drop(dataset);
+
+    if filename.starts_with("/vsimem/") {
+        gdal::vsi::unlink_mem_file(&filename)?;
+    }
+
+    Ok(())
+}
+
+/// Writes an in-memory output dataset (see `SpatialRegion::create_output_dataset`) to `filename`
+/// in the requested format.
+pub fn save_dataset(
+    dataset: &Dataset,
+    filename: &str,
+    format: OutputFormat,
+) -> Result<Dataset, Box<dyn std::error::Error>> {
+    if format == OutputFormat::Cog {
+        return save_cog(dataset, filename);
+    }
+
+    let driver = gdal::DriverManager::get_driver_by_name(format.driver_name())?;
+    let mut options = RasterCreationOptions::new();
+
+    if format == OutputFormat::GeoPackage {
+        // Float32 values can only be stored as TIFF tiles in a GeoPackage
+        options.set_name_value("TILE_FORMAT", "TIFF")?;
+        options.set_name_value("RASTER_TABLE", "primary_production")?;
+    }
+
+    let saved_dataset = dataset.create_copy(&driver, filename, &options)?;
+
+    if format == OutputFormat::GeoPackage {
+        copy_band_metadata(dataset, &saved_dataset)?;
+    }
+
+    Ok(saved_dataset)
+}
+
+/// Creates an in-memory 2-band raster for the temporal mean PP of `period` (first and last date)
+/// and the number of valid days of each pixel, georeferenced like `daily_dataset` and in the same
+/// unit. Values are written with `write_temporal_mean`.
+pub fn create_temporal_mean_dataset(
+    daily_dataset: &Dataset,
+    period: (&NaiveDate, &NaiveDate),
+    nodata: f64,
+) -> Result<Dataset, Box<dyn std::error::Error>> {
+    let driver = gdal::DriverManager::get_driver_by_name("GTiff")?;
+    let size = daily_dataset.raster_size();
+    let mut dataset =
+        driver.create_with_band_type::<f32, _>(vsimem_path("pp_mean"), size.0, size.1, 2)?;
+
+    dataset.set_geo_transform(&daily_dataset.geo_transform()?)?;
+    if let Ok(spatial_ref) = daily_dataset.spatial_ref() {
+        dataset.set_spatial_ref(&spatial_ref)?;
+    }
+
+    // Software and geospatial extent of the daily output
+    for entry in daily_dataset
+        .metadata()
+        .filter(|entry| entry.is_default_domain())
+        .filter(|entry| entry.key == "TIFFTAG_SOFTWARE" || entry.key.starts_with("geospatial_"))
+    {
+        dataset.set_metadata_item(&entry.key, &entry.value, "")?;
+    }
+
+    let (start, end) = (period.0.to_string(), period.1.to_string());
+    dataset.set_metadata_item("TIFFTAG_DOCUMENTNAME", "Mean Primary Production", "")?;
+    dataset.set_metadata_item("time_coverage_start", &start, "")?;
+    dataset.set_metadata_item("time_coverage_end", &end, "")?;
+
+    let mut mean_band = dataset.rasterband(1)?;
+    mean_band.set_description("Mean Primary Production")?;
+    mean_band.set_metadata_item(
+        "long_name",
+        &format!(
+            "Temporal mean of the daily primary production from {} to {}",
+            start, end
+        ),
+        "",
+    )?;
+    mean_band.set_metadata_item("cell_methods", "time: mean", "")?;
+    let unit = daily_dataset.rasterband(1)?.metadata_item("Unit", "");
+    mean_band.set_metadata_item("Unit", unit.as_deref().unwrap_or("mg C m-2 d-1"), "")?;
+    mean_band.set_no_data_value(Some(nodata))?;
+
+    let mut count_band = dataset.rasterband(2)?;
+    count_band.set_description("Valid Days")?;
+    count_band.set_metadata_item(
+        "long_name",
+        "Number of days with a valid primary production",
+        "",
+    )?;
+    count_band.set_metadata_item("Unit", "days", "")?;
+
+    Ok(dataset)
+}
+
+/// Writes the mean PP and valid day counts (row-major order) to a raster created with
+/// `create_temporal_mean_dataset`
+pub fn write_temporal_mean(
+    dataset: &Dataset,
+    mean_values: Vec<f32>,
+    valid_days: Vec<f32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let size = dataset.raster_size();
+
+    for (band_index, values) in [(1, mean_values), (2, valid_days)] {
+        let mut band = dataset.rasterband(band_index)?;
+        band.write((0, 0), size, &mut Buffer::new(size, values))?;
+    }
+
+    Ok(())
+}
+
+// Overviews are built on an in-memory copy first, so COPY_SRC_OVERVIEWS can lay them out before
+// the full resolution data in the final file, as expected for a COG
+fn save_cog(dataset: &Dataset, filename: &str) -> Result<Dataset, Box<dyn std::error::Error>> {
+    let driver = gdal::DriverManager::get_driver_by_name("GTiff")?;
+    let tmp_filename = vsimem_path("cog_overviews");
+
+    let mut tmp_dataset =
+        dataset.create_copy(&driver, &tmp_filename, &RasterCreationOptions::new())?;
+
+    let (width, height) = dataset.raster_size();
+    let levels = overview_levels(width.max(height));
+    if !levels.is_empty() {
+        tmp_dataset.build_overviews("AVERAGE", &levels, &[])?;
+    }
+
+    let block_size = COG_BLOCK_SIZE.to_string();
+    let mut options = RasterCreationOptions::new();
+    options.set_name_value("TILED", "YES")?;
+    options.set_name_value("BLOCKXSIZE", &block_size)?;
+    options.set_name_value("BLOCKYSIZE", &block_size)?;
+    options.set_name_value("COMPRESS", "DEFLATE")?;
+    options.set_name_value("COPY_SRC_OVERVIEWS", "YES")?;
+
+    let saved_dataset = tmp_dataset.create_copy(&driver, filename, &options);
+
+    drop(tmp_dataset);
+    gdal::vsi::unlink_mem_file(&tmp_filename)?;
+
+    Ok(saved_dataset?)
+}
+
+// Decimation factors (2, 4, 8, ...) until the coarsest overview fits in a single tile
+fn overview_levels(max_dimension: usize) -> Vec<i32> {
+    let mut levels = Vec::new();
+    let mut level = 2;
+
+    while max_dimension / (level / 2) > COG_BLOCK_SIZE {
+        levels.push(level as i32);
+        level *= 2;
+    }
+
+    levels
+}
+
+// GPKG does not carry band descriptions and metadata over in create_copy, set them explicitly
+fn copy_band_metadata(
+    source: &Dataset,
+    target: &Dataset,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for band_index in 1..=source.raster_count() {
+        let source_band = source.rasterband(band_index)?;
+        let mut target_band = target.rasterband(band_index)?;
+
+        target_band.set_description(&source_band.description()?)?;
+
+        for entry in source_band
+            .metadata()
+            .filter(|entry| entry.is_default_domain())
+        {
+            target_band.set_metadata_item(&entry.key, &entry.value, "")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_release_mem_dataset() {
+        let driver = gdal::DriverManager::get_driver_by_name("GTiff").unwrap();
+        let filename = vsimem_path("release");
+        assert_ne!(filename, vsimem_path("release"));
+
+        let dataset = driver
+            .create_with_band_type::<f32, _>(&filename, 2, 2, 1)
+            .unwrap();
+        release_mem_dataset(dataset).unwrap();
+
+        assert!(Dataset::open(&filename).is_err());
+    }
+
+    #[test]
+    fn test_overview_levels() {
+        assert!(overview_levels(200).is_empty());
+        assert_eq!(overview_levels(512), vec![2]);
+        assert_eq!(overview_levels(2000), vec![2, 4, 8]);
+    }
+
+    #[test]
+    fn test_save_cog_is_tiled_with_overviews() {
+        let Ok(driver) = gdal::DriverManager::get_driver_by_name("GTiff") else {
+            return;
+        };
+
+        // Skip if this GDAL build has no DEFLATE support
+        let creation_options = driver
+            .metadata_item("DMD_CREATIONOPTIONLIST", "")
+            .unwrap_or_default();
+        if !creation_options.contains("DEFLATE") {
+            return;
+        }
+
+        let size = 2 * COG_BLOCK_SIZE;
+        let dataset = driver
+            .create_with_band_type::<f32, _>("/vsimem/cog_input.tif", size, size, 1)
+            .unwrap();
+        {
+            let mut band = dataset.rasterband(1).unwrap();
+            let mut buffer = gdal::raster::Buffer::new((size, size), vec![1.0f32; size * size]);
+            band.write((0, 0), (size, size), &mut buffer).unwrap();
+        }
+
+        let dir = tempdir().unwrap();
+        let filename = dir.path().join("pp.tif");
+        let saved = save_dataset(&dataset, filename.to_str().unwrap(), OutputFormat::Cog).unwrap();
+
+        let band = saved.rasterband(1).unwrap();
+        assert_eq!(band.block_size(), (COG_BLOCK_SIZE, COG_BLOCK_SIZE));
+        assert!(band.overview_count().unwrap() >= 1);
+
+        drop(dataset);
+        gdal::vsi::unlink_mem_file("/vsimem/cog_input.tif").unwrap();
+    }
+}
@@ -1,16 +1,32 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
+use crate::iop::zeu::euphotic_depth_morel;
+use crate::models::vgpm::{DEFAULT_MAX_PP, vgpm_full, vgpm_with_zeu};
+
+/// How the euphotic depth used in the PP calculation is derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EuphoticDepthModel {
+    /// 1% light level from Kd(490): `4.6 / Kd(490)`
+    #[default]
+    Kd,
+    /// Chlorophyll-based Morel & Maritorena (2001) relationship, does not need Kd(490)
+    Morel,
+}
+
 // Oceanographic data for a single pixel
 #[derive(Debug, Clone)]
 pub struct PixelData {
     pub x: u32,
     pub y: u32,
-    pub rrs_443: Option<f32>, // Remote sensing reflectance at 443nm
-    pub rrs_490: Option<f32>, // Remote sensing reflectance at 490nm
-    pub rrs_555: Option<f32>, // Remote sensing reflectance at 555nm
-    pub kd_490: Option<f32>,  // Diffuse attenuation coefficient
-    pub sst: Option<f32>,     // Sea surface temperature
-    pub chlor_a: Option<f32>, // Chlorophyll-a concentration
+    pub rrs_443: Option<f32>,    // Remote sensing reflectance at 443nm
+    pub rrs_490: Option<f32>,    // Remote sensing reflectance at 490nm
+    pub rrs_555: Option<f32>,    // Remote sensing reflectance at 555nm
+    pub kd_490: Option<f32>,     // Diffuse attenuation coefficient
+    pub sst: Option<f32>,        // Sea surface temperature
+    pub chlor_a: Option<f32>,    // Chlorophyll-a concentration
+    pub par: Option<f32>,        // Incident PAR (mol photons m-2 d-1)
+    pub day_length: Option<f32>, // Day length (hours)
 }
 
 impl PixelData {
@@ -24,31 +40,98 @@ impl PixelData {
             kd_490: None,
             sst: None,
             chlor_a: None,
+            par: None,
+            day_length: None,
+        }
+    }
+
+    /// Builds a pixel from values keyed by input band name (`chlor_a`, `sst`, `kd_490`,
+    /// `rrs_443`, `rrs_490`, `rrs_555`, `par`, `day_length`). Unknown band names are ignored.
+    pub fn from_bands(x: u32, y: u32, bands: &HashMap<String, f32>) -> Self {
+        let mut pixel = Self::new(x, y);
+
+        for (name, &value) in bands {
+            let field = match name.as_str() {
+                "rrs_443" => &mut pixel.rrs_443,
+                "rrs_490" => &mut pixel.rrs_490,
+                "rrs_555" => &mut pixel.rrs_555,
+                "kd_490" => &mut pixel.kd_490,
+                "sst" => &mut pixel.sst,
+                "chlor_a" => &mut pixel.chlor_a,
+                "par" => &mut pixel.par,
+                "day_length" => &mut pixel.day_length,
+                _ => continue,
+            };
+            *field = Some(value);
         }
+
+        pixel
+    }
+
+    /// Euphotic depth (m) from the chlorophyll concentration with the Morel & Maritorena (2001)
+    /// relationship
+    pub fn euphotic_depth_morel(&self) -> Option<f32> {
+        let zeu = euphotic_depth_morel(self.chlor_a? as f64) as f32;
+        zeu.is_finite().then_some(zeu)
+    }
+
+    // Euphotic depth as the 1% light level from Kd(490)
+    fn euphotic_depth_kd(&self) -> Option<f32> {
+        let kd = self.kd_490?; // m−1 (auto-scaled by processor)
+        (kd > 0.0).then(|| 4.6 / kd)
     }
 
     // Primary production calculation using Vertically Generalized Production Model (VGPM)
     pub fn calculate_primary_production(&self) -> Option<f32> {
+        self.calculate_primary_production_with(EuphoticDepthModel::Kd)
+    }
+
+    /// Same as `calculate_primary_production`, choosing how the euphotic depth is derived
+    pub fn calculate_primary_production_with(&self, zeu_model: EuphoticDepthModel) -> Option<f32> {
+        self.calculate_primary_production_bounded(zeu_model, DEFAULT_MAX_PP)
+    }
+
+    /// Same as `calculate_primary_production_with`, discarding values above `max_pp`
+    /// (mg C m^-2 d^-1) instead of `DEFAULT_MAX_PP`
+    pub fn calculate_primary_production_bounded(
+        &self,
+        zeu_model: EuphoticDepthModel,
+        max_pp: f32,
+    ) -> Option<f32> {
         let chl = self.chlor_a?; // mg/m3
         let sst = self.sst?; // °C (auto-scaled by processor)
-        let kd = self.kd_490?; // m−1 (auto-scaled by processor)
 
-        if chl <= 0.0 || kd <= 0.0 {
+        if chl <= 0.0 {
             return None;
         }
 
-        // Simplified VGPM calculation
-        let exponent = 0.0275 * sst - 0.07 * sst.powf(2.0) + 0.0025 * sst.powf(3.0);
-        let pbopt = 1.54 * 10_f32.powf(exponent);
-        let zeu = 4.6 / kd; // Euphotic depth
-        let pp = 0.66125 * pbopt * chl * zeu; // mg C m-2 d-1
+        // Simplified VGPM calculation, see `models::vgpm`
+        vgpm_with_zeu(chl, sst, self.euphotic_depth(zeu_model)?, max_pp)
+    }
+
+    /// Complete VGPM (`models::vgpm::vgpm_full`), which also needs the PAR and the day length
+    pub fn calculate_primary_production_full(&self, zeu_model: EuphoticDepthModel) -> Option<f32> {
+        let chl = self.chlor_a?;
 
-        // Check for reasonable values (typical range: 10-2000 mg C m-2 d-1)
-        if !pp.is_finite() || pp <= 0.0 || pp > 2000.0 {
+        if chl <= 0.0 {
             return None;
         }
 
-        Some(pp)
+        vgpm_full(
+            chl,
+            self.sst?,
+            self.euphotic_depth(zeu_model)?,
+            self.par?,
+            self.day_length?,
+        )
+    }
+
+    /// Euphotic depth (m) used by the production models
+    pub fn euphotic_depth(&self, zeu_model: EuphoticDepthModel) -> Option<f32> {
+        match zeu_model {
+            EuphoticDepthModel::Kd => self.euphotic_depth_kd(),
+            EuphoticDepthModel::Morel => self.euphotic_depth_morel(),
+        }
     }
 }
 
@@ -61,6 +144,8 @@ impl Display for PixelData {
         writeln!(f, "  Kd 490nm: {:?}", self.kd_490)?;
         writeln!(f, "  SST: {:?}", self.sst)?;
         writeln!(f, "  Chlor-a: {:?}", self.chlor_a)?;
+        writeln!(f, "  PAR: {:?}", self.par)?;
+        writeln!(f, "  Day length: {:?}", self.day_length)?;
         Ok(())
     }
 }
@@ -80,4 +165,82 @@ mod tests {
         assert!(pp.is_some());
         assert!(pp.unwrap() > 0.0);
     }
+
+    #[test]
+    fn test_primary_production_with_morel_zeu() {
+        let mut pixel = PixelData::new(0, 0);
+        pixel.chlor_a = Some(1.0);
+        pixel.sst = Some(15.0);
+
+        // No Kd(490): only the Morel variant can be computed
+        assert!(pixel.calculate_primary_production().is_none());
+
+        let zeu = pixel.euphotic_depth_morel().unwrap();
+        let pp = pixel
+            .calculate_primary_production_with(EuphoticDepthModel::Morel)
+            .unwrap();
+
+        // Same pixel with Kd(490) giving the same euphotic depth
+        pixel.kd_490 = Some(4.6 / zeu);
+        let pp_kd = pixel.calculate_primary_production().unwrap();
+        assert!((pp - pp_kd).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_primary_production_full() {
+        let mut pixel = PixelData::new(0, 0);
+        pixel.chlor_a = Some(1.0);
+        pixel.sst = Some(0.0);
+        pixel.kd_490 = Some(0.1);
+
+        assert!(pixel.calculate_primary_production().is_some());
+        assert!(
+            pixel
+                .calculate_primary_production_full(EuphoticDepthModel::Kd)
+                .is_none()
+        );
+
+        pixel.par = Some(40.0);
+        pixel.day_length = Some(12.0);
+        assert_eq!(
+            pixel.calculate_primary_production_full(EuphoticDepthModel::Kd),
+            vgpm_full(1.0, 0.0, 4.6 / 0.1, 40.0, 12.0)
+        );
+    }
+
+    #[test]
+    fn test_from_bands() {
+        let bands = HashMap::from([
+            ("chlor_a".to_string(), 1.0),
+            ("sst".to_string(), 15.0),
+            ("kd_490".to_string(), 0.1),
+            ("rrs_443".to_string(), 0.004),
+            ("aot_869".to_string(), 0.1),
+            ("par".to_string(), 40.0),
+        ]);
+
+        let pixel = PixelData::from_bands(3, 4, &bands);
+
+        assert_eq!((pixel.x, pixel.y), (3, 4));
+        assert_eq!(pixel.chlor_a, Some(1.0));
+        assert_eq!(pixel.sst, Some(15.0));
+        assert_eq!(pixel.kd_490, Some(0.1));
+        assert_eq!(pixel.rrs_443, Some(0.004));
+        assert_eq!(pixel.rrs_490, None);
+        assert_eq!(pixel.rrs_555, None);
+        assert_eq!(pixel.par, Some(40.0));
+        assert_eq!(pixel.day_length, None);
+        assert!(pixel.calculate_primary_production().is_some());
+    }
+
+    #[test]
+    fn test_single_pixel_data_type() {
+        use std::any::TypeId;
+
+        // The re-export and the module path must refer to the same type
+        assert_eq!(
+            TypeId::of::<crate::oceanographic_model::PixelData>(),
+            TypeId::of::<crate::oceanographic_model::pixel::PixelData>()
+        );
+    }
 }
@@ -0,0 +1,144 @@
+//! Export of the values extracted at stations
+//!
+//! Writers for the output of `OceanographicProcessor::extract_points`, independent of GDAL, whose
+//! files can be joined with the station metadata on their coordinates.
+
+use serde_json::{Value, json};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Fails if there is not one value per point
+fn check_lengths(
+    points: &[(f64, f64)],
+    values: &[Option<f32>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if points.len() != values.len() {
+        return Err(format!(
+            "{} points but {} values, expected one value per point",
+            points.len(),
+            values.len()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Writes the points as CSV: a `lon,lat,pp` header and one row per point, in the same order.
+/// `pp` is empty for `None` and non-finite values.
+pub fn write_points_csv<P: AsRef<Path>>(
+    points: &[(f64, f64)],
+    values: &[Option<f32>],
+    path: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_lengths(points, values)?;
+
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "lon,lat,pp")?;
+
+    for (&(lon, lat), value) in points.iter().zip(values) {
+        match value.filter(|pp| pp.is_finite()) {
+            Some(pp) => writeln!(w, "{},{},{}", lon, lat, pp)?,
+            None => writeln!(w, "{},{},", lon, lat)?,
+        }
+    }
+
+    w.flush()?;
+
+    Ok(())
+}
+
+/// GeoJSON FeatureCollection with a Point feature per point (lon/lat) and its value as `pp`
+/// property, `null` for `None` and non-finite values
+pub fn points_geojson(
+    points: &[(f64, f64)],
+    values: &[Option<f32>],
+) -> Result<Value, Box<dyn std::error::Error>> {
+    check_lengths(points, values)?;
+
+    let features: Vec<Value> = points
+        .iter()
+        .zip(values)
+        .map(|(&(lon, lat), value)| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [lon, lat],
+                },
+                "properties": {
+                    "pp": value.filter(|pp| pp.is_finite()),
+                },
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+}
+
+/// Writes `points_geojson` to `path`
+pub fn write_points_geojson<P: AsRef<Path>>(
+    points: &[(f64, f64)],
+    values: &[Option<f32>],
+    path: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let geojson = points_geojson(points, values)?;
+    std::fs::write(path, serde_json::to_string_pretty(&geojson)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const POINTS: [(f64, f64); 3] = [(-64.5, 72.25), (-60.0, 70.5), (-58.75, 73.0)];
+    const VALUES: [Option<f32>; 3] = [Some(412.5), None, Some(f32::NAN)];
+
+    #[test]
+    fn test_write_points_csv() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stations.csv");
+
+        write_points_csv(&POINTS, &VALUES, &path).unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            csv,
+            "lon,lat,pp\n-64.5,72.25,412.5\n-60,70.5,\n-58.75,73,\n"
+        );
+
+        assert!(write_points_csv(&POINTS, &VALUES[..2], &path).is_err());
+    }
+
+    #[test]
+    fn test_write_points_geojson() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stations.geojson");
+
+        write_points_geojson(&POINTS, &VALUES, &path).unwrap();
+
+        let geojson: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[0]["type"], "Feature");
+        assert_eq!(features[0]["geometry"]["type"], "Point");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            json!([-64.5, 72.25])
+        );
+        assert_eq!(features[0]["properties"]["pp"], 412.5);
+        assert!(features[1]["properties"]["pp"].is_null());
+        assert!(features[2]["properties"]["pp"].is_null());
+
+        assert!(points_geojson(&POINTS[..1], &VALUES).is_err());
+    }
+}
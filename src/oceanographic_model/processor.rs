@@ -1,7 +1,261 @@
-use super::pixel::PixelData;
+use super::output::vsimem_path;
+use super::pixel::{EuphoticDepthModel, PixelData};
 use crate::bbox::Bbox;
-use gdal::{Dataset, Metadata};
-use std::{collections::HashMap, fmt::Display, path::Path};
+use crate::config::PpUnit;
+use crate::iop::flags::{DEFAULT_L2_FLAGS_MASK, is_masked};
+use crate::iop::kd::kd490_from_rrs;
+use crate::iop::qaa::{QAA_FAILURE_FLAGS, QaaResult, qaa_v6};
+use crate::lut::sunpos::day_length_hours;
+use crate::models::vgpm::{DEFAULT_MAX_PP, pbopt_simplified};
+use crate::sat_bands::Satellites;
+use chrono::NaiveDate;
+use gdal::raster::{GdalType, RasterBand};
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use gdal::{Dataset, GeoTransformEx, Metadata};
+use log::{info, warn};
+use ndarray::Array2;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::{fmt::Display, path::Path};
+
+/// Name of the optional Level-2 quality flags dataset, see `with_l2_flags_mask`
+pub const L2_FLAGS_DATASET: &str = "l2_flags";
+
+/// Name of the optional bathymetry dataset used to skip land and shallow pixels
+pub const BATHYMETRY_DATASET: &str = "bathymetry";
+
+// Relative tolerance used when comparing raw values to the band no-data value
+const NODATA_RELATIVE_TOLERANCE: f64 = 1e-6;
+
+// Converts a raw band value to its physical value (raw * scale + offset). Returns None for NaN
+// fill values and for values matching the no-data value, which is compared in raw units with a
+// relative tolerance since fill values such as 9.96921e36 do not round-trip exactly through f32.
+fn decode_pixel_value(
+    raw_value: f32,
+    scale: f64,
+    offset: f64,
+    no_data: Option<f64>,
+) -> Option<f32> {
+    if raw_value.is_nan() {
+        return None;
+    }
+
+    let raw_value = raw_value as f64;
+
+    if no_data
+        .is_some_and(|nd| (raw_value - nd).abs() <= NODATA_RELATIVE_TOLERANCE * nd.abs().max(1.0))
+    {
+        return None;
+    }
+
+    Some((raw_value * scale + offset) as f32)
+}
+
+// Number of points sampled along each bbox edge when it is reprojected, since straight lon/lat
+// edges become curves in projections such as polar stereographic
+const EDGE_DENSIFY_POINTS: usize = 21;
+
+// Points along the edges of a rectangle
+fn rectangle_edge_points(xmin: f64, xmax: f64, ymin: f64, ymax: f64) -> Vec<(f64, f64)> {
+    let mut points = Vec::with_capacity(4 * EDGE_DENSIFY_POINTS);
+
+    for i in 0..EDGE_DENSIFY_POINTS {
+        let t = i as f64 / (EDGE_DENSIFY_POINTS - 1) as f64;
+        let x = xmin + t * (xmax - xmin);
+        let y = ymin + t * (ymax - ymin);
+        points.extend([(x, ymin), (x, ymax), (xmin, y), (xmax, y)]);
+    }
+
+    points
+}
+
+// Smallest (xmin, xmax, ymin, ymax) rectangle enclosing the points
+fn enclosing_rectangle(points: &[(f64, f64)]) -> (f64, f64, f64, f64) {
+    points.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(xmin, xmax, ymin, ymax), &(x, y)| (xmin.min(x), xmax.max(x), ymin.min(y), ymax.max(y)),
+    )
+}
+
+// WGS84 with longitude/latitude axis order, the CRS of `Bbox`
+fn wgs84() -> Result<SpatialRef, Box<dyn std::error::Error>> {
+    let mut srs = SpatialRef::from_epsg(4326)?;
+    srs.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    Ok(srs)
+}
+
+fn transform_points(
+    points: &[(f64, f64)],
+    source: &SpatialRef,
+    target: &SpatialRef,
+) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error>> {
+    let transform = CoordTransform::new(source, target)?;
+
+    let (mut xs, mut ys): (Vec<f64>, Vec<f64>) = points.iter().copied().unzip();
+    transform.transform_coords(&mut xs, &mut ys, &mut [])?;
+
+    Ok(xs.into_iter().zip(ys).collect())
+}
+
+// Relative tolerance used when comparing the geotransforms of two grids
+const GRID_RELATIVE_TOLERANCE: f64 = 1e-9;
+
+// Size, geotransform and CRS of a dataset, see `OceanographicProcessor::with_aligned_grids`
+struct DatasetGrid {
+    width: usize,
+    height: usize,
+    geotransform: [f64; 6],
+    spatial_ref: Option<SpatialRef>,
+}
+
+impl DatasetGrid {
+    fn new(dataset: &Dataset) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = dataset.raster_size();
+
+        Ok(Self {
+            width,
+            height,
+            geotransform: dataset.geo_transform()?,
+            spatial_ref: dataset.spatial_ref().ok(),
+        })
+    }
+
+    // Area of a pixel, in the units of the CRS
+    fn pixel_area(&self) -> f64 {
+        let gt = &self.geotransform;
+        (gt[1] * gt[5] - gt[2] * gt[4]).abs()
+    }
+
+    // Whether both grids have the same pixels
+    fn matches(&self, other: &DatasetGrid) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self
+                .geotransform
+                .iter()
+                .zip(&other.geotransform)
+                .all(|(a, b)| {
+                    (a - b).abs() <= GRID_RELATIVE_TOLERANCE * a.abs().max(b.abs()).max(1.0)
+                })
+            && self.spatial_ref == other.spatial_ref
+    }
+
+    // Resamples every band of the dataset onto this grid, in an in-memory Float64 dataset keeping
+    // the no-data value, scale and offset of the bands. Pixels outside the dataset are no-data
+    // (NaN for bands without a no-data value).
+    fn resample(
+        &self,
+        dataset: &Dataset,
+        nearest: bool,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let driver = gdal::DriverManager::get_driver_by_name("MEM")?;
+        let mut resampled = driver.create_with_band_type::<f64, _>(
+            "",
+            self.width,
+            self.height,
+            dataset.raster_count(),
+        )?;
+
+        resampled.set_geo_transform(&self.geotransform)?;
+        if let Some(spatial_ref) = &self.spatial_ref {
+            resampled.set_spatial_ref(spatial_ref)?;
+        }
+
+        for index in 1..=dataset.raster_count() {
+            let band = dataset.rasterband(index)?;
+            let mut resampled_band = resampled.rasterband(index)?;
+
+            let no_data = band.no_data_value().unwrap_or(f64::NAN);
+            resampled_band.set_no_data_value(Some(no_data))?;
+            resampled_band.fill(no_data, None)?;
+
+            if let Some(scale) = band.scale() {
+                resampled_band.set_scale(scale)?;
+            }
+            if let Some(offset) = band.offset() {
+                resampled_band.set_offset(offset)?;
+            }
+        }
+
+        if nearest {
+            self.resample_nearest(dataset, &resampled)?;
+        } else {
+            gdal::raster::reproject(dataset, &resampled)?;
+        }
+
+        Ok(resampled)
+    }
+
+    // Nearest neighbour resampling, which keeps flag values intact where bilinear interpolation
+    // would mix the bits of neighbouring pixels
+    fn resample_nearest(
+        &self,
+        dataset: &Dataset,
+        resampled: &Dataset,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let source = DatasetGrid::new(dataset)?;
+        let inverse = source.geotransform.invert()?;
+
+        let mut centers = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                centers.push(self.geotransform.apply(x as f64 + 0.5, y as f64 + 0.5));
+            }
+        }
+
+        let centers = match (&self.spatial_ref, &source.spatial_ref) {
+            (Some(target), Some(spatial_ref)) if target != spatial_ref => {
+                let mut target = target.clone();
+                target.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+                let mut spatial_ref = spatial_ref.clone();
+                spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+
+                transform_points(&centers, &target, &spatial_ref)?
+            }
+            _ => centers,
+        };
+
+        // Index of the source pixel of each resampled pixel, `None` outside the dataset
+        let indices: Vec<Option<usize>> = centers
+            .iter()
+            .map(|&(x, y)| {
+                let (col, row) = inverse.apply(x, y);
+                let inside = col >= 0.0
+                    && row >= 0.0
+                    && (col as usize) < source.width
+                    && (row as usize) < source.height;
+
+                inside.then(|| row as usize * source.width + col as usize)
+            })
+            .collect();
+
+        for index in 1..=dataset.raster_count() {
+            let band = dataset.rasterband(index)?;
+            let values = band.read_band_as::<f64>()?;
+            let no_data = band.no_data_value().unwrap_or(f64::NAN);
+
+            let resampled_values: Vec<f64> = indices
+                .iter()
+                .map(|pixel| pixel.map_or(no_data, |i| values.data()[i]))
+                .collect();
+
+            SpatialRegion::write_band_block(
+                resampled,
+                index,
+                (0, 0),
+                (self.width, self.height),
+                resampled_values,
+            )?;
+        }
+
+        Ok(())
+    }
+}
 
 struct SpatialRegion {
     start_x: u32,
@@ -9,6 +263,10 @@ struct SpatialRegion {
     output_width: u32,
     output_height: u32,
     geotransform: [f64; 6],
+    // Whether the requested bbox extends beyond the dataset and was clamped to it
+    clipped: bool,
+    // CRS of the geotransform when it is not geographic, see `new_projected`
+    spatial_ref: Option<SpatialRef>,
 }
 
 impl SpatialRegion {
@@ -18,28 +276,83 @@ impl SpatialRegion {
         dataset_width: u32,
         dataset_height: u32,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let Bbox {
-            xmin: min_lon,
-            xmax: max_lon,
-            ymin: min_lat,
-            ymax: max_lat,
-        } = bbox;
-
-        // Convert geographic coordinates to pixel coordinates
-        let pixel_min_x = ((min_lon - geotransform[0]) / geotransform[1]).floor() as i32;
-        let pixel_max_x = ((max_lon - geotransform[0]) / geotransform[1]).ceil() as i32;
-        let pixel_min_y = ((max_lat - geotransform[3]) / geotransform[5]).floor() as i32;
-        let pixel_max_y = ((min_lat - geotransform[3]) / geotransform[5]).ceil() as i32;
+        let corners = [
+            (bbox.xmin, bbox.ymax),
+            (bbox.xmax, bbox.ymax),
+            (bbox.xmin, bbox.ymin),
+            (bbox.xmax, bbox.ymin),
+        ];
+
+        Self::from_points(bbox, &corners, geotransform, dataset_width, dataset_height)
+    }
+
+    /// Same as `new` for a dataset whose geotransform is in the CRS `spatial_ref` (e.g. polar
+    /// stereographic) and a bbox in the CRS `bbox_crs` (lon/lat by default). The bbox edges are
+    /// reprojected into the dataset CRS before the pixel window is computed.
+    fn new_projected(
+        bbox: &Bbox,
+        geotransform: &[f64; 6],
+        dataset_width: u32,
+        dataset_height: u32,
+        bbox_crs: &SpatialRef,
+        spatial_ref: &SpatialRef,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut target = spatial_ref.clone();
+        target.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+
+        let edges = rectangle_edge_points(bbox.xmin, bbox.xmax, bbox.ymin, bbox.ymax);
+        let points = transform_points(&edges, bbox_crs, &target)?;
+
+        let mut region =
+            Self::from_points(bbox, &points, geotransform, dataset_width, dataset_height)?;
+        region.spatial_ref = Some(target);
+
+        Ok(region)
+    }
+
+    // Pixel window enclosing the points (in the geotransform CRS) of the requested bbox
+    fn from_points(
+        bbox: &Bbox,
+        points: &[(f64, f64)],
+        geotransform: &[f64; 6],
+        dataset_width: u32,
+        dataset_height: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Convert coordinates to pixel coordinates with the inverse of the full affine
+        // geotransform, so rotated or sheared grids are supported. The bbox corners then form a
+        // parallelogram in pixel space, whose enclosing window is used.
+        let inverse = geotransform.invert()?;
+        let pixels: Vec<(f64, f64)> = points.iter().map(|&(x, y)| inverse.apply(x, y)).collect();
+
+        let (min_px, max_px, min_py, max_py) = enclosing_rectangle(&pixels);
+
+        let pixel_min_x = min_px.floor() as i32;
+        let pixel_max_x = max_px.ceil() as i32;
+        let pixel_min_y = min_py.floor() as i32;
+        let pixel_max_y = max_py.ceil() as i32;
 
         // Ensure bounds are within dataset dimensions and handle negative values
-        let start_x = pixel_min_x.max(0) as u32;
-        let end_x = pixel_max_x.max(0).min(dataset_width as i32) as u32;
-        let start_y = pixel_min_y.max(0) as u32;
-        let end_y = pixel_max_y.max(0).min(dataset_height as i32) as u32;
+        let start_x = pixel_min_x.clamp(0, dataset_width as i32) as u32;
+        let end_x = pixel_max_x.clamp(0, dataset_width as i32) as u32;
+        let start_y = pixel_min_y.clamp(0, dataset_height as i32) as u32;
+        let end_y = pixel_max_y.clamp(0, dataset_height as i32) as u32;
 
         // Calculate the output dimensions
-        let output_width = end_x - start_x;
-        let output_height = end_y - start_y;
+        let output_width = end_x.saturating_sub(start_x);
+        let output_height = end_y.saturating_sub(start_y);
+
+        if output_width == 0 || output_height == 0 {
+            return Err(format!(
+                "Bounding box ({}, {}, {}, {}) does not overlap the dataset extent",
+                bbox.xmin, bbox.xmax, bbox.ymin, bbox.ymax
+            )
+            .into());
+        }
+
+        let clipped = pixel_min_x < 0
+            || pixel_min_y < 0
+            || pixel_max_x > dataset_width as i32
+            || pixel_max_y > dataset_height as i32;
 
         Ok(Self {
             start_x,
@@ -47,49 +360,157 @@ impl SpatialRegion {
             output_width,
             output_height,
             geotransform: *geotransform,
+            clipped,
+            spatial_ref: None,
         })
     }
 
-    fn create_output_dataset(
+    /// Geographic extent actually covered by the region, which is smaller than the requested bbox
+    /// when the latter extends beyond the dataset. Pixel edges are used, so the extent is aligned
+    /// on the dataset grid. For rotated or projected grids, this is the lon/lat bbox enclosing the
+    /// region edges.
+    fn covered_bbox(&self) -> Result<Bbox, Box<dyn std::error::Error>> {
+        let (x0, y0) = (self.start_x as f64, self.start_y as f64);
+        let (x1, y1) = (
+            x0 + self.output_width as f64,
+            y0 + self.output_height as f64,
+        );
+
+        let mut points: Vec<(f64, f64)> = rectangle_edge_points(x0, x1, y0, y1)
+            .into_iter()
+            .map(|(px, py)| self.geotransform.apply(px, py))
+            .collect();
+
+        if let Some(spatial_ref) = &self.spatial_ref {
+            points = transform_points(&points, spatial_ref, &wgs84()?)?;
+        }
+
+        let (xmin, xmax, ymin, ymax) = enclosing_rectangle(&points);
+
+        Ok(Bbox {
+            xmin,
+            xmax,
+            ymin,
+            ymax,
+        })
+    }
+
+    // Geotransform of a raster covering the region
+    fn output_geotransform(&self) -> [f64; 6] {
+        let (top_left_x, top_left_y) = self
+            .geotransform
+            .apply(self.start_x as f64, self.start_y as f64);
+
+        [
+            top_left_x,
+            self.geotransform[1], // pixel width
+            self.geotransform[2], // rotation (usually 0)
+            top_left_y,
+            self.geotransform[4], // rotation (usually 0)
+            self.geotransform[5], // pixel height (negative)
+        ]
+    }
+
+    // Creates an in-memory GeoTIFF covering the region, georeferenced like the sample dataset.
+    // `name` prefixes its unique /vsimem path.
+    fn create_georeferenced_dataset<T: GdalType>(
         &self,
         sample_dataset: &Dataset,
-        pp_values: Vec<f32>,
+        name: &str,
+        band_count: usize,
     ) -> Result<Dataset, Box<dyn std::error::Error>> {
-        let mem_filename = "/vsimem/pp_output.tif";
         let driver = gdal::DriverManager::get_driver_by_name("GTiff")?;
-        let mut dataset = driver.create_with_band_type::<f32, _>(
-            mem_filename,
+        let mut dataset = driver.create_with_band_type::<T, _>(
+            vsimem_path(name),
             self.output_width as usize,
             self.output_height as usize,
-            1,
+            band_count,
         )?;
 
-        let output_geotransform = [
-            self.geotransform[0] + (self.start_x as f64) * self.geotransform[1], // top-left x
-            self.geotransform[1],                                                // pixel width
-            self.geotransform[2], // rotation (usually 0)
-            self.geotransform[3] + (self.start_y as f64) * self.geotransform[5], // top-left y
-            self.geotransform[4], // rotation (usually 0)
-            self.geotransform[5], // pixel height (negative)
-        ];
-
-        dataset.set_geo_transform(&output_geotransform)?;
+        dataset.set_geo_transform(&self.output_geotransform())?;
 
         if let Ok(spatial_ref) = sample_dataset.spatial_ref() {
             dataset.set_spatial_ref(&spatial_ref)?;
         }
 
-        // Set dataset metadata
-        dataset.set_metadata_item("TIFFTAG_DOCUMENTNAME", "Primary Production", "")?;
         dataset.set_metadata_item(
-            "TIFFTAG_IMAGEDESCRIPTION",
-            "Primary production calculated from satellite oceanographic data",
+            "TIFFTAG_SOFTWARE",
+            "Boreas - Oceanographic Processing Tool",
             "",
         )?;
 
+        // Extent covered by the output (ACDD attribute names)
+        let covered = self.covered_bbox()?;
+        dataset.set_metadata_item("geospatial_lon_min", &covered.xmin.to_string(), "")?;
+        dataset.set_metadata_item("geospatial_lon_max", &covered.xmax.to_string(), "")?;
+        dataset.set_metadata_item("geospatial_lat_min", &covered.ymin.to_string(), "")?;
+        dataset.set_metadata_item("geospatial_lat_max", &covered.ymax.to_string(), "")?;
+
+        Ok(dataset)
+    }
+
+    // Writes the values of the region (row-major order) into the given band of the dataset
+    fn write_band_values<T: GdalType + Copy>(
+        &self,
+        dataset: &Dataset,
+        band_index: usize,
+        values: Vec<T>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::write_band_block(
+            dataset,
+            band_index,
+            (0, 0),
+            (self.output_width as usize, self.output_height as usize),
+            values,
+        )
+    }
+
+    // Writes a block of `size` values (row-major order) at `offset` in the given band
+    fn write_band_block<T: GdalType + Copy>(
+        dataset: &Dataset,
+        band_index: usize,
+        offset: (usize, usize),
+        size: (usize, usize),
+        values: Vec<T>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut band = dataset.rasterband(band_index)?;
+
+        let mut buffer = gdal::raster::Buffer::new(size, values);
+
+        band.write((offset.0 as isize, offset.1 as isize), size, &mut buffer)?;
+
+        Ok(())
+    }
+
+    fn create_output_dataset(
+        &self,
+        sample_dataset: &Dataset,
+        pp_values: Vec<f32>,
+        nodata: f64,
+        unit: PpUnit,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let dataset = self.create_pp_dataset(sample_dataset, nodata, unit)?;
+
+        self.write_band_values(&dataset, 1, pp_values)?;
+
+        Ok(dataset)
+    }
+
+    // Creates the PP output raster with its metadata, without writing any value
+    fn create_pp_dataset(
+        &self,
+        sample_dataset: &Dataset,
+        nodata: f64,
+        unit: PpUnit,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let mut dataset =
+            self.create_georeferenced_dataset::<f32>(sample_dataset, "pp_output", 1)?;
+
+        // Set dataset metadata
+        dataset.set_metadata_item("TIFFTAG_DOCUMENTNAME", "Primary Production", "")?;
         dataset.set_metadata_item(
-            "TIFFTAG_SOFTWARE",
-            "Boreas - Oceanographic Processing Tool",
+            "TIFFTAG_IMAGEDESCRIPTION",
+            "Primary production calculated from satellite oceanographic data",
             "",
         )?;
 
@@ -103,33 +524,221 @@ impl SpatialRegion {
             "net_primary_production_of_biomass_expressed_as_carbon_per_unit_area_in_sea_water",
             "",
         )?;
-        band.set_metadata_item("Unit", "mg C m-2 d-1", "")?;
+        band.set_metadata_item("Unit", unit.label(), "")?;
+        band.set_no_data_value(Some(nodata))?;
 
-        let mut buffer = gdal::raster::Buffer::new(
-            (self.output_width as usize, self.output_height as usize),
-            pp_values,
-        );
+        Ok(dataset)
+    }
 
-        band.write(
-            (0, 0),
-            (self.output_width as usize, self.output_height as usize),
-            &mut buffer,
+    fn create_qaa_dataset(
+        &self,
+        sample_dataset: &Dataset,
+        bands: Vec<QaaBand>,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let mut dataset =
+            self.create_georeferenced_dataset::<f32>(sample_dataset, "qaa_output", bands.len())?;
+
+        dataset.set_metadata_item(
+            "TIFFTAG_DOCUMENTNAME",
+            "QAA Inherent Optical Properties",
+            "",
+        )?;
+        dataset.set_metadata_item(
+            "TIFFTAG_IMAGEDESCRIPTION",
+            "Inherent optical properties derived with the Quasi-Analytical Algorithm (QAA v6)",
+            "",
         )?;
 
+        for (index, qaa_band) in bands.into_iter().enumerate() {
+            let mut band = dataset.rasterband(index + 1)?;
+
+            band.set_description(qaa_band.name)?;
+            band.set_metadata_item("long_name", qaa_band.long_name, "")?;
+            band.set_metadata_item("Unit", qaa_band.unit, "")?;
+
+            self.write_band_values(&dataset, index + 1, qaa_band.values)?;
+        }
+
+        Ok(dataset)
+    }
+
+    fn create_qaa_flags_dataset(
+        &self,
+        sample_dataset: &Dataset,
+        flags: Vec<u16>,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let mut dataset =
+            self.create_georeferenced_dataset::<u16>(sample_dataset, "qaa_flags", 1)?;
+
+        dataset.set_metadata_item("TIFFTAG_DOCUMENTNAME", "QAA Quality Flags", "")?;
+
+        let mut band = dataset.rasterband(1)?;
+
+        band.set_description("qaa_flags")?;
+        band.set_metadata_item("long_name", "QAA quality flags (bitfield)", "")?;
+        band.set_no_data_value(Some(QAA_FLAGS_NODATA as f64))?;
+
+        self.write_band_values(&dataset, 1, flags)?;
+
         Ok(dataset)
     }
 }
 
+/// Georeferencing of the array returned by `OceanographicProcessor::calculate_pp_array`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoReference {
+    /// GDAL geotransform of the array (pixel (0, 0) is the top-left corner)
+    pub geotransform: [f64; 6],
+    /// CRS of the geotransform as WKT, `None` when the input datasets have no CRS
+    pub crs_wkt: Option<String>,
+}
+
+/// Scale, offset and no-data value of a dataset overriding its band metadata, for files whose
+/// headers lack them (see `RasterFile::scale`). `None` fields use the metadata.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ValueScaling {
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub nodata: Option<f64>,
+}
+
+/// Output of `OceanographicProcessor::calculate_qaa_for_bbox`
+pub struct QaaOutput {
+    /// Float bands: chla, aph_443, acdom_443 and bbp_555
+    pub iops: Dataset,
+    /// UInt16 band with the `QaaResult` flags of each pixel (65535 where QAA was not run). GeoTIFF
+    /// bands share one data type, hence the separate dataset.
+    pub flags: Option<Dataset>,
+}
+
+/// No-data value of the QAA flags band
+pub const QAA_FLAGS_NODATA: u16 = u16::MAX;
+
+// One band of the QAA output raster
+struct QaaBand {
+    name: &'static str,
+    long_name: &'static str,
+    unit: &'static str,
+    values: Vec<f32>,
+}
+
+impl QaaBand {
+    fn new(name: &'static str, long_name: &'static str, unit: &'static str, len: usize) -> Self {
+        Self {
+            name,
+            long_name,
+            unit,
+            values: Vec::with_capacity(len),
+        }
+    }
+}
+
+/// Rrs datasets (and their nominal wavelengths) used as QAA input
+pub const QAA_RRS_BANDS: [(&str, u32); 5] = [
+    ("rrs_412", 412),
+    ("rrs_443", 443),
+    ("rrs_490", 490),
+    ("rrs_555", 555),
+    ("rrs_670", 670),
+];
+
+/// How `OceanographicProcessor::extract_points_with` samples the grid at a point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointSampling {
+    /// Value of the pixel containing the point
+    #[default]
+    Nearest,
+    /// Bilinear interpolation between the centers of the 2x2 pixels surrounding the point. Falls
+    /// back to `Nearest` on the outer half pixel of the grid and when one of them has no value.
+    Bilinear,
+}
+
+// Fractional (column, row) coordinates of a point on the grid, None if it falls outside
+type PixelCoordinate = Option<(f64, f64)>;
+
+/// Default memory budget (bytes) of the bands read with `OceanographicProcessor::load_band`
+pub const DEFAULT_BAND_CACHE_LIMIT: usize = 512 * 1024 * 1024;
+
+// Band of a dataset read in memory by `OceanographicProcessor::load_band`, with its decoding
+#[derive(Debug)]
+struct CachedBand {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    scale: f64,
+    offset: f64,
+    no_data: Option<f64>,
+}
+
+impl CachedBand {
+    fn size_bytes(&self) -> usize {
+        self.values.len() * std::mem::size_of::<f32>()
+    }
+
+    // Same as `OceanographicProcessor::read_pixel_value`
+    fn value(&self, x: u32, y: u32) -> Option<f32> {
+        let (x, y) = (x as usize, y as usize);
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        decode_pixel_value(
+            self.values[y * self.width + x],
+            self.scale,
+            self.offset,
+            self.no_data,
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct OceanographicProcessor {
     // HashMap containing all the input datasets loaded by GDAL
     datasets: HashMap<String, Dataset>,
     width: u32,
     height: u32,
+    // Name of the dataset used to mask pixels (nonzero = masked), see `with_mask`
+    mask: Option<String>,
+    // Value written for pixels without a valid PP, see `with_output_nodata`
+    output_nodata: f64,
+    // Date of the inputs, used to compute the day length, see `with_date`
+    date: Option<NaiveDate>,
+    // Minimum water depth (m) of the computed pixels when a bathymetry dataset is loaded
+    min_water_depth: f64,
+    // Bits of the l2_flags dataset that exclude a pixel, see `with_l2_flags_mask`
+    l2_flags_mask: u32,
+    // Sensor used to derive the chlorophyll with QAA instead of reading chlor_a, see `with_qaa_chla`
+    qaa_chla: Option<Satellites>,
+    // QAA flags that discard the derived chlorophyll, see `with_qaa_flags_mask`
+    qaa_flags_mask: u16,
+    // Upper bound of valid PP values (mg C m-2 d-1), see `with_max_pp`
+    max_pp: f32,
+    // Unit of the PP outputs, see `with_output_unit`
+    output_unit: PpUnit,
+    // CRS of the bbox given to `calculate_pp_for_bbox`, lon/lat if `None`, see `with_bbox_crs`
+    bbox_crs: Option<String>,
+    // Band read from each dataset when it is not band 1, see `with_bands`
+    bands: HashMap<String, usize>,
+    // Decoding of the datasets whose metadata is missing or wrong, see `with_scaling_overrides`
+    scaling_overrides: HashMap<String, ValueScaling>,
+    // Bands read in memory by `load_band`
+    band_cache: HashMap<String, CachedBand>,
+    // Largest memory (bytes) used by `band_cache`, see `with_band_cache_limit`
+    band_cache_limit: usize,
 }
 
 impl OceanographicProcessor {
     pub fn new(raster_files: &HashMap<String, String>) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::new_with_variables(raster_files, &HashMap::new())
+    }
+
+    /// Same as `new`, where `netcdf_variables` maps raster names to the variable to read from
+    /// NetCDF files (see `RasterFile::netcdf_variable`). Rasters absent from the map use their
+    /// name as variable.
+    pub fn new_with_variables(
+        raster_files: &HashMap<String, String>,
+        netcdf_variables: &HashMap<String, String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let mut datasets = HashMap::new();
         let mut width = 0;
         let mut height = 0;
@@ -142,7 +751,8 @@ impl OceanographicProcessor {
             }
 
             // Automatically detect file format and create appropriate GDAL path
-            let gdal_path = Self::detect_file_format_and_path(path, name);
+            let variable_name = netcdf_variables.get(name).unwrap_or(name);
+            let gdal_path = Self::detect_file_format_and_path(path, variable_name);
 
             match Dataset::open(&gdal_path) {
                 Ok(dataset) => {
@@ -151,54 +761,390 @@ impl OceanographicProcessor {
                         width = w as u32;
                         height = h as u32;
                     }
-                    // Verify all rasters have same dimensions
+                    // Verify all rasters have same dimensions. Reads outside a smaller raster
+                    // are treated as missing values by read_pixel_value.
                     if w as u32 != width || h as u32 != height {
-                        eprintln!("Warning: {} has different dimensions", name);
+                        warn!(
+                            "{} has dimensions {}x{}, expected {}x{}; pixels outside its extent will be treated as missing unless the grids are aligned",
+                            name, w, h, width, height
+                        );
                     }
                     datasets.insert(name.to_string(), dataset);
                 }
-                Err(e) => eprintln!("Could not load {}: {}", name, e),
+                Err(e) => warn!("Could not load {}: {}", name, e),
             }
         }
 
-        Ok(Self {
+        Ok(Self::from_datasets(datasets, width, height))
+    }
+
+    // Processor of loaded datasets sharing a `width` x `height` grid, with the default settings
+    fn from_datasets(datasets: HashMap<String, Dataset>, width: u32, height: u32) -> Self {
+        Self {
             datasets,
             width,
             height,
-        })
+            mask: None,
+            output_nodata: f64::NAN,
+            date: None,
+            min_water_depth: 0.0,
+            l2_flags_mask: DEFAULT_L2_FLAGS_MASK,
+            qaa_chla: None,
+            qaa_flags_mask: QAA_FAILURE_FLAGS,
+            max_pp: DEFAULT_MAX_PP,
+            output_unit: PpUnit::MgCPerDay,
+            bbox_crs: None,
+            bands: HashMap::new(),
+            scaling_overrides: HashMap::new(),
+            band_cache: HashMap::new(),
+            band_cache_limit: DEFAULT_BAND_CACHE_LIMIT,
+        }
     }
 
-    fn detect_file_format_and_path(file_path: &str, variable_name: &str) -> String {
-        if file_path.ends_with(".nc") {
-            // NetCDF format - add NETCDF: prefix and variable suffix
-            format!("NETCDF:{}:{}", file_path, variable_name)
-        } else {
-            // Assume GeoTIFF or other GDAL-supported format
-            file_path.to_string()
-        }
+    /// Sets the value written for masked or invalid pixels in the PP output, and declared as the
+    /// no-data value of the output band. Defaults to `NaN`.
+    pub fn with_output_nodata(mut self, nodata: f64) -> Self {
+        self.output_nodata = nodata;
+        self
     }
 
-    fn read_pixel_value(
-        &self,
-        dataset_name: &str,
-        x: u32,
-        y: u32,
-    ) -> Result<Option<f32>, Box<dyn std::error::Error>> {
-        if let Some(dataset) = self.datasets.get(dataset_name) {
-            let band = dataset.rasterband(1)?;
-            let buffer = band.read_as::<f32>((x as isize, y as isize), (1, 1), (1, 1), None)?;
-            let raw_value = buffer[(0, 0)];
-            let scale = band.scale().unwrap_or(1.0);
-            let missing_value = band.no_data_value();
+    /// Sets the date of the inputs. Without a `day_length` dataset, `debug_pixel` then reports the
+    /// day length computed from this date and the pixel latitude. It is not computed for
+    /// `calculate_pixel_pp`, whose simplified VGPM does not use it.
+    pub fn with_date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
 
-            if missing_value.is_some_and(|mv| raw_value == mv as f32) {
-                Ok(None)
-            } else {
-                Ok(Some(raw_value * scale as f32))
-            }
-        } else {
-            Ok(None)
-        }
+    /// Skips pixels shallower than `depth` meters according to the `"bathymetry"` dataset
+    /// (elevation, negative below sea level). Without this setting, only pixels above sea level
+    /// are skipped. Pixels with a no-data bathymetry are computed.
+    pub fn with_min_water_depth(mut self, depth: f64) -> Self {
+        self.min_water_depth = depth;
+        self
+    }
+
+    /// Sets the bits of the `"l2_flags"` dataset that exclude a pixel (see `iop::flags`).
+    /// Defaults to `DEFAULT_L2_FLAGS_MASK`. The mask is only applied when an `"l2_flags"` dataset
+    /// is loaded.
+    pub fn with_l2_flags_mask(mut self, mask_bits: u32) -> Self {
+        self.l2_flags_mask = mask_bits;
+        self
+    }
+
+    /// Derives the chlorophyll of each pixel from the Rrs datasets with QAA v6 (see
+    /// `calculate_pixel_qaa`) instead of reading the `"chlor_a"` dataset
+    pub fn with_qaa_chla(mut self, satellite: Satellites) -> Self {
+        self.qaa_chla = Some(satellite);
+        self
+    }
+
+    /// Sets the QAA flags that discard the chlorophyll derived with `with_qaa_chla`. Defaults to
+    /// `QAA_FAILURE_FLAGS`.
+    pub fn with_qaa_flags_mask(mut self, mask_bits: u16) -> Self {
+        self.qaa_flags_mask = mask_bits;
+        self
+    }
+
+    /// Sets the largest PP (mg C m^-2 d^-1) considered valid, higher values are written as
+    /// no-data. Defaults to `DEFAULT_MAX_PP` (2000), raise it for eutrophic coastal or upwelling
+    /// waters.
+    pub fn with_max_pp(mut self, max_pp: f32) -> Self {
+        self.max_pp = max_pp;
+        self
+    }
+
+    /// Sets the unit of the PP returned by `calculate_region_pp` and written by
+    /// `calculate_pp_for_bbox`, and declared in the `Unit` metadata of the output band. Defaults to
+    /// mg C m^-2 d^-1. `calculate_pixel_pp` and `with_max_pp` always use mg C m^-2 d^-1.
+    pub fn with_output_unit(mut self, unit: PpUnit) -> Self {
+        self.output_unit = unit;
+        self
+    }
+
+    /// Sets the (1-based) band read from the named datasets, e.g. when several inputs are bands
+    /// of the same multi-band GeoTIFF (see `RasterFile::band`). Other datasets use band 1.
+    ///
+    /// Returns an error if a band is not in its loaded dataset.
+    pub fn with_bands(
+        mut self,
+        bands: HashMap<String, usize>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        for (name, &band) in &bands {
+            if let Some(dataset) = self.datasets.get(name)
+                && !(1..=dataset.raster_count()).contains(&band)
+            {
+                return Err(format!(
+                    "Band {} of '{}' is out of range: the dataset has {} band(s)",
+                    band,
+                    name,
+                    dataset.raster_count()
+                )
+                .into());
+            }
+        }
+
+        self.bands = bands;
+        self.band_cache.clear();
+        Ok(self)
+    }
+
+    /// Decodes the named datasets with these scale, offset and no-data values rather than the
+    /// ones of their band metadata, when set.
+    pub fn with_scaling_overrides(mut self, overrides: HashMap<String, ValueScaling>) -> Self {
+        self.scaling_overrides = overrides;
+        self.band_cache.clear();
+        self
+    }
+
+    /// Sets the memory budget (bytes) of the bands read with `load_band`. Defaults to
+    /// `DEFAULT_BAND_CACHE_LIMIT` (512 MiB).
+    pub fn with_band_cache_limit(mut self, limit: usize) -> Self {
+        self.band_cache_limit = limit;
+        self
+    }
+
+    /// Reads the whole band of the dataset `name` in memory, so that the following pixel reads
+    /// (e.g. when extracting many stations) no longer go through GDAL one pixel at a time. Fails if
+    /// the dataset is not loaded or if the cached bands would exceed the memory budget (see
+    /// `with_band_cache_limit`). Loading a cached band again reads it anew.
+    ///
+    /// The cache is dropped by `unload_band`, `clear_band_cache` and the settings changing how the
+    /// datasets are read (`with_bands`, `with_scaling_overrides`, `with_aligned_grids`). The
+    /// `"l2_flags"` dataset is always read from GDAL, as integers.
+    pub fn load_band(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let dataset = self
+            .datasets
+            .get(name)
+            .ok_or_else(|| format!("Dataset '{}' is not loaded", name))?;
+
+        let (width, height) = dataset.raster_size();
+        let size = width * height * std::mem::size_of::<f32>();
+        let cached: usize = self
+            .band_cache
+            .iter()
+            .filter(|(cached_name, _)| cached_name.as_str() != name)
+            .map(|(_, band)| band.size_bytes())
+            .sum();
+
+        if cached + size > self.band_cache_limit {
+            return Err(format!(
+                "Caching {} ({} bytes) would exceed the band cache limit ({} bytes, {} in use)",
+                name, size, self.band_cache_limit, cached
+            )
+            .into());
+        }
+
+        let cached_band = {
+            let band = dataset.rasterband(self.band_index(name))?;
+            let (scale, offset, no_data) = self.band_scaling(name, &band);
+            let (_, values) = band.read_band_as::<f32>()?.into_shape_and_vec();
+
+            CachedBand {
+                width,
+                height,
+                values,
+                scale,
+                offset,
+                no_data,
+            }
+        };
+
+        self.band_cache.insert(name.to_string(), cached_band);
+
+        Ok(())
+    }
+
+    /// Drops the band of the dataset `name` read by `load_band`, if any. Its pixels are read from
+    /// GDAL again.
+    pub fn unload_band(&mut self, name: &str) {
+        self.band_cache.remove(name);
+    }
+
+    /// Drops every band read by `load_band`
+    pub fn clear_band_cache(&mut self) {
+        self.band_cache.clear();
+    }
+
+    /// Sets the CRS (any definition accepted by GDAL, e.g. `"EPSG:3413"`) of the bboxes given to
+    /// `calculate_pp_for_bbox`, `covered_bbox` and `is_bbox_clipped`, which are lon/lat (EPSG:4326)
+    /// by default. Their edges are reprojected to the CRS of the datasets, projected or not.
+    /// `covered_bbox` and `full_extent_bbox` are always lon/lat.
+    pub fn with_bbox_crs(mut self, crs: &str) -> Self {
+        self.bbox_crs = Some(crs.to_string());
+        self
+    }
+
+    /// Resamples the datasets whose grid (size, geotransform or CRS) differs from the reference
+    /// one, so that a pixel index refers to the same location in every dataset, e.g. a 4 km SST
+    /// NetCDF and 1 km Rrs GeoTIFFs. The reference is the dataset named `reference`, or the one
+    /// with the finest resolution (smallest pixel area, in the units of its CRS) if `None`.
+    ///
+    /// Values are interpolated bilinearly, except in the `"l2_flags"` and mask (`with_mask`, to
+    /// be set before) datasets, which take the value of the nearest pixel. Resampled datasets are
+    /// held in memory as Float64 bands with the no-data value, scale and offset of the original
+    /// ones.
+    pub fn with_aligned_grids(
+        mut self,
+        reference: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let reference = match reference {
+            Some(name) => name.to_string(),
+            None => self.finest_dataset()?,
+        };
+        let grid = DatasetGrid::new(
+            self.datasets
+                .get(&reference)
+                .ok_or_else(|| format!("Reference dataset '{}' is not loaded", reference))?,
+        )?;
+
+        for (name, dataset) in self.datasets.iter_mut() {
+            if DatasetGrid::new(dataset)?.matches(&grid) {
+                continue;
+            }
+
+            info!("Resampling {} onto the grid of {}", name, reference);
+            let nearest = name == L2_FLAGS_DATASET || self.mask.as_deref() == Some(name.as_str());
+            *dataset = grid.resample(dataset, nearest)?;
+        }
+
+        self.width = grid.width as u32;
+        self.height = grid.height as u32;
+        self.band_cache.clear();
+
+        Ok(self)
+    }
+
+    // Name of the dataset with the smallest pixels, the first in alphabetical order on ties
+    fn finest_dataset(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut names: Vec<&String> = self.datasets.keys().collect();
+        names.sort();
+
+        let mut finest: Option<(f64, &String)> = None;
+        for name in names {
+            let area = DatasetGrid::new(&self.datasets[name])?.pixel_area();
+            if finest.is_none_or(|(finest_area, _)| area < finest_area) {
+                finest = Some((area, name));
+            }
+        }
+
+        finest
+            .map(|(_, name)| name.clone())
+            .ok_or_else(|| "No datasets loaded".into())
+    }
+
+    /// Uses the dataset named `mask_name` (e.g. `"l2_flags"` or a boolean `"mask"` band) to skip
+    /// land, cloud or otherwise invalid pixels. A pixel is masked when the mask value is nonzero;
+    /// no-data mask values and pixels outside the mask extent are not masked. Masked pixels are
+    /// not computed and come out as `NaN` in the output rasters.
+    pub fn with_mask(mut self, mask_name: &str) -> Self {
+        self.mask = Some(mask_name.to_string());
+        self
+    }
+
+    // Check the mask dataset (if any) for this pixel
+    fn is_masked(&self, x: u32, y: u32) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(mask_name) = &self.mask else {
+            return Ok(false);
+        };
+
+        if !self.datasets.contains_key(mask_name) {
+            return Err(format!("Mask dataset '{}' is not loaded", mask_name).into());
+        }
+
+        Ok(matches!(self.read_pixel_value(mask_name, x, y)?, Some(value) if value != 0.0))
+    }
+
+    // Whether the l2_flags of the pixel (if loaded) intersect `l2_flags_mask`
+    fn is_flagged(&self, x: u32, y: u32) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(dataset) = self.datasets.get(L2_FLAGS_DATASET) else {
+            return Ok(false);
+        };
+
+        let (width, height) = dataset.raster_size();
+        if x as usize >= width || y as usize >= height {
+            return Ok(false);
+        }
+
+        // Read as integers, bits above 2^24 would be lost in f32
+        let band = dataset.rasterband(self.band_index(L2_FLAGS_DATASET))?;
+        let flags = band.read_as::<i32>((x as isize, y as isize), (1, 1), (1, 1), None)?[(0, 0)];
+
+        if band.no_data_value() == Some(flags as f64) {
+            return Ok(false);
+        }
+
+        Ok(is_masked(flags as u32, self.l2_flags_mask))
+    }
+
+    // Whether the pixel is on land or shallower than `min_water_depth`
+    fn is_too_shallow(&self, x: u32, y: u32) -> Result<bool, Box<dyn std::error::Error>> {
+        if !self.datasets.contains_key(BATHYMETRY_DATASET) {
+            return Ok(false);
+        }
+
+        Ok(matches!(
+            self.read_pixel_value(BATHYMETRY_DATASET, x, y)?,
+            Some(elevation) if elevation as f64 > -self.min_water_depth
+        ))
+    }
+
+    fn detect_file_format_and_path(file_path: &str, variable_name: &str) -> String {
+        if file_path.ends_with(".nc") {
+            // NetCDF format - add NETCDF: prefix and variable suffix
+            format!("NETCDF:{}:{}", file_path, variable_name)
+        } else {
+            // Assume GeoTIFF or other GDAL-supported format
+            file_path.to_string()
+        }
+    }
+
+    // Band of the dataset holding its values, see `with_bands`
+    fn band_index(&self, dataset_name: &str) -> usize {
+        self.bands.get(dataset_name).copied().unwrap_or(1)
+    }
+
+    // Scale, offset and no-data value of the band of the dataset, see `with_scaling_overrides`
+    fn band_scaling(&self, dataset_name: &str, band: &RasterBand) -> (f64, f64, Option<f64>) {
+        let overrides = self
+            .scaling_overrides
+            .get(dataset_name)
+            .copied()
+            .unwrap_or_default();
+
+        (
+            overrides.scale.or(band.scale()).unwrap_or(1.0),
+            overrides.offset.or(band.offset()).unwrap_or(0.0),
+            overrides.nodata.or(band.no_data_value()),
+        )
+    }
+
+    fn read_pixel_value(
+        &self,
+        dataset_name: &str,
+        x: u32,
+        y: u32,
+    ) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        if let Some(cached) = self.band_cache.get(dataset_name) {
+            return Ok(cached.value(x, y));
+        }
+
+        if let Some(dataset) = self.datasets.get(dataset_name) {
+            // Datasets can be smaller than the processor grid, check against their own size
+            let (width, height) = dataset.raster_size();
+            if x as usize >= width || y as usize >= height {
+                return Ok(None);
+            }
+
+            let band = dataset.rasterband(self.band_index(dataset_name))?;
+            let buffer = band.read_as::<f32>((x as isize, y as isize), (1, 1), (1, 1), None)?;
+            let raw_value = buffer[(0, 0)];
+            let (scale, offset, no_data) = self.band_scaling(dataset_name, &band);
+
+            Ok(decode_pixel_value(raw_value, scale, offset, no_data))
+        } else {
+            Ok(None)
+        }
     }
 
     // Simple method to calculate primary production for a single pixel
@@ -207,231 +1153,1622 @@ impl OceanographicProcessor {
         x: u32,
         y: u32,
     ) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        if self.is_masked(x, y)? || self.is_flagged(x, y)? || self.is_too_shallow(x, y)? {
+            return Ok(None);
+        }
+
+        let pixel = self.pixel_data(x, y)?;
+
+        Ok(pixel.calculate_primary_production_bounded(EuphoticDepthModel::Kd, self.max_pp))
+    }
+
+    // Inputs of the production model for a pixel
+    fn pixel_data(&self, x: u32, y: u32) -> Result<PixelData, Box<dyn std::error::Error>> {
         let mut pixel = PixelData::new(x, y);
 
-        // Read data from each dataset for this pixel.
-        pixel.chlor_a = self.read_pixel_value("chlor_a", x, y)?;
-        pixel.sst = self.read_pixel_value("sst", x, y)?;
-        pixel.kd_490 = self.read_pixel_value("kd_490", x, y)?;
+        // Read data from each dataset for this pixel.
+        pixel.chlor_a = match self.qaa_chla {
+            Some(satellite) => self.qaa_chla(x, y, satellite)?,
+            None => self.read_pixel_value("chlor_a", x, y)?,
+        };
+        pixel.sst = self.read_pixel_value("sst", x, y)?;
+        pixel.kd_490 = if self.datasets.contains_key("kd_490") {
+            self.read_pixel_value("kd_490", x, y)?
+        } else {
+            self.estimate_kd_490(x, y)?
+        };
+        // Optional inputs of the fuller production models
+        for (name, field) in [
+            ("par", &mut pixel.par),
+            ("day_length", &mut pixel.day_length),
+        ] {
+            if self.datasets.contains_key(name) {
+                *field = self.read_pixel_value(name, x, y)?;
+            }
+        }
+
+        Ok(pixel)
+    }
+
+    // Day length (hours) of a pixel from the `with_date` date and its latitude, None with a
+    // `day_length` dataset or without a date. Kept out of `pixel_data` since only the complete
+    // VGPM needs it and projected grids need a coordinate transform per pixel.
+    fn date_day_length(&self, x: u32, y: u32) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        match self.date {
+            Some(date) if !self.datasets.contains_key("day_length") => {
+                Ok(Some(day_length_hours(date, self.pixel_latitude(x, y)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Describes how the PP of the pixel containing (`lon`, `lat`) is computed: the raw, scaled
+    /// and decoded value of every input dataset, the masks, the `PixelData` inputs and the VGPM
+    /// terms. Meant to diagnose why a known location has no PP. Fails if the coordinate is outside
+    /// the grid.
+    pub fn debug_pixel(&self, lon: f64, lat: f64) -> Result<String, Box<dyn std::error::Error>> {
+        let (x, y) = self.pixel_index(lon, lat)?;
+        let mut out = String::new();
+
+        writeln!(out, "Coordinate ({}, {}) -> pixel ({}, {})", lon, lat, x, y)?;
+        writeln!(out, "Inputs:")?;
+
+        let mut names: Vec<&String> = self.datasets.keys().collect();
+        names.sort();
+
+        for name in names {
+            let dataset = &self.datasets[name];
+            let (width, height) = dataset.raster_size();
+
+            if x as usize >= width || y as usize >= height {
+                writeln!(out, "  {}: outside the dataset extent", name)?;
+                continue;
+            }
+
+            let band = dataset.rasterband(self.band_index(name))?;
+            let raw = band.read_as::<f64>((x as isize, y as isize), (1, 1), (1, 1), None)?[(0, 0)];
+            let (scale, offset, no_data) = self.band_scaling(name, &band);
+
+            writeln!(
+                out,
+                "  {}: raw {} (scale {}, offset {}, no-data {:?}) -> {:?}",
+                name,
+                raw,
+                scale,
+                offset,
+                no_data,
+                self.read_pixel_value(name, x, y)?
+            )?;
+        }
+
+        writeln!(
+            out,
+            "Masks: mask {}, l2_flags {}, bathymetry {}",
+            self.is_masked(x, y)?,
+            self.is_flagged(x, y)?,
+            self.is_too_shallow(x, y)?
+        )?;
+
+        let mut pixel = self.pixel_data(x, y)?;
+        if let Some(day_length) = self.date_day_length(x, y)? {
+            pixel.day_length = Some(day_length);
+        }
+        write!(out, "{}", pixel)?;
+
+        writeln!(out, "VGPM:")?;
+        writeln!(out, "  Pbopt: {:?}", pixel.sst.map(pbopt_simplified))?;
+        writeln!(
+            out,
+            "  Zeu: {:?}",
+            pixel.euphotic_depth(EuphoticDepthModel::Kd)
+        )?;
+        writeln!(out, "  PP: {:?}", self.calculate_pixel_pp(x, y)?)?;
+
+        Ok(out)
+    }
+
+    /// PP at lon/lat points, e.g. in situ stations, in the unit of `with_output_unit`. Each point
+    /// takes the value of the grid pixel containing it (nearest neighbour). Points outside the
+    /// datasets, masked pixels and pixels without a valid PP give `None`.
+    pub fn extract_points(&self, points: &[(f64, f64)]) -> Vec<Option<f32>> {
+        self.extract_points_with(points, PointSampling::Nearest)
+    }
+
+    /// Same as `extract_points`, choosing how the grid is sampled at the points
+    pub fn extract_points_with(
+        &self,
+        points: &[(f64, f64)],
+        sampling: PointSampling,
+    ) -> Vec<Option<f32>> {
+        self.extract(points, sampling, |x, y| {
+            Ok(self
+                .calculate_pixel_pp(x, y)?
+                .map(|pp| self.output_unit.convert(pp)))
+        })
+    }
+
+    /// Same as `extract_points` for the decoded values of the dataset `name` (e.g. `"chlor_a"`),
+    /// `None` where they are no-data or if the dataset is not loaded. Cache the band with
+    /// `load_band` before extracting many points.
+    pub fn extract_band_points(&self, name: &str, points: &[(f64, f64)]) -> Vec<Option<f32>> {
+        self.extract_band_points_with(name, points, PointSampling::Nearest)
+    }
+
+    /// Same as `extract_band_points`, choosing how the grid is sampled at the points
+    pub fn extract_band_points_with(
+        &self,
+        name: &str,
+        points: &[(f64, f64)],
+        sampling: PointSampling,
+    ) -> Vec<Option<f32>> {
+        self.extract(points, sampling, |x, y| self.read_pixel_value(name, x, y))
+    }
+
+    // Values sampled at the points, None where they cannot be read
+    fn extract(
+        &self,
+        points: &[(f64, f64)],
+        sampling: PointSampling,
+        value: impl Fn(u32, u32) -> Result<Option<f32>, Box<dyn std::error::Error>>,
+    ) -> Vec<Option<f32>> {
+        let coordinates = match self.pixel_coordinates(points) {
+            Ok(coordinates) => coordinates,
+            Err(e) => {
+                warn!("Could not locate the points on the dataset grid: {}", e);
+                return vec![None; points.len()];
+            }
+        };
+
+        let read = |x: u32, y: u32| {
+            value(x, y).unwrap_or_else(|e| {
+                warn!("Could not read pixel ({}, {}): {}", x, y, e);
+                None
+            })
+        };
+
+        coordinates
+            .into_iter()
+            .map(|coordinate| {
+                let (px, py) = coordinate?;
+
+                match sampling {
+                    PointSampling::Bilinear => self.bilinear(px, py, &read),
+                    PointSampling::Nearest => None,
+                }
+                .or_else(|| read(px as u32, py as u32))
+            })
+            .collect()
+    }
+
+    // Bilinear interpolation at the fractional pixel coordinates (px, py) between the centers of
+    // the 2x2 surrounding pixels. None on the outer half pixel of the grid or if one of them has
+    // no value.
+    fn bilinear(&self, px: f64, py: f64, read: &impl Fn(u32, u32) -> Option<f32>) -> Option<f32> {
+        let (fx, fy) = (px - 0.5, py - 0.5);
+        let (x0, y0) = (fx.floor(), fy.floor());
+
+        if x0 < 0.0 || y0 < 0.0 || x0 + 1.0 >= self.width as f64 || y0 + 1.0 >= self.height as f64 {
+            return None;
+        }
+
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as u32, y0 as u32);
+
+        let top = read(x0, y0)? as f64 * (1.0 - tx) + read(x0 + 1, y0)? as f64 * tx;
+        let bottom = read(x0, y0 + 1)? as f64 * (1.0 - tx) + read(x0 + 1, y0 + 1)? as f64 * tx;
+
+        Some((top * (1.0 - ty) + bottom * ty) as f32)
+    }
+
+    // Indices of the grid pixel containing a lon/lat coordinate
+    fn pixel_index(&self, lon: f64, lat: f64) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+        self.pixel_coordinates(&[(lon, lat)])?[0]
+            .map(|(px, py)| (px as u32, py as u32))
+            .ok_or_else(|| {
+                format!(
+                    "Coordinate ({}, {}) is outside the dataset extent",
+                    lon, lat
+                )
+                .into()
+            })
+    }
+
+    // Fractional pixel coordinates of lon/lat coordinates on the grid, None outside the grid or
+    // where the coordinate cannot be projected
+    fn pixel_coordinates(
+        &self,
+        points: &[(f64, f64)],
+    ) -> Result<Vec<PixelCoordinate>, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+
+        let points = match sample_dataset.spatial_ref() {
+            Ok(mut spatial_ref) if !spatial_ref.is_geographic() => {
+                spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+                let wgs84 = wgs84()?;
+
+                // One point out of the projection domain fails the whole batch, retry one by one
+                transform_points(points, &wgs84, &spatial_ref).unwrap_or_else(|_| {
+                    points
+                        .iter()
+                        .map(|&point| {
+                            transform_points(&[point], &wgs84, &spatial_ref)
+                                .map_or((f64::NAN, f64::NAN), |projected| projected[0])
+                        })
+                        .collect()
+                })
+            }
+            _ => points.to_vec(),
+        };
+
+        let inverse = sample_dataset.geo_transform()?.invert()?;
+
+        Ok(points
+            .iter()
+            .map(|&(x, y)| {
+                let (px, py) = inverse.apply(x, y);
+                let inside =
+                    px >= 0.0 && py >= 0.0 && px < self.width as f64 && py < self.height as f64;
+
+                inside.then_some((px, py))
+            })
+            .collect())
+    }
+
+    // Latitude (decimal degrees) of the center of a pixel of the grid
+    fn pixel_latitude(&self, x: u32, y: u32) -> Result<f64, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let center = sample_dataset
+            .geo_transform()?
+            .apply(x as f64 + 0.5, y as f64 + 0.5);
+
+        match sample_dataset.spatial_ref() {
+            Ok(mut spatial_ref) if !spatial_ref.is_geographic() => {
+                spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+                let points = transform_points(&[center], &spatial_ref, &wgs84()?)?;
+                Ok(points[0].1)
+            }
+            _ => Ok(center.1),
+        }
+    }
+
+    /// Runs QAA v6 for a single pixel from the rrs_412, rrs_443, rrs_490, rrs_555 and rrs_670
+    /// datasets. Returns `None` if the pixel is masked or any of these bands is missing.
+    pub fn calculate_pixel_qaa(
+        &self,
+        x: u32,
+        y: u32,
+        satellite: Satellites,
+    ) -> Result<Option<QaaResult>, Box<dyn std::error::Error>> {
+        if self.is_masked(x, y)? {
+            return Ok(None);
+        }
+
+        let mut rrs = BTreeMap::new();
+
+        for (name, wavelength) in QAA_RRS_BANDS {
+            match self.read_pixel_value(name, x, y)? {
+                Some(value) => rrs.insert(wavelength, value as f64),
+                None => return Ok(None),
+            };
+        }
+
+        Ok(Some(qaa_v6(&rrs, satellite)))
+    }
+
+    // QAA chlorophyll of a pixel, `None` when QAA can't run or its flags intersect `qaa_flags_mask`
+    fn qaa_chla(
+        &self,
+        x: u32,
+        y: u32,
+        satellite: Satellites,
+    ) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        let Some(result) = self.calculate_pixel_qaa(x, y, satellite)? else {
+            return Ok(None);
+        };
+
+        if result.flags() & self.qaa_flags_mask != 0 || !result.chla().is_finite() {
+            return Ok(None);
+        }
+
+        Ok(Some(result.chla() as f32))
+    }
+
+    // Derive Kd(490) from the rrs_490 and rrs_555 bands when no kd_490 dataset is provided
+    fn estimate_kd_490(&self, x: u32, y: u32) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        let rrs_490 = self.read_pixel_value("rrs_490", x, y)?;
+        let rrs_555 = self.read_pixel_value("rrs_555", x, y)?;
+
+        let kd = match (rrs_490, rrs_555) {
+            (Some(rrs_490), Some(rrs_555)) => kd490_from_rrs(rrs_490 as f64, rrs_555 as f64),
+            _ => return Ok(None),
+        };
+
+        Ok(kd.is_finite().then_some(kd as f32))
+    }
+
+    /// Calculates PP for a window of the grid. The returned vector always holds `width * height`
+    /// values in row-major order; invalid pixels and pixels outside the grid are set to the output
+    /// no-data value.
+    pub fn calculate_region_pp(
+        &self,
+        x_start: u32,
+        y_start: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let mut results = Vec::with_capacity((width * height) as usize);
+
+        for y in y_start..(y_start + height) {
+            for x in x_start..(x_start + width) {
+                let pp_value = if x >= self.width || y >= self.height {
+                    self.output_nodata as f32
+                } else {
+                    match self.calculate_pixel_pp(x, y)? {
+                        Some(pp) => self.output_unit.convert(pp),
+                        None => self.output_nodata as f32, // No-data value for missing pixels
+                    }
+                };
+                results.push(pp_value);
+            }
+        }
+
+        Ok(results)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_valid_pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    #[allow(dead_code)]
+    pub fn get_dim(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // Pixel window of the bbox on the grid of the loaded datasets. Fails if the bbox does not
+    // overlap them. The bbox is reprojected when the datasets are in a projected CRS or when it
+    // is not lon/lat (`with_bbox_crs`).
+    fn spatial_region(&self, bbox: &Bbox) -> Result<SpatialRegion, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let geotransform = sample_dataset.geo_transform()?;
+
+        let bbox_crs = match &self.bbox_crs {
+            Some(definition) => {
+                let mut srs = SpatialRef::from_definition(definition)?;
+                srs.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+                srs
+            }
+            None => wgs84()?,
+        };
+
+        match sample_dataset.spatial_ref() {
+            Ok(spatial_ref) if !spatial_ref.is_geographic() => SpatialRegion::new_projected(
+                bbox,
+                &geotransform,
+                self.width,
+                self.height,
+                &bbox_crs,
+                &spatial_ref,
+            ),
+            // Geographic datasets are assumed to be lon/lat, as in `SpatialRegion::new`
+            _ if self.bbox_crs.is_some() => SpatialRegion::new_projected(
+                bbox,
+                &geotransform,
+                self.width,
+                self.height,
+                &bbox_crs,
+                &wgs84()?,
+            ),
+            _ => SpatialRegion::new(bbox, &geotransform, self.width, self.height),
+        }
+    }
+
+    // Same as `spatial_region`, warning when the bbox is only partly covered by the datasets
+    fn output_region(&self, bbox: &Bbox) -> Result<SpatialRegion, Box<dyn std::error::Error>> {
+        let spatial_region = self.spatial_region(bbox)?;
+
+        if spatial_region.clipped {
+            let covered = spatial_region.covered_bbox()?;
+            warn!(
+                "bbox ({}, {}, {}, {}) extends beyond the dataset extent; output is clipped to ({}, {}, {}, {})",
+                bbox.xmin,
+                bbox.xmax,
+                bbox.ymin,
+                bbox.ymax,
+                covered.xmin,
+                covered.xmax,
+                covered.ymin,
+                covered.ymax
+            );
+        }
+
+        Ok(spatial_region)
+    }
+
+    /// Lon/lat extent of the loaded datasets, to process the whole scene with
+    /// `calculate_pp_for_bbox`. For projected datasets, this is the bbox enclosing their
+    /// reprojected edges.
+    pub fn full_extent_bbox(&self) -> Result<Bbox, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let geotransform = sample_dataset.geo_transform()?;
+
+        match sample_dataset.spatial_ref() {
+            Ok(spatial_ref) if !spatial_ref.is_geographic() => {
+                let mut source = spatial_ref.clone();
+                source.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+
+                let region = SpatialRegion {
+                    start_x: 0,
+                    start_y: 0,
+                    output_width: self.width,
+                    output_height: self.height,
+                    geotransform,
+                    clipped: false,
+                    spatial_ref: Some(source),
+                };
+
+                region.covered_bbox()
+            }
+            _ => Ok(Bbox::from_geotransform(
+                &geotransform,
+                self.width,
+                self.height,
+            )?),
+        }
+    }
+
+    /// Returns the extent of the output of `calculate_pp_for_bbox` for this bbox, i.e. the part of
+    /// the bbox covered by the input datasets, snapped to their grid.
+    pub fn covered_bbox(&self, bbox: &Bbox) -> Result<Bbox, Box<dyn std::error::Error>> {
+        self.spatial_region(bbox)?.covered_bbox()
+    }
+
+    /// Whether the bbox extends beyond the input datasets, in which case the outputs only cover
+    /// `covered_bbox`. Fails if the bbox does not overlap the datasets at all.
+    pub fn is_bbox_clipped(&self, bbox: &Bbox) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.spatial_region(bbox)?.clipped)
+    }
+
+    // Calculate PP for a geographic bounding box
+    pub fn calculate_pp_for_bbox(
+        &self,
+        bbox: &Bbox,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let spatial_region = self.output_region(bbox)?;
+
+        let (pp_values, _) = self
+            .region_pp_array(&spatial_region)?
+            .into_raw_vec_and_offset();
+
+        spatial_region.create_output_dataset(
+            sample_dataset,
+            pp_values,
+            self.output_nodata,
+            self.output_unit,
+        )
+    }
+
+    /// Same as `calculate_pp_for_bbox`, returning the PP values as a (rows, columns) array with
+    /// its georeferencing instead of a GDAL dataset.
+    pub fn calculate_pp_array(
+        &self,
+        bbox: &Bbox,
+    ) -> Result<(Array2<f32>, GeoReference), Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let spatial_region = self.output_region(bbox)?;
+
+        let georeference = GeoReference {
+            geotransform: spatial_region.output_geotransform(),
+            crs_wkt: sample_dataset
+                .spatial_ref()
+                .ok()
+                .and_then(|spatial_ref| spatial_ref.to_wkt().ok()),
+        };
+
+        Ok((self.region_pp_array(&spatial_region)?, georeference))
+    }
+
+    // PP of every pixel of the region, as a (rows, columns) array
+    fn region_pp_array(
+        &self,
+        spatial_region: &SpatialRegion,
+    ) -> Result<Array2<f32>, Box<dyn std::error::Error>> {
+        // Based on bbox, we calculated the starting pixel position and the width, height of the
+        // window where to calculate pp
+        let pp_values = self.calculate_region_pp(
+            spatial_region.start_x,
+            spatial_region.start_y,
+            spatial_region.output_width,
+            spatial_region.output_height,
+        )?;
+
+        let shape = (
+            spatial_region.output_height as usize,
+            spatial_region.output_width as usize,
+        );
+
+        Ok(Array2::from_shape_vec(shape, pp_values)?)
+    }
+
+    /// Same as `calculate_pp_for_bbox`, processing the bbox in blocks of at most
+    /// `tile_size` x `tile_size` pixels written to the output as they are computed. Peak memory is
+    /// bounded by the block size instead of the bbox size, for large high resolution scenes.
+    pub fn calculate_pp_for_bbox_tiled(
+        &self,
+        bbox: &Bbox,
+        tile_size: u32,
+    ) -> Result<Dataset, Box<dyn std::error::Error>> {
+        if tile_size == 0 {
+            return Err("Tile size must be greater than 0".into());
+        }
+
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let spatial_region = self.output_region(bbox)?;
+
+        let dataset = spatial_region.create_pp_dataset(
+            sample_dataset,
+            self.output_nodata,
+            self.output_unit,
+        )?;
+
+        for tile_y in (0..spatial_region.output_height).step_by(tile_size as usize) {
+            for tile_x in (0..spatial_region.output_width).step_by(tile_size as usize) {
+                let tile_width = tile_size.min(spatial_region.output_width - tile_x);
+                let tile_height = tile_size.min(spatial_region.output_height - tile_y);
+
+                let pp_values = self.calculate_region_pp(
+                    spatial_region.start_x + tile_x,
+                    spatial_region.start_y + tile_y,
+                    tile_width,
+                    tile_height,
+                )?;
+
+                SpatialRegion::write_band_block(
+                    &dataset,
+                    1,
+                    (tile_x as usize, tile_y as usize),
+                    (tile_width as usize, tile_height as usize),
+                    pp_values,
+                )?;
+            }
+        }
+
+        Ok(dataset)
+    }
+
+    /// Runs QAA v6 on every pixel of a geographic bounding box and returns a 4-band raster with
+    /// chla, aph(443), acdom(443) and bbp(555). Pixels with missing Rrs bands are set to NaN.
+    /// When `include_flags` is set, the per-pixel quality flags are returned as well.
+    pub fn calculate_qaa_for_bbox(
+        &self,
+        bbox: &Bbox,
+        satellite: Satellites,
+        include_flags: bool,
+    ) -> Result<QaaOutput, Box<dyn std::error::Error>> {
+        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
+        let spatial_region = self.output_region(bbox)?;
+        let pixel_count = (spatial_region.output_width * spatial_region.output_height) as usize;
+
+        let mut chla = QaaBand::new("chla", "Chlorophyll-a concentration", "mg m-3", pixel_count);
+        let mut aph_443 = QaaBand::new(
+            "aph_443",
+            "Phytoplankton absorption at 443 nm",
+            "m-1",
+            pixel_count,
+        );
+        let mut acdom_443 = QaaBand::new(
+            "acdom_443",
+            "CDOM and detrital absorption at 443 nm",
+            "m-1",
+            pixel_count,
+        );
+        let mut bbp_555 = QaaBand::new(
+            "bbp_555",
+            "Particulate backscattering at 555 nm",
+            "m-1",
+            pixel_count,
+        );
+
+        let mut flags = Vec::with_capacity(if include_flags { pixel_count } else { 0 });
+
+        let x_end = spatial_region.start_x + spatial_region.output_width;
+        let y_end = spatial_region.start_y + spatial_region.output_height;
+
+        for y in spatial_region.start_y..y_end {
+            for x in spatial_region.start_x..x_end {
+                let result = self.calculate_pixel_qaa(x, y, satellite)?;
+
+                if include_flags {
+                    flags.push(result.as_ref().map_or(QAA_FLAGS_NODATA, |r| r.flags()));
+                }
+
+                let values = match result {
+                    Some(result) => [
+                        result.chla(),
+                        result.aph_at(443),
+                        result.acdom_at(443),
+                        result.bbp_at(555),
+                    ],
+                    None => [f64::NAN; 4],
+                };
+
+                chla.values.push(values[0] as f32);
+                aph_443.values.push(values[1] as f32);
+                acdom_443.values.push(values[2] as f32);
+                bbp_555.values.push(values[3] as f32);
+            }
+        }
+
+        let iops = spatial_region
+            .create_qaa_dataset(sample_dataset, vec![chla, aph_443, acdom_443, bbp_555])?;
+
+        let flags = if include_flags {
+            Some(spatial_region.create_qaa_flags_dataset(sample_dataset, flags)?)
+        } else {
+            None
+        };
+
+        Ok(QaaOutput { iops, flags })
+    }
+}
+
+impl Display for OceanographicProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OceanographicProcessor {{ datasets: {}, dimensions: {}x{} }}",
+            self.datasets.len(),
+            self.width,
+            self.height
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use tempfile::tempdir;
+
+    // Creates a single band in-memory dataset filled with `values` (row-major order)
+    fn create_mem_dataset(width: usize, height: usize, values: Vec<f32>) -> Dataset {
+        let driver = gdal::DriverManager::get_driver_by_name("MEM").unwrap();
+        let dataset = driver
+            .create_with_band_type::<f32, _>("", width, height, 1)
+            .unwrap();
+
+        let mut band = dataset.rasterband(1).unwrap();
+        let mut buffer = gdal::raster::Buffer::new((width, height), values);
+        band.write((0, 0), (width, height), &mut buffer).unwrap();
+
+        dataset
+    }
+
+    #[test]
+    fn test_read_pixel_value_applies_scale_and_offset() {
+        let dataset = create_mem_dataset(2, 1, vec![100.0, -32767.0]);
+        {
+            let mut band = dataset.rasterband(1).unwrap();
+            band.set_scale(0.005).unwrap();
+            band.set_offset(20.0).unwrap();
+            band.set_no_data_value(Some(-32767.0)).unwrap();
+        }
+
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([("sst".to_string(), dataset)]),
+            2,
+            1,
+        );
+
+        // real = raw * scale + offset
+        let value = processor.read_pixel_value("sst", 0, 0).unwrap().unwrap();
+        assert!((value - 20.5).abs() < 1e-6);
+
+        assert_eq!(processor.read_pixel_value("sst", 1, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_pixel_value_scaling_overrides() {
+        // Scaled integers without scale, offset or no-data metadata
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([(
+                "sst".to_string(),
+                create_mem_dataset(2, 1, vec![100.0, -32767.0]),
+            )]),
+            2,
+            1,
+        );
+
+        assert_eq!(
+            processor.read_pixel_value("sst", 0, 0).unwrap(),
+            Some(100.0)
+        );
+        assert_eq!(
+            processor.read_pixel_value("sst", 1, 0).unwrap(),
+            Some(-32767.0)
+        );
+
+        let processor = processor.with_scaling_overrides(HashMap::from([(
+            "sst".to_string(),
+            ValueScaling {
+                scale: Some(0.005),
+                offset: Some(20.0),
+                nodata: Some(-32767.0),
+            },
+        )]));
+
+        let value = processor.read_pixel_value("sst", 0, 0).unwrap().unwrap();
+        assert!((value - 20.5).abs() < 1e-6);
+        assert_eq!(processor.read_pixel_value("sst", 1, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_pixel_value_band() {
+        // Rrs bands 1 to 3 of a multi-band GeoTIFF
+        let rrs = || {
+            let driver = gdal::DriverManager::get_driver_by_name("MEM").unwrap();
+            let dataset = driver.create_with_band_type::<f32, _>("", 1, 1, 3).unwrap();
+            for index in 1..=3 {
+                let mut buffer = gdal::raster::Buffer::new((1, 1), vec![index as f32 * 0.001]);
+                let mut band = dataset.rasterband(index).unwrap();
+                band.write((0, 0), (1, 1), &mut buffer).unwrap();
+            }
+            dataset
+        };
+
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([
+                ("rrs_412".to_string(), rrs()),
+                ("rrs_443".to_string(), rrs()),
+            ]),
+            1,
+            1,
+        )
+        .with_bands(HashMap::from([("rrs_443".to_string(), 3)]))
+        .unwrap();
+
+        assert_eq!(
+            processor.read_pixel_value("rrs_412", 0, 0).unwrap(),
+            Some(0.001)
+        );
+        assert_eq!(
+            processor.read_pixel_value("rrs_443", 0, 0).unwrap(),
+            Some(0.003)
+        );
+
+        // Missing bands
+        let error = processor
+            .with_bands(HashMap::from([("rrs_443".to_string(), 4)]))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Band 4 of 'rrs_443'"));
+    }
+
+    #[test]
+    fn test_read_pixel_value_outside_smaller_dataset() {
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([
+                (
+                    "chlor_a".to_string(),
+                    create_mem_dataset(2, 2, vec![1.0, 2.0, 3.0, 4.0]),
+                ),
+                ("sst".to_string(), create_mem_dataset(1, 1, vec![5.0])),
+            ]),
+            2,
+            2,
+        );
+
+        assert_eq!(
+            processor.read_pixel_value("chlor_a", 1, 1).unwrap(),
+            Some(4.0)
+        );
+        assert_eq!(processor.read_pixel_value("sst", 0, 0).unwrap(), Some(5.0));
+        assert_eq!(processor.read_pixel_value("sst", 1, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_load_band() {
+        let mut processor = create_georeferenced_pp_processor(3, 2);
+        {
+            let mut band = processor.datasets["sst"].rasterband(1).unwrap();
+            band.set_no_data_value(Some(-1.0)).unwrap();
+            band.set_scale(2.0).unwrap();
+        }
+
+        processor.load_band("sst").unwrap();
+        assert_eq!(processor.band_cache["sst"].size_bytes(), 24);
+
+        // The cache is read instead of the dataset
+        let mut buffer = gdal::raster::Buffer::new((1, 1), vec![-1.0_f32]);
+        processor.datasets["sst"]
+            .rasterband(1)
+            .unwrap()
+            .write((2, 1), (1, 1), &mut buffer)
+            .unwrap();
+        assert_eq!(processor.read_pixel_value("sst", 2, 1).unwrap(), Some(10.0));
+        assert_eq!(processor.read_pixel_value("sst", 3, 1).unwrap(), None);
+
+        processor.load_band("sst").unwrap();
+        assert_eq!(processor.read_pixel_value("sst", 2, 1).unwrap(), None);
+
+        processor.unload_band("sst");
+        assert!(processor.band_cache.is_empty());
+
+        // Settings changing how the bands are read drop the cache
+        processor.load_band("chlor_a").unwrap();
+        let processor = processor.with_scaling_overrides(HashMap::new());
+        assert!(processor.band_cache.is_empty());
+
+        // Memory budget, two 3x2 f32 bands
+        let mut processor = processor.with_band_cache_limit(48);
+        processor.load_band("chlor_a").unwrap();
+        processor.load_band("sst").unwrap();
+        processor.load_band("sst").unwrap();
+        assert!(processor.load_band("kd_490").is_err());
+
+        processor.clear_band_cache();
+        processor.load_band("kd_490").unwrap();
+
+        assert!(processor.load_band("par").is_err());
+    }
+
+    #[test]
+    fn test_with_aligned_grids() {
+        // 1 degree chlor_a and kd_490, 2 degree sst and l2_flags over the same 4x4 degree extent
+        let mut processor = create_georeferenced_pp_processor(4, 4);
+
+        let mut sst = create_mem_dataset(2, 2, vec![8.0; 4]);
+        sst.set_geo_transform(&[0.0, 2.0, 0.0, 4.0, 0.0, -2.0])
+            .unwrap();
+        processor.datasets.insert("sst".to_string(), sst);
+
+        let driver = gdal::DriverManager::get_driver_by_name("MEM").unwrap();
+        let mut l2_flags = driver.create_with_band_type::<i32, _>("", 2, 2, 1).unwrap();
+        l2_flags
+            .set_geo_transform(&[0.0, 2.0, 0.0, 4.0, 0.0, -2.0])
+            .unwrap();
+        let mut buffer = gdal::raster::Buffer::new((2, 2), vec![1, 2, 4, 8]);
+        l2_flags
+            .rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buffer)
+            .unwrap();
+        processor
+            .datasets
+            .insert(L2_FLAGS_DATASET.to_string(), l2_flags);
+
+        // Finest grid
+        let aligned = processor.with_aligned_grids(None).unwrap();
+        assert_eq!(aligned.get_dim(), (4, 4));
+        for dataset in aligned.datasets.values() {
+            assert_eq!(dataset.raster_size(), (4, 4));
+        }
+        assert_eq!(aligned.read_pixel_value("sst", 0, 0).unwrap(), Some(8.0));
+        assert_eq!(aligned.read_pixel_value("sst", 3, 3).unwrap(), Some(8.0));
 
-        Ok(pixel.calculate_primary_production())
+        // Flags are not interpolated
+        assert_eq!(
+            aligned.read_pixel_value(L2_FLAGS_DATASET, 0, 0).unwrap(),
+            Some(1.0)
+        );
+        assert_eq!(
+            aligned.read_pixel_value(L2_FLAGS_DATASET, 2, 1).unwrap(),
+            Some(2.0)
+        );
+        assert_eq!(
+            aligned.read_pixel_value(L2_FLAGS_DATASET, 3, 3).unwrap(),
+            Some(8.0)
+        );
+
+        // Configured reference
+        let aligned = aligned.with_aligned_grids(Some("sst")).unwrap();
+        assert_eq!(aligned.get_dim(), (2, 2));
+        assert_eq!(aligned.datasets["chlor_a"].raster_size(), (2, 2));
+
+        assert!(aligned.with_aligned_grids(Some("rrs_443")).is_err());
     }
 
-    pub fn calculate_region_pp(
-        &self,
-        x_start: u32,
-        y_start: u32,
-        width: u32,
-        height: u32,
-    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-        let mut results = Vec::with_capacity((width * height) as usize);
+    #[test]
+    fn test_calculate_pixel_qaa_requires_all_rrs_bands() {
+        let rrs = [0.0020, 0.0023, 0.0030, 0.0015, 0.0002];
+        let mut datasets: HashMap<String, Dataset> = QAA_RRS_BANDS
+            .iter()
+            .zip(rrs)
+            .map(|((name, _), value)| (name.to_string(), create_mem_dataset(1, 1, vec![value])))
+            .collect();
 
-        for y in y_start..(y_start + height).min(self.height) {
-            for x in x_start..(x_start + width).min(self.width) {
-                let pp_value = match self.calculate_pixel_pp(x, y)? {
-                    Some(pp) => pp,
-                    None => f32::NAN, // Use NaN for missing/no-data pixels
-                };
-                results.push(pp_value);
-            }
-        }
+        let processor = OceanographicProcessor::from_datasets(datasets, 1, 1);
 
-        Ok(results)
+        let result = processor
+            .calculate_pixel_qaa(0, 0, Satellites::Modis)
+            .unwrap()
+            .unwrap();
+        assert!(result.aph_at(443).is_finite());
+        assert!(result.bbp_at(555).is_finite());
+
+        datasets = processor.datasets;
+        datasets.remove("rrs_670");
+        let processor = OceanographicProcessor::from_datasets(datasets, 1, 1);
+
+        assert!(
+            processor
+                .calculate_pixel_qaa(0, 0, Satellites::Modis)
+                .unwrap()
+                .is_none()
+        );
     }
 
-    #[allow(dead_code)]
-    pub fn get_valid_pixel_count(&self) -> usize {
-        self.width as usize * self.height as usize
+    #[test]
+    fn test_mask_skips_nonzero_pixels() {
+        let mask = create_mem_dataset(3, 1, vec![0.0, 1.0, -9999.0]);
+        mask.rasterband(1)
+            .unwrap()
+            .set_no_data_value(Some(-9999.0))
+            .unwrap();
+
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([
+                (
+                    "chlor_a".to_string(),
+                    create_mem_dataset(3, 1, vec![1.0; 3]),
+                ),
+                ("sst".to_string(), create_mem_dataset(3, 1, vec![5.0; 3])),
+                ("kd_490".to_string(), create_mem_dataset(3, 1, vec![0.1; 3])),
+                ("mask".to_string(), mask),
+            ]),
+            3,
+            1,
+        );
+
+        let unmasked = processor.calculate_region_pp(0, 0, 3, 1).unwrap();
+        let masked = processor
+            .with_mask("mask")
+            .calculate_region_pp(0, 0, 3, 1)
+            .unwrap();
+
+        assert_eq!(masked[0], unmasked[0]);
+        assert!(masked[1].is_nan());
+        assert_eq!(masked[2], unmasked[2]);
     }
 
-    #[allow(dead_code)]
-    pub fn get_dim(&self) -> (u32, u32) {
-        (self.width, self.height)
+    #[test]
+    fn test_qaa_chla_replaces_chlor_a() {
+        let rrs = [0.0020, 0.0023, 0.0030, 0.0015, 0.0002];
+        let mut datasets: HashMap<String, Dataset> = QAA_RRS_BANDS
+            .iter()
+            .zip(rrs)
+            .map(|((name, _), value)| (name.to_string(), create_mem_dataset(1, 1, vec![value])))
+            .collect();
+        datasets.insert("sst".to_string(), create_mem_dataset(1, 1, vec![5.0]));
+        datasets.insert("kd_490".to_string(), create_mem_dataset(1, 1, vec![0.1]));
+
+        let processor = OceanographicProcessor::from_datasets(datasets, 1, 1);
+
+        // No chlor_a dataset
+        assert_eq!(processor.calculate_pixel_pp(0, 0).unwrap(), None);
+
+        let processor = processor.with_qaa_chla(Satellites::SeaWiFS);
+        let qaa = processor
+            .calculate_pixel_qaa(0, 0, Satellites::SeaWiFS)
+            .unwrap()
+            .unwrap();
+
+        let mut pixel = PixelData::new(0, 0);
+        pixel.chlor_a = Some(qaa.chla() as f32);
+        pixel.sst = Some(5.0);
+        pixel.kd_490 = Some(0.1);
+
+        let pp = processor.calculate_pixel_pp(0, 0).unwrap();
+        assert!(pp.is_some());
+        assert_eq!(pp, pixel.calculate_primary_production());
     }
 
-    // Calculate PP for a geographic bounding box
-    pub fn calculate_pp_for_bbox(
-        &self,
-        bbox: &Bbox,
-    ) -> Result<Dataset, Box<dyn std::error::Error>> {
-        let sample_dataset = self.datasets.values().next().ok_or("No datasets loaded")?;
-        let geotransform = sample_dataset.geo_transform()?;
+    #[test]
+    fn test_output_nodata_for_invalid_pixels() {
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([
+                (
+                    "chlor_a".to_string(),
+                    create_mem_dataset(2, 1, vec![1.0, -1.0]),
+                ),
+                ("sst".to_string(), create_mem_dataset(2, 1, vec![5.0; 2])),
+                ("kd_490".to_string(), create_mem_dataset(2, 1, vec![0.1; 2])),
+            ]),
+            2,
+            1,
+        )
+        .with_output_nodata(-9999.0);
 
-        let spatial_region = SpatialRegion::new(bbox, &geotransform, self.width, self.height)?;
+        let pp = processor.calculate_region_pp(0, 0, 2, 1).unwrap();
 
-        // Based on bbox, we calculated the starting pixel position and the width, height of the
-        // window where to calculate pp
-        let pp_values = self.calculate_region_pp(
-            spatial_region.start_x,
-            spatial_region.start_y,
-            spatial_region.output_width,
-            spatial_region.output_height,
-        )?;
+        assert!(pp[0] > 0.0);
+        assert_eq!(pp[1], -9999.0);
+    }
 
-        spatial_region.create_output_dataset(sample_dataset, pp_values)
+    #[test]
+    fn test_region_pp_is_full_length() {
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([
+                (
+                    "chlor_a".to_string(),
+                    create_mem_dataset(2, 2, vec![1.0; 4]),
+                ),
+                ("sst".to_string(), create_mem_dataset(2, 2, vec![5.0; 4])),
+                ("kd_490".to_string(), create_mem_dataset(2, 2, vec![0.1; 4])),
+            ]),
+            2,
+            2,
+        );
+
+        // Window extends one pixel past the grid on each axis
+        let pp = processor.calculate_region_pp(1, 1, 2, 2).unwrap();
+
+        assert_eq!(pp.len(), 4);
+        assert!(pp[0] > 0.0);
+        assert!(pp[1..].iter().all(|v| v.is_nan()));
     }
-}
 
-impl Display for OceanographicProcessor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "OceanographicProcessor {{ datasets: {}, dimensions: {}x{} }}",
-            self.datasets.len(),
-            self.width,
-            self.height
+    #[test]
+    fn test_missing_mask_dataset_is_an_error() {
+        let processor = OceanographicProcessor::from_datasets(
+            HashMap::from([("chlor_a".to_string(), create_mem_dataset(1, 1, vec![1.0]))]),
+            1,
+            1,
         )
+        .with_mask("l2_flags");
+
+        assert!(processor.calculate_pixel_pp(0, 0).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_covered_bbox_is_clamped_to_dataset() {
+        // 1 degree global grid
+        let geotransform = [-180.0, 1.0, 0.0, 90.0, 0.0, -1.0];
 
-    fn create_mock_data() -> HashMap<String, String> {
-        let mut mock_data = HashMap::new();
-        mock_data.insert(
-            "rrs_443".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.RRS.Rrs_443.4km.cog.tif"
-                .to_string(),
-        );
-        mock_data.insert(
-            "rrs_490".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.RRS.Rrs_488.4km.cog.tif"
-                .to_string(),
-        );
-        mock_data.insert(
-            "rrs_555".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.RRS.Rrs_555.4km.cog.tif"
-                .to_string(),
-        );
-        mock_data.insert(
-            "kd_490".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.KD.Kd_490.4km.cog.tif"
-                .to_string(),
-        );
-        mock_data.insert(
-            "sst".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.SST.sst.4km.nc"
-                .to_string(),
-        );
-        mock_data.insert(
-            "chlor_a".to_string(),
-            "./data/geotiff/modis_aqua/AQUA_MODIS.20250701_20250731.L3m.MO.CHL.chlor_a.4km.cog.tif"
-                .to_string(),
+        let inside = Bbox::new(-10.5, 10.5, 20.2, 30.8).unwrap();
+        let region = SpatialRegion::new(&inside, &geotransform, 360, 180).unwrap();
+        let covered = region.covered_bbox().unwrap();
+        assert_eq!(
+            (covered.xmin, covered.xmax, covered.ymin, covered.ymax),
+            (-11.0, 11.0, 20.0, 31.0)
         );
-        mock_data
+
+        // Dataset only covers the western hemisphere
+        let region = SpatialRegion::new(&inside, &geotransform, 180, 180).unwrap();
+        let covered = region.covered_bbox().unwrap();
+        assert_eq!((covered.xmin, covered.xmax), (-11.0, 0.0));
     }
 
     #[test]
-    fn test_region_pp_vs_bbox_pp_equivalence() {
-        let rasters = create_mock_data();
-        let processor = match OceanographicProcessor::new(&rasters) {
-            Ok(p) => p,
-            Err(_) => {
-                // Skip test if datasets can't be loaded (e.g., in CI environments)
-                return;
-            }
+    fn test_spatial_region_clipping() {
+        // Dataset covering 0-10E, 0-10N with 1 degree pixels
+        let geotransform = [0.0, 1.0, 0.0, 10.0, 0.0, -1.0];
+
+        let inside = Bbox::new(2.0, 5.0, 2.0, 5.0).unwrap();
+        let region = SpatialRegion::new(&inside, &geotransform, 10, 10).unwrap();
+        assert!(!region.clipped);
+        assert_eq!((region.output_width, region.output_height), (3, 3));
+
+        let partly_outside = Bbox::new(-5.0, 5.0, 2.0, 5.0).unwrap();
+        let region = SpatialRegion::new(&partly_outside, &geotransform, 10, 10).unwrap();
+        assert!(region.clipped);
+        assert_eq!((region.start_x, region.output_width), (0, 5));
+
+        let outside = Bbox::new(20.0, 30.0, 2.0, 5.0).unwrap();
+        assert!(SpatialRegion::new(&outside, &geotransform, 10, 10).is_err());
+
+        let outside = Bbox::new(-30.0, -20.0, -50.0, -40.0).unwrap();
+        assert!(SpatialRegion::new(&outside, &geotransform, 10, 10).is_err());
+    }
+
+    #[test]
+    fn test_spatial_region_rotated_geotransform() {
+        // Nonzero rotation terms: pixel (3, 4) has its top-left corner at (111.0, 46.3)
+        let geotransform = [100.0, 1.0, 2.0, 50.0, 0.1, -1.0];
+
+        let bbox = Bbox::new(111.0, 111.01, 46.29, 46.3).unwrap();
+        let region = SpatialRegion::new(&bbox, &geotransform, 20, 20).unwrap();
+
+        assert!((region.start_x..region.start_x + region.output_width).contains(&3));
+        assert!((region.start_y..region.start_y + region.output_height).contains(&4));
+
+        // Ignoring the rotation would have selected column 11
+        assert!(region.start_x + region.output_width <= 5);
+
+        let covered = region.covered_bbox().unwrap();
+        assert!(covered.xmin <= 111.0 && covered.xmax >= 111.01);
+        assert!(covered.ymin <= 46.29 && covered.ymax >= 46.3);
+
+        // Non-invertible geotransform
+        let singular = [100.0, 1.0, 1.0, 50.0, 1.0, 1.0];
+        assert!(SpatialRegion::new(&bbox, &singular, 20, 20).is_err());
+    }
+
+    #[test]
+    fn test_spatial_region_polar_stereographic() {
+        // NSIDC sea ice polar stereographic north, 25 km pixels centered on the pole
+        let spatial_ref = SpatialRef::from_epsg(3413).unwrap();
+        let geotransform = [-2_000_000.0, 25_000.0, 0.0, 2_000_000.0, 0.0, -25_000.0];
+
+        // Around 80N on the -45 meridian, i.e. about 1080 km below the pole on the x = 0 line
+        let bbox = Bbox::new(-46.0, -44.0, 79.9, 80.1).unwrap();
+        let region = SpatialRegion::new_projected(
+            &bbox,
+            &geotransform,
+            160,
+            160,
+            &wgs84().unwrap(),
+            &spatial_ref,
+        )
+        .unwrap();
+
+        assert!((region.start_x..region.start_x + region.output_width).contains(&80));
+        assert!((region.start_y..region.start_y + region.output_height).contains(&123));
+        assert!(!region.clipped);
+
+        // The covered extent is reported in lon/lat and encloses the bbox
+        let covered = region.covered_bbox().unwrap();
+        assert!(covered.xmin <= -46.0 && covered.xmax >= -44.0);
+        assert!(covered.ymin <= 79.9 && covered.ymax >= 80.1);
+        assert!(covered.ymin > 79.0 && covered.ymax < 81.0);
+    }
+
+    #[test]
+    fn test_spatial_region_projected_bbox() {
+        let mut spatial_ref = SpatialRef::from_epsg(3413).unwrap();
+        spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+        let geotransform = [-2_000_000.0, 25_000.0, 0.0, 2_000_000.0, 0.0, -25_000.0];
+
+        // Bbox in metres in the dataset CRS, i.e. pixels 80.4..81.6 and 124.4..125.6
+        let bbox = Bbox::new_projected(10_000.0, 40_000.0, -1_140_000.0, -1_110_000.0).unwrap();
+        let region = SpatialRegion::new_projected(
+            &bbox,
+            &geotransform,
+            160,
+            160,
+            &spatial_ref,
+            &spatial_ref,
+        )
+        .unwrap();
+
+        assert_eq!((region.start_x, region.output_width), (80, 2));
+        assert_eq!((region.start_y, region.output_height), (124, 2));
+
+        // Still reported in lon/lat
+        let covered = region.covered_bbox().unwrap();
+        assert!(covered.ymin > 79.0 && covered.ymax < 81.0);
+    }
+
+    // PP inputs on a width x height grid of 1 degree pixels with its top-left corner at (0, height)
+    fn create_georeferenced_pp_processor(width: usize, height: usize) -> OceanographicProcessor {
+        let chlor_a: Vec<f32> = (1..=width * height).map(|v| v as f32 * 0.1).collect();
+
+        let mut datasets = HashMap::from([
+            (
+                "chlor_a".to_string(),
+                create_mem_dataset(width, height, chlor_a),
+            ),
+            (
+                "sst".to_string(),
+                create_mem_dataset(width, height, vec![5.0; width * height]),
+            ),
+            (
+                "kd_490".to_string(),
+                create_mem_dataset(width, height, vec![0.1; width * height]),
+            ),
+        ]);
+        for dataset in datasets.values_mut() {
+            dataset
+                .set_geo_transform(&[0.0, 1.0, 0.0, height as f64, 0.0, -1.0])
+                .unwrap();
+        }
+
+        OceanographicProcessor::from_datasets(datasets, width as u32, height as u32)
+    }
+
+    #[test]
+    fn test_tiled_pp_matches_untiled() {
+        let (width, height) = (5, 3);
+        let processor = create_georeferenced_pp_processor(width, height);
+
+        let bbox = Bbox::new(0.0, 5.0, 0.0, 3.0).unwrap();
+        let read_all = |dataset: &Dataset| {
+            let (w, h) = dataset.raster_size();
+            dataset
+                .rasterband(1)
+                .unwrap()
+                .read_as::<f32>((0, 0), (w, h), (w, h), None)
+                .unwrap()
+                .data()
+                .to_vec()
         };
 
-        // Use Baffin Bay coordinates (same as main.rs) which should have data
-        let bbox = Bbox::new(-67.2, -58.7, 70.9, 73.3).unwrap();
+        let untiled = read_all(&processor.calculate_pp_for_bbox(&bbox).unwrap());
 
-        // Calculate PP using bbox method first - now returns Dataset
-        let bbox_dataset = processor.calculate_pp_for_bbox(&bbox).unwrap();
+        // Tiles of 2x2 do not divide the 5x3 window, leaving partial tiles on the edges
+        let tiled = read_all(&processor.calculate_pp_for_bbox_tiled(&bbox, 2).unwrap());
+
+        assert_eq!(untiled.len(), width * height);
+        assert!(untiled.iter().all(|v| *v > 0.0));
+        assert_eq!(tiled, untiled);
+
+        assert!(processor.calculate_pp_for_bbox_tiled(&bbox, 0).is_err());
+    }
+
+    #[test]
+    fn test_output_unit() {
+        let processor = create_georeferenced_pp_processor(2, 2);
+        let bbox = Bbox::new(0.0, 2.0, 0.0, 2.0).unwrap();
+
+        let pp_mg = processor.calculate_region_pp(0, 0, 2, 2).unwrap();
+
+        let processor = processor.with_output_unit(PpUnit::GCPerYear);
+        let pp_g_year = processor.calculate_region_pp(0, 0, 2, 2).unwrap();
+
+        for (mg, g_year) in pp_mg.iter().zip(&pp_g_year) {
+            assert!((mg * 0.365 - g_year).abs() < 1e-4);
+        }
+
+        let dataset = processor.calculate_pp_for_bbox(&bbox).unwrap();
+        let band = dataset.rasterband(1).unwrap();
+        assert_eq!(
+            band.metadata_item("Unit", "").as_deref(),
+            Some("g C m-2 yr-1")
+        );
+    }
+
+    #[test]
+    fn test_debug_pixel() {
+        let processor = create_georeferenced_pp_processor(5, 3);
+
+        // Pixel (2, 1) covers lon 2..3 and lat 1..2
+        let report = processor.debug_pixel(2.5, 1.5).unwrap();
+
+        assert!(report.starts_with("Coordinate (2.5, 1.5) -> pixel (2, 1)"));
+        assert!(report.contains("  chlor_a: raw 0.8"));
+        assert!(report.contains("Pixel (2, 1)"));
+        assert!(report.contains("Pbopt: Some("));
+        assert!(report.contains("PP: Some("));
+
+        assert!(processor.debug_pixel(10.0, 1.5).is_err());
+    }
+
+    #[test]
+    fn test_extract_points() {
+        let processor = create_georeferenced_pp_processor(5, 3);
+
+        // Pixels (2, 1) and (0, 2), a point outside the grid and one on the top-left corner
+        let points = [(2.5, 1.5), (0.2, 0.9), (10.0, 1.5), (0.0, 3.0)];
+
+        let pp = processor.extract_points(&points);
+        assert_eq!(pp.len(), 4);
+        assert_eq!(pp[0], processor.calculate_pixel_pp(2, 1).unwrap());
+        assert_eq!(pp[1], processor.calculate_pixel_pp(0, 2).unwrap());
+        assert!(pp[0].is_some());
+        assert_eq!(pp[2], None);
+        assert_eq!(pp[3], processor.calculate_pixel_pp(0, 0).unwrap());
+
+        let chlor_a = processor.extract_band_points("chlor_a", &points);
+        assert_eq!(
+            chlor_a,
+            vec![Some(0.8), Some(11.0_f32 * 0.1), None, Some(0.1)]
+        );
+        assert_eq!(processor.extract_band_points("par", &points), vec![None; 4]);
+
+        let processor = processor.with_output_unit(PpUnit::GCPerDay);
+        let pp_g = processor.extract_points(&points[..1]);
+        assert!((pp_g[0].unwrap() - pp[0].unwrap() * 1e-3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_extract_points_bilinear() {
+        let (width, height) = (5, 4);
+        let mut processor = create_georeferenced_pp_processor(width, height);
+
+        // Linear gradient, 10 x + y at pixel (x, y)
+        let values = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (10 * x + y) as f32))
+            .collect();
+        let mut gradient = create_mem_dataset(width, height, values);
+        gradient
+            .set_geo_transform(&[0.0, 1.0, 0.0, height as f64, 0.0, -1.0])
+            .unwrap();
+        gradient
+            .rasterband(1)
+            .unwrap()
+            .set_no_data_value(Some(-1.0))
+            .unwrap();
+        processor.datasets.insert("gradient".to_string(), gradient);
+
+        // Pixel coordinates (1.75, 2.25), i.e. 1.25 and 1.75 pixels from the center of (0, 0)
+        let point = [(1.75, height as f64 - 2.25)];
+
+        let nearest = processor.extract_band_points("gradient", &point);
+        assert_eq!(nearest, vec![Some(12.0)]);
+
+        let bilinear =
+            processor.extract_band_points_with("gradient", &point, PointSampling::Bilinear);
+        assert!((bilinear[0].unwrap() - 14.25).abs() < 1e-5);
+
+        // Pixel centers and the outer half pixel are not interpolated
+        let centers = [(2.5, height as f64 - 1.5), (0.2, height as f64 - 0.2)];
+        assert_eq!(
+            processor.extract_band_points_with("gradient", &centers, PointSampling::Bilinear),
+            vec![Some(21.0), Some(0.0)]
+        );
+
+        // PP interpolated between the pixels
+        let pp = processor.extract_points_with(&point, PointSampling::Bilinear)[0].unwrap();
+        let neighbors = [(1, 1), (2, 1), (1, 2), (2, 2)]
+            .map(|(x, y)| processor.calculate_pixel_pp(x, y).unwrap().unwrap());
+        assert!(neighbors.iter().any(|&n| n <= pp) && neighbors.iter().any(|&n| n >= pp));
+
+        // A no-data neighbor falls back to the nearest pixel
+        let mut buffer = gdal::raster::Buffer::new((1, 1), vec![-1.0_f32]);
+        processor.datasets["gradient"]
+            .rasterband(1)
+            .unwrap()
+            .write((2, 1), (1, 1), &mut buffer)
+            .unwrap();
+        assert_eq!(
+            processor.extract_band_points_with("gradient", &point, PointSampling::Bilinear),
+            vec![Some(12.0)]
+        );
+    }
+
+    #[test]
+    fn test_pp_array_matches_region_pp() {
+        let processor = create_georeferenced_pp_processor(5, 3);
+
+        // Columns 1 to 3 of the two bottom rows
+        let bbox = Bbox::new(1.0, 4.0, 0.0, 2.0).unwrap();
+        let (pp, georeference) = processor.calculate_pp_array(&bbox).unwrap();
+
+        assert_eq!(pp.dim(), (2, 3));
+        assert_eq!(georeference.geotransform, [1.0, 1.0, 0.0, 2.0, 0.0, -1.0]);
+        assert_eq!(georeference.crs_wkt, None);
+
+        let region = processor.calculate_region_pp(1, 1, 3, 2).unwrap();
+        assert_eq!(pp.iter().copied().collect::<Vec<f32>>(), region);
+        assert_eq!(pp[[1, 0]], region[3]);
+    }
+
+    #[test]
+    fn test_full_extent_bbox() {
+        let processor = create_georeferenced_pp_processor(4, 3);
+
+        let bbox = processor.full_extent_bbox().unwrap();
+        assert_eq!(
+            (bbox.xmin, bbox.xmax, bbox.ymin, bbox.ymax),
+            (0.0, 4.0, 0.0, 3.0)
+        );
+        assert!(!processor.is_bbox_clipped(&bbox).unwrap());
+        assert_eq!(processor.calculate_pp_array(&bbox).unwrap().0.dim(), (3, 4));
+    }
+
+    #[test]
+    fn test_bathymetry_skips_land_and_shallow_pixels() {
+        let mut processor = create_georeferenced_pp_processor(3, 1);
+        processor.datasets.insert(
+            BATHYMETRY_DATASET.to_string(),
+            create_mem_dataset(3, 1, vec![-500.0, -5.0, 12.0]),
+        );
+
+        let pp = processor.calculate_region_pp(0, 0, 3, 1).unwrap();
+        assert!(!pp[0].is_nan());
+        assert!(!pp[1].is_nan());
+        assert!(pp[2].is_nan());
+
+        let processor = processor.with_min_water_depth(10.0);
+        let pp = processor.calculate_region_pp(0, 0, 3, 1).unwrap();
+        assert!(!pp[0].is_nan());
+        assert!(pp[1].is_nan());
+        assert!(pp[2].is_nan());
+    }
+
+    #[test]
+    fn test_l2_flags_skip_flagged_pixels() {
+        use crate::iop::flags::{CLDICE, LAND, TURBIDW};
+
+        let driver = gdal::DriverManager::get_driver_by_name("MEM").unwrap();
+        let l2_flags = driver.create_with_band_type::<i32, _>("", 4, 1, 1).unwrap();
+        let flags = vec![0, LAND as i32, TURBIDW as i32, (CLDICE | 1 << 30) as i32];
+        l2_flags
+            .rasterband(1)
+            .unwrap()
+            .write(
+                (0, 0),
+                (4, 1),
+                &mut gdal::raster::Buffer::new((4, 1), flags),
+            )
+            .unwrap();
+
+        let mut processor = create_georeferenced_pp_processor(4, 1);
+        processor
+            .datasets
+            .insert(L2_FLAGS_DATASET.to_string(), l2_flags);
+
+        let pp = processor.calculate_region_pp(0, 0, 4, 1).unwrap();
+        assert!(!pp[0].is_nan());
+        assert!(pp[1].is_nan());
+        assert!(!pp[2].is_nan());
+        assert!(pp[3].is_nan());
+
+        let pp = processor
+            .with_l2_flags_mask(TURBIDW)
+            .calculate_region_pp(0, 0, 4, 1)
+            .unwrap();
+        assert!(!pp[1].is_nan());
+        assert!(pp[2].is_nan());
+        assert!(!pp[3].is_nan());
+    }
+
+    #[test]
+    fn test_pixel_latitude() {
+        // Top-left corner at (0, 3) with 1 degree pixels
+        let processor = create_georeferenced_pp_processor(4, 3);
+
+        assert_eq!(processor.pixel_latitude(0, 0).unwrap(), 2.5);
+        assert_eq!(processor.pixel_latitude(3, 2).unwrap(), 0.5);
+    }
+
+    #[test]
+    fn test_date_day_length() {
+        let processor = create_georeferenced_pp_processor(4, 3);
+        assert_eq!(processor.date_day_length(0, 0).unwrap(), None);
+
+        // 0.5N, about 12 hours even at the solstice
+        let processor = processor.with_date(NaiveDate::from_ymd_opt(2023, 6, 21).unwrap());
+        let day_length = processor.date_day_length(3, 2).unwrap().unwrap();
+        assert!((day_length - 12.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_detect_file_format_and_path() {
+        assert_eq!(
+            OceanographicProcessor::detect_file_format_and_path(
+                "/data/sst.nc",
+                "sea_surface_temperature"
+            ),
+            "NETCDF:/data/sst.nc:sea_surface_temperature"
+        );
+        assert_eq!(
+            OceanographicProcessor::detect_file_format_and_path("/data/chl.tif", "chlor_a"),
+            "/data/chl.tif"
+        );
+    }
+
+    #[test]
+    fn test_decode_pixel_value_nan_fill() {
+        assert_eq!(decode_pixel_value(f32::NAN, 1.0, 0.0, None), None);
+        assert_eq!(decode_pixel_value(f32::NAN, 1.0, 0.0, Some(-32767.0)), None);
+    }
+
+    #[test]
+    fn test_decode_pixel_value_integer_fill() {
+        assert_eq!(decode_pixel_value(-32767.0, 1.0, 0.0, Some(-32767.0)), None);
+        assert_eq!(
+            decode_pixel_value(12.0, 1.0, 0.0, Some(-32767.0)),
+            Some(12.0)
+        );
+    }
+
+    #[test]
+    fn test_decode_pixel_value_scaled_fill() {
+        // Fill value is compared in raw units, before scale and offset are applied
+        assert_eq!(
+            decode_pixel_value(-32767.0, 0.005, 20.0, Some(-32767.0)),
+            None
+        );
+
+        // NetCDF default fill does not round-trip exactly through f32
+        assert_eq!(
+            decode_pixel_value(9.96921e36_f32, 1.0, 0.0, Some(9.969209968386869e36)),
+            None
+        );
+
+        let value = decode_pixel_value(1000.0, 0.005, 2.0, Some(-32767.0)).unwrap();
+        assert!((value - 7.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_synthetic_pp_matches_vgpm() {
+        let dir = tempdir().unwrap();
+        let processor =
+            OceanographicProcessor::new(&test_support::write_pp_inputs(dir.path())).unwrap();
+        assert_eq!(
+            processor.get_dim(),
+            (test_support::WIDTH as u32, test_support::HEIGHT as u32)
+        );
 
-        // Get dataset reference to calculate geotransform for region method
-        let sample_dataset = processor.datasets.values().next().unwrap();
-        let geotransform = sample_dataset.geo_transform().unwrap();
+        // 0°C, 0.2 mg m-3 and Zeu = 46 m: 0.66125 * 1.54 * 0.2 * 46
+        assert!((test_support::expected_pp(0, 0) - 9.36859).abs() < 1e-4);
 
-        // Convert bbox coordinates to pixel coordinates for region method
-        let pixel_min_x = ((-67.2 - geotransform[0]) / geotransform[1]).floor() as i32;
-        let pixel_max_x = ((-58.7 - geotransform[0]) / geotransform[1]).ceil() as i32;
-        let pixel_min_y = ((73.3 - geotransform[3]) / geotransform[5]).floor() as i32;
-        let pixel_max_y = ((70.9 - geotransform[3]) / geotransform[5]).ceil() as i32;
+        for y in 0..test_support::HEIGHT {
+            for x in 0..test_support::WIDTH {
+                let pp = processor
+                    .calculate_pixel_pp(x as u32, y as u32)
+                    .unwrap()
+                    .unwrap();
+                let expected = test_support::expected_pp(x, y);
+                assert!(
+                    (pp - expected).abs() <= 1e-4 * expected,
+                    "pixel ({x}, {y}): {pp} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_region_pp_vs_bbox_pp_equivalence() {
+        let dir = tempdir().unwrap();
+        let processor =
+            OceanographicProcessor::new(&test_support::write_pp_inputs(dir.path())).unwrap();
+
+        // Pixels 1..3 x 1..2 of the synthetic inputs
+        let bbox = Bbox::new(-66.5, -65.5, 72.0, 73.0).unwrap();
+        let bbox_dataset = processor.calculate_pp_for_bbox(&bbox).unwrap();
 
-        // Ensure bounds are within dataset dimensions
-        let start_x = pixel_min_x.max(0) as u32;
-        let end_x = pixel_max_x.max(0).min(processor.width as i32) as u32;
-        let start_y = pixel_min_y.max(0) as u32;
-        let end_y = pixel_max_y.max(0).min(processor.height as i32) as u32;
+        let geotransform = test_support::GEOTRANSFORM;
+        let start_x = ((bbox.xmin - geotransform[0]) / geotransform[1]).floor() as u32;
+        let end_x = ((bbox.xmax - geotransform[0]) / geotransform[1]).ceil() as u32;
+        let start_y = ((bbox.ymax - geotransform[3]) / geotransform[5]).floor() as u32;
+        let end_y = ((bbox.ymin - geotransform[3]) / geotransform[5]).ceil() as u32;
+        assert_eq!((start_x, end_x, start_y, end_y), (1, 3, 1, 3));
 
-        // Calculate PP using region method
         let region_results = processor
             .calculate_region_pp(start_x, start_y, end_x - start_x, end_y - start_y)
             .unwrap();
 
-        // Read data from bbox dataset for comparison
-        let bbox_band = bbox_dataset.rasterband(1).unwrap();
         let (width, height) = bbox_dataset.raster_size();
-        let bbox_data = bbox_band
+        assert_eq!((width, height), (2, 2));
+        let bbox_results = bbox_dataset
+            .rasterband(1)
+            .unwrap()
             .read_as::<f32>((0, 0), (width, height), (width, height), None)
-            .unwrap();
-        let bbox_results: Vec<f32> = bbox_data.data().to_vec();
+            .unwrap()
+            .data()
+            .to_vec();
 
-        // Results should be identical
         assert_eq!(region_results.len(), bbox_results.len());
-
-        // Compare each value with small tolerance for floating point precision
-        for (region_val, bbox_val) in region_results.iter().zip(bbox_results.iter()) {
+        for (index, (region_val, bbox_val)) in region_results.iter().zip(&bbox_results).enumerate()
+        {
             assert!(
                 (region_val - bbox_val).abs() < 1e-6,
                 "Values differ: region={}, bbox={}",
                 region_val,
                 bbox_val
             );
+
+            let expected = test_support::expected_pp(1 + index % width, 1 + index / width);
+            assert!((bbox_val - expected).abs() <= 1e-4 * expected);
         }
     }
 
     #[test]
     fn test_bbox_coordinate_conversion() {
-        let rasters = create_mock_data();
-        let processor = match OceanographicProcessor::new(&rasters) {
-            Ok(p) => p,
-            Err(_) => return,
-        };
-
-        // Use a smaller area within Baffin Bay that should have data
-        let bbox = Bbox::new(-67.0, -60.0, 71.0, 72.0).unwrap();
+        let dir = tempdir().unwrap();
+        let processor =
+            OceanographicProcessor::new(&test_support::write_pp_inputs(dir.path())).unwrap();
 
+        // Not aligned on the 0.5 degree grid, the output covers the pixels it intersects
+        let bbox = Bbox::new(-66.8, -65.2, 72.3, 73.2).unwrap();
         let bbox_dataset = processor.calculate_pp_for_bbox(&bbox).unwrap();
+        assert_eq!(bbox_dataset.raster_size(), (4, 3));
 
-        // Get dataset reference to calculate corresponding pixel coordinates
-        let sample_dataset = processor.datasets.values().next().unwrap();
-        let geotransform = sample_dataset.geo_transform().unwrap();
-
-        // Convert bbox coordinates to pixel coordinates for region method
-        let pixel_min_x = ((-67.0 - geotransform[0]) / geotransform[1]).floor() as i32;
-        let pixel_max_x = ((-60.0 - geotransform[0]) / geotransform[1]).ceil() as i32;
-        let pixel_min_y = ((72.0 - geotransform[3]) / geotransform[5]).floor() as i32;
-        let pixel_max_y = ((71.0 - geotransform[3]) / geotransform[5]).ceil() as i32;
-
-        // Ensure bounds are within dataset dimensions
-        let start_x = pixel_min_x.max(0) as u32;
-        let end_x = pixel_max_x.max(0).min(processor.width as i32) as u32;
-        let start_y = pixel_min_y.max(0) as u32;
-        let end_y = pixel_max_y.max(0).min(processor.height as i32) as u32;
-
-        let region_results = processor
-            .calculate_region_pp(start_x, start_y, end_x - start_x, end_y - start_y)
-            .unwrap();
+        let geotransform = bbox_dataset.geo_transform().unwrap();
+        assert_eq!(geotransform, test_support::GEOTRANSFORM);
 
-        // Read data from bbox dataset
-        let bbox_band = bbox_dataset.rasterband(1).unwrap();
-        let (width, height) = bbox_dataset.raster_size();
-        let bbox_data = bbox_band
-            .read_as::<f32>((0, 0), (width, height), (width, height), None)
-            .unwrap();
-        let bbox_results: Vec<f32> = bbox_data.data().to_vec();
+        let region_results = processor.calculate_region_pp(0, 0, 4, 3).unwrap();
+        let bbox_results = bbox_dataset
+            .rasterband(1)
+            .unwrap()
+            .read_as::<f32>((0, 0), (4, 3), (4, 3), None)
+            .unwrap()
+            .data()
+            .to_vec();
 
-        // Should produce similar number of results
-        let diff = (bbox_results.len() as i32 - region_results.len() as i32).abs();
-        assert!(
-            bbox_results.len() == region_results.len(),
-            "The number of produced PP values are not the same: bbox_results.len() = {}, region_results.len() = {}, diff = {}",
-            bbox_results.len(),
-            region_results.len(),
-            diff
-        )
+        assert_eq!(bbox_results.len(), region_results.len());
     }
 }
@@ -1,10 +1,20 @@
+use crate::iop::constants;
+use serde::Deserialize;
 use std::fmt::Display;
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum Satellites {
+    #[default]
     SeaWiFS,
     Modis,
+    Meris,
+}
+
+impl Satellites {
+    /// Every supported sensor
+    pub const ALL: [Satellites; 3] = [Satellites::SeaWiFS, Satellites::Modis, Satellites::Meris];
 }
 
 #[allow(dead_code)]
@@ -21,6 +31,8 @@ impl SatBands {
             Satellites::SeaWiFS => &[412, 443, 490, 510, 555, 670],
             // Bands 8, 9, 10, 11, 12 and 13
             Satellites::Modis => &[412, 443, 488, 531, 547, 667],
+            // Bands 1, 2, 3, 4, 5 and 7
+            Satellites::Meris => &[413, 443, 490, 510, 560, 665],
         };
         Self {
             sensor,
@@ -33,6 +45,13 @@ impl SatBands {
         self.wavelengths
     }
 
+    /// Coefficients of the QAA v6 reference absorption polynomial for this sensor. OCSSW has a
+    /// single set, fitted at 555nm, which the IOCCG QAA notes allow for the 547nm (MODIS) and
+    /// 560nm (MERIS) green bands as well.
+    pub fn qaa_acoefs(&self) -> [f64; 3] {
+        constants::QAA_ACOEFS_555
+    }
+
     pub fn closest_band(&self, target: u32) -> u32 {
         self.wavelengths
             .iter()
@@ -47,6 +66,7 @@ impl Display for Satellites {
         match self {
             Satellites::SeaWiFS => write!(f, "SeaWiFS"),
             Satellites::Modis => write!(f, "MODIS"),
+            Satellites::Meris => write!(f, "MERIS"),
         }
     }
 }
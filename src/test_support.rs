@@ -0,0 +1,146 @@
+//! Synthetic inputs for the tests
+//!
+//! Tiny GeoTIFFs with known geotransforms and values, written to a temporary directory, so that
+//! the processing paths are exercised without the satellite data files of `./data`.
+
+use chrono::NaiveDate;
+use gdal::spatial_ref::SpatialRef;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::bbox::Bbox;
+use crate::config::{Config, ConfigBuilder, RasterFile, TimeStep};
+
+/// Size (pixels) of the synthetic PP inputs
+pub(crate) const WIDTH: usize = 4;
+pub(crate) const HEIGHT: usize = 3;
+
+/// Geotransform of the synthetic PP inputs, 0.5 degree pixels over Baffin Bay
+/// (-67, -65, 72, 73.5)
+pub(crate) const GEOTRANSFORM: [f64; 6] = [-67.0, 0.5, 0.0, 73.5, 0.0, -0.5];
+
+/// Kd(490) (m^-1) of every pixel, i.e. a 46 m euphotic depth
+pub(crate) const KD_490: f32 = 0.1;
+
+/// Writes a single band Float32 GeoTIFF in EPSG:4326 with `values` in row-major order
+pub(crate) fn write_geotiff(
+    path: &Path,
+    (width, height): (usize, usize),
+    geotransform: &[f64; 6],
+    values: Vec<f32>,
+) {
+    let driver = gdal::DriverManager::get_driver_by_name("GTiff").unwrap();
+    let mut dataset = driver
+        .create_with_band_type::<f32, _>(path, width, height, 1)
+        .unwrap();
+
+    dataset.set_geo_transform(geotransform).unwrap();
+    dataset
+        .set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+        .unwrap();
+
+    let mut buffer = gdal::raster::Buffer::new((width, height), values);
+    dataset
+        .rasterband(1)
+        .unwrap()
+        .write((0, 0), (width, height), &mut buffer)
+        .unwrap();
+}
+
+/// Chlorophyll (mg m^-3) of the synthetic pixel (x, y), from 0.2 to 2.4
+pub(crate) fn chlor_a(x: usize, y: usize) -> f32 {
+    0.2 * (1 + x + y * WIDTH) as f32
+}
+
+/// SST (°C) of the synthetic pixel (x, y), 0, 2 and 4 on the successive rows
+pub(crate) fn sst(_x: usize, y: usize) -> f32 {
+    2.0 * y as f32
+}
+
+/// Writes the `chlor_a`, `sst` and `kd_490` GeoTIFFs of the synthetic PP inputs to `dir` and
+/// returns their paths by raster name, as given to `OceanographicProcessor::new`
+pub(crate) fn write_pp_inputs(dir: &Path) -> HashMap<String, String> {
+    let pixels = || (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x, y)));
+
+    let rasters = [
+        ("chlor_a", pixels().map(|(x, y)| chlor_a(x, y)).collect()),
+        ("sst", pixels().map(|(x, y)| sst(x, y)).collect()),
+        ("kd_490", vec![KD_490; WIDTH * HEIGHT]),
+    ];
+
+    rasters
+        .into_iter()
+        .map(|(name, values)| {
+            let path = dir.join(format!("{}.tif", name));
+            write_geotiff(&path, (WIDTH, HEIGHT), &GEOTRANSFORM, values);
+            (name.to_string(), path.display().to_string())
+        })
+        .collect()
+}
+
+/// PP (mg C m^-2 d^-1) of the synthetic pixel (x, y), written out from the simplified VGPM:
+/// `0.66125 * 1.54 * 10^(0.0275 T - 0.07 T^2 + 0.0025 T^3) * Chl * 4.6 / Kd(490)`
+pub(crate) fn expected_pp(x: usize, y: usize) -> f32 {
+    let t = sst(x, y);
+    let pb_opt = 1.54 * 10_f32.powf(0.0275 * t - 0.07 * t * t + 0.0025 * t * t * t);
+
+    0.66125 * pb_opt * chlor_a(x, y) * 4.6 / KD_490
+}
+
+/// Valid daily configuration of `test_model` from 2023-01-01 to 2023-01-03, every 6 hours over
+/// (0, 1, 0, 1) and writing to `/tmp`, without raster templates
+pub(crate) fn test_builder() -> ConfigBuilder {
+    Config::builder()
+        .model_id("test_model")
+        .start_date(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+        .end_date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap())
+        .frequency(TimeStep::Daily)
+        .hourly_increment(6)
+        .bbox(Bbox::new(0.0, 1.0, 0.0, 1.0).unwrap())
+        .output_directory("/tmp")
+}
+
+/// Raster template `name` of the `YYYYMMDD` dated files matching `pattern` in `/tmp`, without
+/// NetCDF variable, band or scaling overrides
+pub(crate) fn raster_template(name: &str, pattern: &str) -> RasterFile {
+    RasterFile {
+        name: name.to_string(),
+        base_directory: "/tmp".to_string(),
+        filename_pattern: pattern.to_string(),
+        date_format: "YYYYMMDD".to_string(),
+        netcdf_variable: None,
+        max_depth: None,
+        band: None,
+        scale: None,
+        offset: None,
+        nodata: None,
+    }
+}
+
+/// Writes 2 x 2 pixel `chl_{date}.tif`, `sst_{date}.tif` and `kd_{date}.tif` GeoTIFFs of 0.5
+/// degree over (0, 1, 0, 1), with a chlor_a of 1, an SST of 5 and a Kd(490) of 0.1 everywhere
+pub(crate) fn write_test_inputs(dir: &Path, date: &str) {
+    for (name, value) in [("chl", 1.0), ("sst", 5.0), ("kd", 0.1)] {
+        write_geotiff(
+            &dir.join(format!("{}_{}.tif", name, date)),
+            (2, 2),
+            &[0.0, 0.5, 0.0, 1.0, 0.0, -0.5],
+            vec![value; 4],
+        );
+    }
+}
+
+/// `test_builder` reading the inputs of `write_test_inputs` from `dir` and writing to `dir`
+pub(crate) fn test_inputs_config(dir: &Path) -> ConfigBuilder {
+    let template = |name: &str, prefix: &str| RasterFile {
+        base_directory: dir.display().to_string(),
+        ..raster_template(name, &format!("{}_{{}}.tif", prefix))
+    };
+
+    test_builder()
+        .model_id("Test_inputs")
+        .raster_template(template("chlor_a", "chl"))
+        .raster_template(template("sst", "sst"))
+        .raster_template(template("kd_490", "kd"))
+        .output_directory(dir.display().to_string())
+}
@@ -1,4 +1,133 @@
-use gdal::Dataset;
+use gdal::{Dataset, Metadata};
+use serde::Serialize;
+
+/// Summary statistics of a raster band. NaN values are ignored; every statistic is NaN when the
+/// band has no valid value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Stats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub std: f32,
+    pub p10: f32,
+    pub p50: f32,
+    pub p90: f32,
+    pub valid: usize,
+    pub total: usize,
+}
+
+impl Stats {
+    pub fn from_values(values: &[f32]) -> Self {
+        let mut valid_values: Vec<f32> = values.iter().filter(|v| !v.is_nan()).cloned().collect();
+        valid_values.sort_by(|a, b| a.total_cmp(b));
+
+        let valid = valid_values.len();
+
+        let (mean, std) = if valid == 0 {
+            (f32::NAN, f32::NAN)
+        } else {
+            let mean = valid_values.iter().sum::<f32>() / valid as f32;
+            let variance =
+                valid_values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / valid as f32;
+            (mean, variance.sqrt())
+        };
+
+        Self {
+            min: valid_values.first().copied().unwrap_or(f32::NAN),
+            max: valid_values.last().copied().unwrap_or(f32::NAN),
+            mean,
+            std,
+            p10: percentile(&valid_values, 10.0),
+            p50: percentile(&valid_values, 50.0),
+            p90: percentile(&valid_values, 90.0),
+            valid,
+            total: values.len(),
+        }
+    }
+}
+
+// Percentile (0-100) of sorted values, linearly interpolated between the closest ranks
+fn percentile(sorted_values: &[f32], p: f32) -> f32 {
+    if sorted_values.is_empty() {
+        return f32::NAN;
+    }
+
+    let rank = p / 100.0 * (sorted_values.len() - 1) as f32;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f32;
+
+    sorted_values[lower] + (sorted_values[upper] - sorted_values[lower]) * weight
+}
+
+/// Computes the statistics of the first band of a dataset. Pixels equal to the band no-data value
+/// are ignored like NaN values.
+pub fn dataset_stats(dataset: &Dataset) -> Result<Stats, Box<dyn std::error::Error>> {
+    let band = dataset.rasterband(1)?;
+    let (width, height) = dataset.raster_size();
+    let buffer = band.read_as::<f32>((0, 0), (width, height), (width, height), None)?;
+
+    let values: Vec<f32> = match band.no_data_value() {
+        Some(nodata) if !nodata.is_nan() => buffer
+            .data()
+            .iter()
+            .map(|&v| if v == nodata as f32 { f32::NAN } else { v })
+            .collect(),
+        _ => buffer.data().to_vec(),
+    };
+
+    Ok(Stats::from_values(&values))
+}
+
+/// Subdataset (variable) of a multi-variable file such as NetCDF or HDF
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subdataset {
+    /// GDAL path of the subdataset, e.g. `NETCDF:"file.nc":chlor_a`
+    pub name: String,
+    /// GDAL description, e.g. `[2160x4320] chlor_a (32-bit floating-point)`
+    pub description: String,
+}
+
+impl Subdataset {
+    /// Variable name, i.e. the last component of the GDAL path (`chlor_a`)
+    pub fn variable(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or(&self.name)
+    }
+}
+
+/// Lists the subdatasets of a NetCDF or HDF file, to find the variable names used in
+/// `raster_templates`. Returns an empty list for single-variable files.
+pub fn list_subdatasets(path: &str) -> Result<Vec<Subdataset>, Box<dyn std::error::Error>> {
+    let dataset = Dataset::open(path)?;
+    let entries = dataset.metadata_domain("SUBDATASETS").unwrap_or_default();
+
+    Ok(parse_subdatasets(&entries))
+}
+
+// Pairs the SUBDATASET_<n>_NAME=... and SUBDATASET_<n>_DESC=... metadata entries
+fn parse_subdatasets(entries: &[String]) -> Vec<Subdataset> {
+    let mut subdatasets = Vec::new();
+
+    for index in 1.. {
+        let value = |suffix: &str| {
+            let key = format!("SUBDATASET_{}_{}=", index, suffix);
+            entries
+                .iter()
+                .find_map(|entry| entry.strip_prefix(&key).map(str::to_string))
+        };
+
+        let Some(name) = value("NAME") else {
+            break;
+        };
+
+        subdatasets.push(Subdataset {
+            name,
+            description: value("DESC").unwrap_or_default(),
+        });
+    }
+
+    subdatasets
+}
 
 #[allow(dead_code)]
 pub fn print_dataset_statistics(datasets: &[Dataset]) -> Result<(), Box<dyn std::error::Error>> {
@@ -16,53 +145,87 @@ pub fn print_dataset_statistics(datasets: &[Dataset]) -> Result<(), Box<dyn std:
         total_pp_count
     );
 
-    if let Some(first_dataset) = datasets.first() {
-        let band = first_dataset.rasterband(1)?;
-        let (width, height) = first_dataset.raster_size();
-        let buffer = band.read_as::<f32>((0, 0), (width, height), (width, height), None)?;
-        let all_values: Vec<f32> = buffer.data().to_vec();
+    for (index, dataset) in datasets.iter().enumerate() {
+        let stats = dataset_stats(dataset)?;
 
-        if !all_values.is_empty() {
-            let valid_values: Vec<f32> = all_values
-                .iter()
-                .filter(|&&v| !v.is_nan())
-                .cloned()
-                .collect();
-
-            println!(
-                "  Min: {:.2} mg C m−2 d−1",
-                valid_values.iter().fold(f32::INFINITY, |a, &b| a.min(b))
-            );
-            println!(
-                "  Max: {:.2} mg C m−2 d−1",
-                valid_values
-                    .iter()
-                    .fold(f32::NEG_INFINITY, |a, &b| a.max(b))
-            );
-            println!(
-                "  Mean: {:.2} mg C m−2 d−1",
-                if valid_values.is_empty() {
-                    f32::NAN
-                } else {
-                    valid_values.iter().sum::<f32>() / valid_values.len() as f32
-                }
-            );
-            println!(
-                "  Valid pixels: {} / {} ({:.1}%)",
-                valid_values.len(),
-                all_values.len(),
-                100.0 * valid_values.len() as f32 / all_values.len() as f32
-            );
-            println!(
-                "  First 10 non NaN values: {:?}",
-                all_values
-                    .iter()
-                    .filter(|pp| !pp.is_nan())
-                    .take(10)
-                    .collect::<Vec<&f32>>()
-            );
+        if stats.total == 0 {
+            continue;
         }
+
+        let unit = dataset
+            .rasterband(1)?
+            .metadata_item("Unit", "")
+            .unwrap_or_else(|| "mg C m-2 d-1".to_string());
+
+        println!("Dataset {}:", index + 1);
+        println!("  Min: {:.2} {}", stats.min, unit);
+        println!("  Max: {:.2} {}", stats.max, unit);
+        println!("  Mean: {:.2} {}", stats.mean, unit);
+        println!("  Std: {:.2} {}", stats.std, unit);
+        println!(
+            "  P10 / P50 / P90: {:.2} / {:.2} / {:.2} {}",
+            stats.p10, stats.p50, stats.p90, unit
+        );
+        println!(
+            "  Valid pixels: {} / {} ({:.1}%)",
+            stats.valid,
+            stats.total,
+            100.0 * stats.valid as f32 / stats.total as f32
+        );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_ignore_nan() {
+        let stats = Stats::from_values(&[f32::NAN, 4.0, 1.0, 3.0, 2.0, 5.0, f32::NAN]);
+
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 5.0);
+        assert_eq!(stats.mean, 3.0);
+        assert!((stats.std - 2.0_f32.sqrt()).abs() < 1e-6);
+        assert!((stats.p10 - 1.4).abs() < 1e-6);
+        assert_eq!(stats.p50, 3.0);
+        assert!((stats.p90 - 4.6).abs() < 1e-6);
+        assert_eq!(stats.valid, 5);
+        assert_eq!(stats.total, 7);
+    }
+
+    #[test]
+    fn test_parse_subdatasets() {
+        let entries = [
+            "SUBDATASET_1_NAME=NETCDF:\"chl.nc\":chlor_a",
+            "SUBDATASET_1_DESC=[2160x4320] chlor_a (32-bit floating-point)",
+            "SUBDATASET_2_NAME=NETCDF:\"chl.nc\":palette",
+            "SUBDATASET_2_DESC=[3x256] palette (8-bit unsigned integer)",
+        ]
+        .map(String::from);
+
+        let subdatasets = parse_subdatasets(&entries);
+
+        assert_eq!(subdatasets.len(), 2);
+        assert_eq!(subdatasets[0].name, "NETCDF:\"chl.nc\":chlor_a");
+        assert_eq!(subdatasets[0].variable(), "chlor_a");
+        assert_eq!(
+            subdatasets[1].description,
+            "[3x256] palette (8-bit unsigned integer)"
+        );
+        assert!(parse_subdatasets(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_stats_all_nan() {
+        let stats = Stats::from_values(&[f32::NAN; 3]);
+
+        assert!(stats.min.is_nan());
+        assert!(stats.mean.is_nan());
+        assert!(stats.p50.is_nan());
+        assert_eq!(stats.valid, 0);
+        assert_eq!(stats.total, 3);
+    }
+}